@@ -4,5 +4,13 @@ import type { ArchiveAlgo } from "./ArchiveAlgo";
 export type ArchiveConfig = {
   algorithm: ArchiveAlgo;
   level: number;
+  /**
+   * Archive the current saves before restoring an archive over them.
+   */
   backupBeforeRestore: boolean;
+  /**
+   * Delete files that are not in the archive when restoring, instead of
+   * only overwriting the ones that are.
+   */
+  mirrorRestore: boolean;
 };
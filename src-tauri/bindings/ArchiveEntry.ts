@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A file or dir inside an archive.
+ */
+export type ArchiveEntry = {
+  /**
+   * `/`-separated path in the archive. The first component is the
+   * [`SavePath::name`] of the save path it belongs to.
+   */
+  path: string;
+  isDir: boolean;
+  size: bigint;
+  /**
+   * Unix timestamp in seconds.
+   */
+  mtime: bigint;
+};
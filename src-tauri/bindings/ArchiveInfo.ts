@@ -1,3 +1,17 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ArchiveAlgo } from "./ArchiveAlgo";
+import type { ArchiveMeta } from "./ArchiveMeta";
 
-export type ArchiveInfo = { name: string; size: bigint };
+export type ArchiveInfo = {
+  name: string;
+  size: bigint;
+  /**
+   * Detected format, only reported for local archives.
+   */
+  format?: ArchiveAlgo;
+  /**
+   * `None` if the archive has no metadata and its filename is not in the
+   * format of older versions either.
+   */
+  meta?: ArchiveMeta;
+};
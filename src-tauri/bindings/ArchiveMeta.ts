@@ -0,0 +1,35 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ArchiveAlgo } from "./ArchiveAlgo";
+import type { ArchiveTrigger } from "./ArchiveTrigger";
+
+export type ArchiveMeta = {
+  createdAt: string;
+  /**
+   * Uid of the device the archive was created on, empty if unknown.
+   */
+  deviceUid: string;
+  deviceName: string;
+  algorithm: ArchiveAlgo;
+  /**
+   * Play time of the game when the archive was created. [secs, nanos]
+   */
+  useTime: [number, number];
+  trigger: ArchiveTrigger;
+  /**
+   * User note.
+   */
+  note?: string;
+  /**
+   * Pinned archives are never pruned.
+   */
+  pinned?: boolean;
+  /**
+   * Fingerprint of the saves by mtime when the archive was created.
+   */
+  fingerprint?: string;
+  /**
+   * Fingerprint of the saves by content when the archive was created, the
+   * same for the same saves on every device.
+   */
+  contentHash?: string;
+};
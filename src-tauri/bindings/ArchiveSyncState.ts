@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Where an archive is, and whether both copies agree.
+ */
+export type ArchiveSyncState =
+  | "localOnly"
+  | "remoteOnly"
+  | "identical"
+  | "different";
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ArchiveSyncState } from "./ArchiveSyncState";
+
+export type ArchiveSyncStatus = {
+  name: string;
+  state: ArchiveSyncState;
+  localSize?: bigint;
+  /**
+   * Size of the remote copy, which may be compressed or encrypted
+   * differently from the local one.
+   */
+  remoteSize?: bigint;
+  /**
+   * Whether the archive is a snapshot taken while the game was running.
+   * These are never uploaded by [`reconcile`](crate::bindings::reconcile).
+   */
+  inSession: boolean;
+};
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * What an archive was created for.
+ */
+export type ArchiveTrigger =
+  | "unknown"
+  | "manual"
+  | "autoUpload"
+  | "preRestore"
+  | "inSession";
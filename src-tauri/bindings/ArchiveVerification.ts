@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ChecksumStatus } from "./ChecksumStatus";
+
+/**
+ * Checksum status of a local or remote archive of a game.
+ */
+export type ArchiveVerification = {
+  name: string;
+  remote: boolean;
+  status: ChecksumStatus;
+};
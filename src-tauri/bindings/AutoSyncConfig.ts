@@ -0,0 +1,24 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * When the config is synced with remote in the background, besides every
+ * [`Settings::auto_sync_interval`].
+ */
+export type AutoSyncConfig = {
+  /**
+   * Apply the remote config on startup, if it is newer.
+   */
+  onStartup: boolean;
+  /**
+   * Upload the config when the window is closed to the tray.
+   */
+  onMinimize: boolean;
+  /**
+   * Upload the config after a game exits.
+   */
+  onGameExit: boolean;
+  /**
+   * Upload the config when quitting from the tray.
+   */
+  onQuit: boolean;
+};
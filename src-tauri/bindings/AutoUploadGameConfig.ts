@@ -3,15 +3,69 @@ import type { RetentionScope } from "./RetentionScope";
 
 /**
  * Per-game config for the AutoUpload plugin.
+ *
+ * An archive is kept if any of the rules below keeps it, and pinned archives
+ * are always kept. Nothing is pruned if all the numbers are `0`.
  */
 export type AutoUploadGameConfig = {
   /**
-   * Max number of archives to keep for this game; the oldest are evicted
-   * (末位淘汰) once exceeded. `0` means unlimited.
+   * Number of the newest archives to keep for this game; the older ones
+   * are evicted (末位淘汰) unless kept by the rules below.
    */
   maxKept: number;
+  /**
+   * Also keep the newest archive of each of the last `keep_daily` days
+   * that have archives.
+   */
+  keepDaily: number;
+  /**
+   * Also keep the newest archive of each of the last `keep_weekly` weeks
+   * that have archives.
+   */
+  keepWeekly: number;
+  /**
+   * Also keep the newest archive of each of the last `keep_monthly` months
+   * that have archives.
+   */
+  keepMonthly: number;
   /**
    * Where the retention policy is enforced.
    */
   retentionScope: RetentionScope;
+  /**
+   * Tell whether the saves changed since the last archive by their content
+   * instead of their mtime. Slower, but not fooled by games that rewrite
+   * their saves without changing them.
+   */
+  hashContents: boolean;
+  /**
+   * Before the game starts, pull and restore the newest remote archive if
+   * it is newer than the local saves. If the local saves changed since the
+   * last sync too, the game is not started.
+   */
+  pullBeforeLaunch: boolean;
+  /**
+   * Take local snapshots of the saves while the game is running, once the
+   * writes to them go quiet.
+   */
+  sessionSnapshots: boolean;
+  /**
+   * Seconds without writes to the saves before a snapshot is taken.
+   */
+  sessionQuietSecs: number;
+  /**
+   * Also take a snapshot every `session_interval_mins` minutes if the saves
+   * changed, `0` for never.
+   */
+  sessionIntervalMins: number;
+  /**
+   * Most snapshots to take while the game is running once, `0` for no
+   * limit.
+   */
+  sessionMaxSnapshots: number;
+  /**
+   * Number of the newest in-session snapshots to keep locally. They are
+   * pruned apart from the archives taken on exit; `0` keeps all of them.
+   */
+  sessionMaxKept: number;
 };
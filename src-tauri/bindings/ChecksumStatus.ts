@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Result of checking an archive against its checksum.
+ */
+export type ChecksumStatus = "ok" | "missing" | "mismatch";
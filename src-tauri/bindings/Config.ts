@@ -3,18 +3,28 @@ import type { Device } from "./Device";
 import type { Game } from "./Game";
 import type { PluginMetadatas } from "./PluginMetadatas";
 import type { Settings } from "./Settings";
+import type { VersionVector } from "./VersionVector";
 
 export type Config = {
   dbVersion: number;
   /**
-   * The last time the config was updated from frontend
+   * The last time the config was updated from frontend. Only for display,
+   * sync compares [`revision`](Self::revision) instead.
    */
   lastUpdated: string;
   /**
    * The last time the config was uploaded to remote or downloaded from
-   * remote
+   * remote. Only for display.
    */
   lastSync: string | null;
+  /**
+   * Writes to the config, counted per device.
+   */
+  revision: VersionVector;
+  /**
+   * [`revision`](Self::revision) of the config at the last sync.
+   */
+  syncedRevision: VersionVector;
   lastUploaded: string | null;
   games: Array<Game>;
   devices: Array<Device>;
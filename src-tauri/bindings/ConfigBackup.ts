@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BackupReason } from "./BackupReason";
+
+export type ConfigBackup = {
+  name: string;
+  /**
+   * Local time the backup was taken.
+   */
+  createdAt: string;
+  reason: BackupReason;
+  size: bigint;
+};
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A field that was changed differently on both sides since the last sync.
+ *
+ * Values are rendered as TOML; `None` means the item does not exist on that
+ * side (e.g. a game deleted on one device and edited on the other).
+ */
+export type ConfigConflict = {
+  /**
+   * Dotted path of the field, e.g. `games.42.savePaths` or
+   * `settings.storage`. Used as the key of a resolution.
+   */
+  path: string;
+  base: string | null;
+  local: string | null;
+  remote: string | null;
+};
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConfigSyncOutcome } from "./ConfigSyncOutcome";
+import type { SyncTrigger } from "./SyncTrigger";
+
+export type ConfigSyncEvent = {
+  /**
+   * Increases with every sync, to tell events apart.
+   */
+  id: number;
+  trigger: SyncTrigger;
+  outcome: ConfigSyncOutcome;
+};
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Config } from "./Config";
+
+export type ConfigSyncOutcome =
+  | { "kind": "uploaded" }
+  | { "kind": "merged" }
+  | { "kind": "localClean" }
+  | { "kind": "conflict" }
+  | { "kind": "applied"; previous: Config }
+  | { "kind": "upToDate" }
+  | { "kind": "failed"; error: string };
@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Client-side encryption of archives and config uploaded to remote.
+ */
+export type EncryptionConfig = {
+  /**
+   * Encrypt archives and config before uploading. Encrypted objects are
+   * always decrypted on download, regardless of this switch.
+   */
+  enabled: boolean;
+  passphrase: string | null;
+};
@@ -0,0 +1,17 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { FileChangeKind } from "./FileChangeKind";
+import type { FileState } from "./FileState";
+
+export type FileChange = {
+  /**
+   * See [`ArchiveEntry::path`].
+   */
+  path: string;
+  kind: FileChangeKind;
+  old: FileState | null;
+  new: FileState | null;
+  /**
+   * Unified diff, only for small text files that were modified.
+   */
+  textDiff?: string;
+};
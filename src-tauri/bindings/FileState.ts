@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type FileState = {
+  size: bigint;
+  /**
+   * Unix timestamp in seconds.
+   */
+  mtime: bigint;
+  /**
+   * SHA-256 of the content, hex encoded.
+   */
+  hash: string;
+};
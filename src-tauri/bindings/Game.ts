@@ -1,11 +1,12 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { PluginInstance } from "./PluginInstance";
+import type { SavePathConfig } from "./SavePathConfig";
 
 export type Game = {
   id: number;
   name: string;
   excutablePath: string | null;
-  savePaths: Array<string>;
+  savePaths: Array<SavePathConfig>;
   imageUrl: string | null;
   imageSha256: string | null;
   addedTime: string;
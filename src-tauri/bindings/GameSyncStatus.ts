@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ArchiveSyncStatus } from "./ArchiveSyncStatus";
+import type { SyncTotal } from "./SyncTotal";
+
+export type GameSyncStatus = {
+  gameId: number;
+  /**
+   * Sorted by name.
+   */
+  archives: Array<ArchiveSyncStatus>;
+  localOnly: SyncTotal;
+  remoteOnly: SyncTotal;
+  identical: SyncTotal;
+  different: SyncTotal;
+};
@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConfigConflict } from "./ConfigConflict";
+
+/**
+ * Outcome of a three-way merge with the remote config.
+ */
+export type MergeOutcome = {
+  /**
+   * Whether the merged config was applied locally and uploaded.
+   */
+  applied: boolean;
+  /**
+   * Conflicts left unresolved; not empty if and only if not applied.
+   */
+  conflicts: Array<ConfigConflict>;
+};
@@ -0,0 +1,17 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Outcome of copying an archive while reconciling.
+ */
+export type ReconcileResult = {
+  gameId: number;
+  name: string;
+  /**
+   * `true` if uploaded, `false` if pulled.
+   */
+  uploaded: boolean;
+  /**
+   * The error if copying failed.
+   */
+  error?: string;
+};
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * How the config is stored on the remote. The remote is moved to this
+ * layout on the next upload.
+ */
+export type RemoteLayout = "single" | "split";
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Archives the retention policy of a game would delete, from the oldest to
+ * the newest.
+ */
+export type RetentionPreview = { local: Array<string>; remote: Array<string> };
@@ -0,0 +1,17 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SyncRecord } from "./SyncRecord";
+
+/**
+ * Saves of a game that diverged between two devices.
+ */
+export type SaveConflict = {
+  gameId: number;
+  /**
+   * The saves of this device.
+   */
+  local: SyncRecord;
+  /**
+   * The saves of the other device.
+   */
+  remote: SyncRecord;
+};
@@ -0,0 +1,21 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A save path of a game and which files in it to archive.
+ */
+export type SavePathConfig = {
+  /**
+   * Path template, may contain vars.
+   */
+  path: string;
+  /**
+   * Glob patterns relative to the save path. If any, only the files
+   * matching one of them are archived.
+   */
+  include?: Array<string>;
+  /**
+   * Glob patterns relative to the save path of files and dirs that are
+   * never archived. Takes precedence over `include`.
+   */
+  exclude?: Array<string>;
+};
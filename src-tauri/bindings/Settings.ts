@@ -1,6 +1,7 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { AppearanceConfig } from "./AppearanceConfig";
 import type { ArchiveConfig } from "./ArchiveConfig";
+import type { AutoSyncConfig } from "./AutoSyncConfig";
 import type { LaunchConfig } from "./LaunchConfig";
 import type { StorageConfig } from "./StorageConfig";
 
@@ -13,6 +14,7 @@ export type Settings = {
    * in secs
    */
   autoSyncInterval: number;
+  autoSync: AutoSyncConfig;
   /**
    * IO timeout for remote sync operations (upload/download), in seconds.
    */
@@ -22,4 +24,8 @@ export type Settings = {
    * seconds.
    */
   syncNonIoTimeoutSecs: number;
+  /**
+   * Number of daily remote config snapshots to keep, 0 keeps all.
+   */
+  configSnapshotRetention: number;
 };
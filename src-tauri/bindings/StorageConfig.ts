@@ -1,5 +1,7 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { EncryptionConfig } from "./EncryptionConfig";
 import type { LocalConfig } from "./LocalConfig";
+import type { RemoteLayout } from "./RemoteLayout";
 import type { S3Config } from "./S3Config";
 import type { StorageProvider } from "./StorageProvider";
 import type { WebDavConfig } from "./WebDavConfig";
@@ -9,4 +11,6 @@ export type StorageConfig = {
   local: LocalConfig;
   webdav: WebDavConfig;
   s3: S3Config;
+  encryption: EncryptionConfig;
+  layout: RemoteLayout;
 };
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * An archive a device uploaded or restored.
+ */
+export type SyncRecord = {
+  deviceUid: string;
+  deviceName: string;
+  archive: string;
+  /**
+   * See [`ArchiveMeta::content_hash`].
+   */
+  contentHash?: string;
+  at: string;
+};
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Number and total size of archives in a state. Archives on both sides count
+ * with their local size.
+ */
+export type SyncTotal = { count: number; size: bigint };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * What started a background sync.
+ */
+export type SyncTrigger =
+  | "startup"
+  | "interval"
+  | "minimize"
+  | "gameExit"
+  | "quit";
@@ -3,4 +3,4 @@
 /**
  * Outcome of a safe config upload attempt.
  */
-export type UploadConfigStatus = "uploaded" | "localClean" | "conflict";
+export type UploadConfigStatus = "uploaded" | "merged" | "localClean" | "conflict";
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Number of writes per device. Devices without writes are left out.
+ */
+export type VersionVector = { [key in string]: number };
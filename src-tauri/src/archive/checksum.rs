@@ -0,0 +1,191 @@
+//! SHA-256 checksums of archives.
+//!
+//! The checksum of every archive is kept in a `{archive}.sha256` sidecar
+//! next to it, locally and on remote, in the format of `sha256sum`. It is the
+//! checksum of the plain archive, so it stays the same whether or not the
+//! remote copy is encrypted.
+
+use std::{
+    borrow::Cow,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use ts_rs::TS;
+
+use crate::error::{Error, Result};
+
+pub const CHECKSUM_EXT: &str = "sha256";
+
+/// Result of checking an archive against its checksum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub enum ChecksumStatus {
+    Ok,
+    /// No checksum recorded, e.g. archives created by older versions.
+    Missing,
+    Mismatch,
+}
+
+/// Checksum status of a local or remote archive of a game.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveVerification {
+    pub name: String,
+    pub remote: bool,
+    pub status: ChecksumStatus,
+}
+
+/// Name of the checksum sidecar of `archive_filename`.
+#[inline]
+pub fn checksum_filename(archive_filename: &str) -> String {
+    format!("{archive_filename}.{CHECKSUM_EXT}")
+}
+
+#[inline]
+pub fn is_checksum(filename: &str) -> bool {
+    filename.ends_with(&format!(".{CHECKSUM_EXT}"))
+}
+
+/// Hex encoded SHA-256 of everything read from `reader`.
+pub fn sha256_of(mut reader: impl io::Read) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Sidecar content for an archive named `archive_filename`.
+#[inline]
+pub fn format_checksum(checksum: &str, archive_filename: &str) -> String {
+    format!("{checksum}  {archive_filename}\n")
+}
+
+/// The checksum in sidecar content, `None` if there is none.
+#[inline]
+pub fn parse_checksum(content: &str) -> Option<String> {
+    content
+        .split_whitespace()
+        .next()
+        .map(|s| s.to_ascii_lowercase())
+}
+
+#[inline]
+fn file_name(path: &Path) -> Cow<'_, str> {
+    path.file_name().unwrap_or_default().to_string_lossy()
+}
+
+/// Path of the sidecar of the local archive at `archive_path`.
+#[inline]
+fn sidecar_path(archive_path: &Path) -> PathBuf {
+    archive_path.with_file_name(checksum_filename(&file_name(archive_path)))
+}
+
+/// Write the sidecar of the local archive at `archive_path`.
+fn store_checksum(archive_path: &Path, checksum: &str) -> io::Result<()> {
+    fs::write(
+        sidecar_path(archive_path),
+        format_checksum(checksum, &file_name(archive_path)),
+    )
+}
+
+/// Compute the checksum of the local archive at `archive_path` and write its
+/// sidecar.
+pub fn write_checksum(archive_path: &Path) -> io::Result<String> {
+    let checksum = sha256_of(File::open(archive_path)?)?;
+    store_checksum(archive_path, &checksum)?;
+    Ok(checksum)
+}
+
+/// The checksum in the sidecar of the local archive at `archive_path`, `None`
+/// if it has none.
+pub fn read_checksum(archive_path: &Path) -> io::Result<Option<String>> {
+    match fs::read_to_string(sidecar_path(archive_path)) {
+        Ok(content) => Ok(parse_checksum(&content)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Check the local archive at `archive_path` against its sidecar.
+pub fn check_local(archive_path: &Path) -> io::Result<ChecksumStatus> {
+    let Some(expected) = read_checksum(archive_path)? else {
+        return Ok(ChecksumStatus::Missing);
+    };
+    Ok(if sha256_of(File::open(archive_path)?)? == expected {
+        ChecksumStatus::Ok
+    } else {
+        ChecksumStatus::Mismatch
+    })
+}
+
+/// Like [`check_local`], but a mismatch is an error. Archives without a
+/// checksum are accepted with a warning.
+pub fn verify_local(archive_path: &Path) -> Result<()> {
+    match check_local(archive_path)? {
+        ChecksumStatus::Ok => Ok(()),
+        ChecksumStatus::Missing => {
+            warn!("no checksum for {}, not verified", archive_path.display());
+            Ok(())
+        }
+        ChecksumStatus::Mismatch => {
+            Err(Error::ChecksumMismatch(archive_path.display().to_string()))
+        }
+    }
+}
+
+/// Remove the sidecar of the local archive at `archive_path`, if any.
+pub fn remove_checksum(archive_path: &Path) -> io::Result<()> {
+    match fs::remove_file(sidecar_path(archive_path)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Move the sidecar of the local archive at `from` along with the archive,
+/// which is renamed to `to`.
+pub fn rename_checksum(from: &Path, to: &Path) -> io::Result<()> {
+    let Some(checksum) = read_checksum(from)? else {
+        return Ok(());
+    };
+    store_checksum(to, &checksum)?;
+    remove_checksum(from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_corrupted_archive() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let archive = dir.path().join("20240101_000000_pc.tar");
+        fs::write(&archive, "archive")?;
+        assert_eq!(check_local(&archive)?, ChecksumStatus::Missing);
+
+        let checksum = write_checksum(&archive)?;
+        assert_eq!(read_checksum(&archive)?, Some(checksum));
+        assert!(is_checksum(&checksum_filename("20240101_000000_pc.tar")));
+        assert_eq!(check_local(&archive)?, ChecksumStatus::Ok);
+
+        fs::write(&archive, "archiv")?;
+        assert_eq!(check_local(&archive)?, ChecksumStatus::Mismatch);
+        assert!(verify_local(&archive).is_err());
+
+        remove_checksum(&archive)?;
+        assert_eq!(check_local(&archive)?, ChecksumStatus::Missing);
+        Ok(())
+    }
+}
@@ -0,0 +1,271 @@
+//! Compare the files of two archives, or of an archive and the live saves.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, Read},
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use similar::TextDiff;
+use ts_rs::TS;
+
+use super::{Archive, ArchiveEntry, Archiver, SavePath};
+
+/// Files larger than this are never shown as text diffs.
+const TEXT_DIFF_LIMIT: u64 = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub enum FileChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub struct FileState {
+    pub size: u64,
+    /// Unix timestamp in seconds.
+    pub mtime: u64,
+    /// SHA-256 of the content, hex encoded.
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub struct FileChange {
+    /// See [`ArchiveEntry::path`].
+    pub path: String,
+    pub kind: FileChangeKind,
+    pub old: Option<FileState>,
+    pub new: Option<FileState>,
+    /// Unified diff, only for small text files that were modified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_diff: Option<String>,
+}
+
+/// The files of one side of a diff, by path.
+pub(crate) struct FileSet {
+    files: BTreeMap<String, (FileState, Option<Vec<u8>>)>,
+    /// Whether to keep the content of small files for text diffs.
+    keep_content: bool,
+}
+
+impl FileSet {
+    fn new(keep_content: bool) -> Self {
+        Self {
+            files: BTreeMap::new(),
+            keep_content,
+        }
+    }
+
+    fn insert(&mut self, entry: ArchiveEntry, reader: &mut dyn Read) -> io::Result<()> {
+        let keep = self.keep_content && entry.size <= TEXT_DIFF_LIMIT;
+        let mut hasher = Sha256::new();
+        let mut content = vec![];
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            if keep {
+                content.extend_from_slice(&buf[..n]);
+            }
+        }
+        let state = FileState {
+            size: entry.size,
+            mtime: entry.mtime,
+            hash: hex::encode(hasher.finalize()),
+        };
+        self.files
+            .insert(entry.path, (state, keep.then_some(content)));
+        Ok(())
+    }
+
+    /// Files of an archive.
+    pub fn from_archive(
+        archiver: &Archiver,
+        reader: impl Read + io::Seek + Send,
+        keep_content: bool,
+    ) -> io::Result<Self> {
+        let mut this = Self::new(keep_content);
+        archiver.visit_files(reader, &mut |entry, reader| this.insert(entry, reader))?;
+        Ok(this)
+    }
+
+    /// Files currently in the save paths. Missing save paths are skipped.
+    pub fn from_save_paths(save_paths: &[SavePath], keep_content: bool) -> io::Result<Self> {
+        let mut this = Self::new(keep_content);
+        for save_path in save_paths.iter().filter(|p| p.path.exists()) {
+            for entry in save_path.walk() {
+                let entry = entry?;
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+                let relative = entry
+                    .path()
+                    .strip_prefix(&save_path.path)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                let path = if relative.as_os_str().is_empty() {
+                    save_path.name.clone()
+                } else {
+                    format!("{}/{}", save_path.name, super::to_entry_path(relative))
+                };
+                let metadata = entry.metadata().map_err(io::Error::other)?;
+                let info = ArchiveEntry {
+                    path,
+                    is_dir: false,
+                    size: metadata.len(),
+                    mtime: metadata
+                        .modified()
+                        .ok()
+                        .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+                        .map(|d| d.as_secs())
+                        .unwrap_or_default(),
+                };
+                this.insert(info, &mut File::open(entry.path())?)?;
+            }
+        }
+        Ok(this)
+    }
+
+    /// Rename the save path of every file to the [`SavePath::name`] of
+    /// `save_paths`, for archives that store save paths under their file
+    /// name.
+    pub fn normalize_names(mut self, save_paths: &[SavePath]) -> Self {
+        let rename = |root: &str| {
+            save_paths
+                .iter()
+                .find(|p| p.name == root)
+                .or_else(|| {
+                    save_paths
+                        .iter()
+                        .find(|p| p.path.file_name().is_some_and(|n| n == root))
+                })
+                .map(|p| p.name.clone())
+        };
+        self.files = self
+            .files
+            .into_iter()
+            .map(|(path, file)| {
+                let (root, rest) = path.split_once('/').unwrap_or((&path, ""));
+                let path = match rename(root) {
+                    Some(name) if rest.is_empty() => name,
+                    Some(name) => format!("{name}/{rest}"),
+                    None => path,
+                };
+                (path, file)
+            })
+            .collect();
+        self
+    }
+}
+
+fn unified_diff(path: &str, old: &[u8], new: &[u8]) -> Option<String> {
+    let old = std::str::from_utf8(old).ok()?;
+    let new = std::str::from_utf8(new).ok()?;
+    if old.contains('\0') || new.contains('\0') {
+        return None;
+    }
+    Some(
+        TextDiff::from_lines(old, new)
+            .unified_diff()
+            .context_radius(2)
+            .header(path, path)
+            .to_string(),
+    )
+}
+
+/// Changes from `old` to `new`, sorted by path. Files with the same content
+/// are unchanged, even if their mtime differs.
+pub fn diff(old: FileSet, mut new: FileSet) -> Vec<FileChange> {
+    let mut changes = vec![];
+    for (path, (old_state, old_content)) in old.files {
+        match new.files.remove(&path) {
+            None => changes.push(FileChange {
+                path,
+                kind: FileChangeKind::Removed,
+                old: Some(old_state),
+                new: None,
+                text_diff: None,
+            }),
+            Some((new_state, _)) if new_state.hash == old_state.hash => {}
+            Some((new_state, new_content)) => {
+                let text_diff = old_content
+                    .zip(new_content)
+                    .and_then(|(old, new)| unified_diff(&path, &old, &new));
+                changes.push(FileChange {
+                    path,
+                    kind: FileChangeKind::Modified,
+                    old: Some(old_state),
+                    new: Some(new_state),
+                    text_diff,
+                })
+            }
+        }
+    }
+    changes.extend(new.files.into_iter().map(|(path, (state, _))| FileChange {
+        path,
+        kind: FileChangeKind::Added,
+        old: None,
+        new: Some(state),
+        text_diff: None,
+    }));
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::archive::ArchiveAlgo;
+
+    #[test]
+    fn diff_archive_with_save_dir() -> io::Result<()> {
+        let src = tempfile::tempdir()?;
+        let save = SavePath::new("{game}/save", src.path().join("save"));
+        fs::create_dir(&save.path)?;
+        fs::write(save.path.join("slot1"), "hp=10\nmp=5\n")?;
+        fs::write(save.path.join("slot2"), "same")?;
+        fs::write(save.path.join("slot3"), "removed")?;
+
+        let backup = tempfile::tempdir()?;
+        let archive_path = backup.path().join("1.tar");
+        let archiver = Archiver::new(ArchiveAlgo::Tar, 0, backup.path());
+        archiver.archive(std::slice::from_ref(&save), File::create(&archive_path)?)?;
+
+        fs::write(save.path.join("slot1"), "hp=10\nmp=7\n")?;
+        fs::remove_file(save.path.join("slot3"))?;
+        fs::write(save.path.join("slot4"), "added")?;
+
+        let old = FileSet::from_archive(&archiver, File::open(&archive_path)?, true)?;
+        let new = FileSet::from_save_paths(std::slice::from_ref(&save), true)?;
+        let changes = diff(old, new);
+        let kinds: Vec<_> = changes
+            .iter()
+            .map(|c| (c.path.strip_prefix(&save.name).unwrap(), c.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ("/slot1", FileChangeKind::Modified),
+                ("/slot3", FileChangeKind::Removed),
+                ("/slot4", FileChangeKind::Added),
+            ]
+        );
+        let text_diff = changes[0].text_diff.as_deref().unwrap();
+        assert!(text_diff.contains("-mp=5") && text_diff.contains("+mp=7"));
+        Ok(())
+    }
+}
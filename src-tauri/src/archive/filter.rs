@@ -0,0 +1,82 @@
+//! Include/exclude rules of save paths, see [`SavePathConfig`].
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+use crate::{db::SavePathConfig, error::Result};
+
+#[derive(Debug, Clone)]
+pub struct PathFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+fn build(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern.trim_matches('/'))?);
+    }
+    Ok(builder.build()?)
+}
+
+impl PathFilter {
+    /// `None` if the save path has no rules.
+    pub fn new(config: &SavePathConfig) -> Result<Option<Self>> {
+        if config.include.is_empty() && config.exclude.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            include: if config.include.is_empty() {
+                None
+            } else {
+                Some(build(&config.include)?)
+            },
+            exclude: build(&config.exclude)?,
+        }))
+    }
+
+    /// Whether to archive the entry at `relative`, a `/`-separated path
+    /// relative to the save path. Dirs are only checked against `exclude`,
+    /// so that included files in them are reached.
+    pub fn is_included(&self, relative: &str, is_dir: bool) -> bool {
+        if self.exclude.is_match(relative) {
+            return false;
+        }
+        is_dir || self.include.as_ref().is_none_or(|i| i.is_match(relative))
+    }
+
+    /// Like [`Self::is_included`] for a file, also checking the dirs it is in.
+    pub fn is_file_included(&self, relative: &str) -> bool {
+        let mut parent = relative;
+        while let Some((dir, _)) = parent.rsplit_once('/') {
+            if !self.is_included(dir, true) {
+                return false;
+            }
+            parent = dir;
+        }
+        self.is_included(relative, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn include_and_exclude() {
+        let filter = PathFilter::new(&SavePathConfig {
+            path: "save".to_string(),
+            include: vec!["*.sav".to_string(), "system.dat".to_string()],
+            exclude: vec!["cache".to_string(), "auto*.sav".to_string()],
+        })
+        .unwrap()
+        .unwrap();
+        assert!(filter.is_file_included("1.sav"));
+        assert!(filter.is_file_included("slots/1.sav"));
+        assert!(filter.is_file_included("system.dat"));
+        assert!(!filter.is_file_included("log.txt"));
+        assert!(!filter.is_file_included("auto1.sav"));
+        assert!(!filter.is_file_included("cache/1.sav"));
+        assert!(filter.is_included("slots", true));
+        assert!(!filter.is_included("cache", true));
+    }
+}
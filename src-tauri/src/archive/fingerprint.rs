@@ -0,0 +1,117 @@
+//! Fingerprints of the saves, to tell whether they changed since an archive.
+//!
+//! A fingerprint is a hash over the archived files and dirs of the save
+//! paths, with either the mtime or the content hash of each file. The mtime
+//! fingerprint is cheap but changes whenever a game touches its saves; the
+//! content fingerprint needs to read every file but is the same on every
+//! device.
+
+use std::{
+    fs::File,
+    io,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use sha2::{Digest, Sha256};
+
+use super::{SavePath, sha256_of};
+
+/// Fingerprint of the files in `save_paths`, by their mtime or, if `content`,
+/// by their content. Save paths that do not exist are part of it too.
+pub fn fingerprint(save_paths: &[SavePath], content: bool) -> io::Result<String> {
+    let mut lines = vec![];
+    for save_path in save_paths {
+        if !save_path.path.exists() {
+            lines.push(format!("{}\tmissing", save_path.name));
+            continue;
+        }
+        for entry in save_path.walk() {
+            let entry = entry?;
+            let relative = save_path.relative(entry.path()).unwrap_or_default();
+            let name = format!("{}/{relative}", save_path.name);
+            let file_type = entry.file_type();
+            if file_type.is_dir() {
+                lines.push(format!("{name}/"));
+                continue;
+            }
+            if !file_type.is_file() || !save_path.is_archived(entry.path()) {
+                continue;
+            }
+            let metadata = entry.metadata().map_err(io::Error::other)?;
+            let version = if content {
+                sha256_of(File::open(entry.path())?)?
+            } else {
+                metadata
+                    .modified()?
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos()
+                    .to_string()
+            };
+            lines.push(format!("{name}\t{}\t{version}", metadata.len()));
+        }
+    }
+    lines.sort_unstable();
+
+    let mut hasher = Sha256::new();
+    for line in lines {
+        hasher.update(line.as_bytes());
+        hasher.update(b"\n");
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// The newest mtime of the archived files in `save_paths`, `None` if there is
+/// no such file.
+pub fn last_modified(save_paths: &[SavePath]) -> io::Result<Option<SystemTime>> {
+    let mut last = None;
+    for save_path in save_paths.iter().filter(|p| p.path.exists()) {
+        for entry in save_path.walk() {
+            let entry = entry?;
+            if !entry.file_type().is_file() || !save_path.is_archived(entry.path()) {
+                continue;
+            }
+            let modified = entry.metadata().map_err(io::Error::other)?.modified()?;
+            last = last.max(Some(modified));
+        }
+    }
+    Ok(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn detects_changes() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let save_dir = dir.path().join("save");
+        fs::create_dir(&save_dir)?;
+        fs::write(save_dir.join("slot1.dat"), "1")?;
+        let save_paths = [SavePath::new("{save}", save_dir.clone())];
+
+        let quick = fingerprint(&save_paths, false)?;
+        let content = fingerprint(&save_paths, true)?;
+        assert_eq!(fingerprint(&save_paths, false)?, quick);
+
+        // touched, but the same content
+        let file = File::options()
+            .write(true)
+            .open(save_dir.join("slot1.dat"))?;
+        file.set_modified(UNIX_EPOCH)?;
+        drop(file);
+        assert_ne!(fingerprint(&save_paths, false)?, quick);
+        assert_eq!(fingerprint(&save_paths, true)?, content);
+
+        fs::write(save_dir.join("slot1.dat"), "2")?;
+        assert_ne!(fingerprint(&save_paths, true)?, content);
+
+        assert!(last_modified(&save_paths)? > Some(UNIX_EPOCH));
+        fs::remove_dir_all(&save_dir)?;
+        assert_ne!(fingerprint(&save_paths, true)?, content);
+        assert_eq!(last_modified(&save_paths)?, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+//! Metadata of archives.
+//!
+//! The metadata of every archive is kept in a `{archive}.meta.toml` sidecar
+//! next to it, locally and on remote, so that archives can be sorted and
+//! told apart without parsing their filenames. Archives created by older
+//! versions have no sidecar; their metadata is recovered from the filename
+//! as far as possible, see [`ArchiveMeta::legacy`].
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Duration, Local, NaiveDateTime, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::{ArchiveAlgo, is_checksum};
+
+pub const META_EXT: &str = "meta.toml";
+
+/// What an archive was created for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub enum ArchiveTrigger {
+    /// Created by older versions, which did not record it.
+    #[default]
+    Unknown,
+    Manual,
+    AutoUpload,
+    /// The saves taken before restoring an archive over them.
+    PreRestore,
+    /// A snapshot taken while the game was running.
+    InSession,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveMeta {
+    pub created_at: DateTime<Utc>,
+    /// Uid of the device the archive was created on, empty if unknown.
+    #[serde(default)]
+    pub device_uid: String,
+    #[serde(default)]
+    pub device_name: String,
+    pub algorithm: ArchiveAlgo,
+    /// Play time of the game when the archive was created. [secs, nanos]
+    #[serde(default)]
+    pub use_time: Duration,
+    #[serde(default)]
+    pub trigger: ArchiveTrigger,
+    /// User note.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Pinned archives are never pruned.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// Fingerprint of the saves by mtime when the archive was created.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    /// Fingerprint of the saves by content when the archive was created, the
+    /// same for the same saves on every device.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+}
+
+impl ArchiveMeta {
+    /// Metadata of an archive created by older versions, recovered from its
+    /// `YYYYMMDD_HHMMSS_{DeviceName}.{Ext}` filename. `None` if the filename
+    /// is not in that format.
+    pub fn legacy(archive_filename: &str) -> Option<Self> {
+        let (stem, ext) = archive_filename.rsplit_once('.')?;
+        let algorithm = ArchiveAlgo::from_ext(ext)?;
+        let timestamp = stem.get(..15)?;
+        let created_at = NaiveDateTime::parse_from_str(timestamp, "%Y%m%d_%H%M%S")
+            .ok()?
+            .and_local_timezone(Local)
+            .earliest()?
+            .to_utc();
+        Some(Self {
+            created_at,
+            device_uid: String::new(),
+            device_name: stem[15..].trim_start_matches('_').to_string(),
+            algorithm,
+            use_time: Duration::zero(),
+            trigger: ArchiveTrigger::Unknown,
+            note: None,
+            pinned: false,
+            fingerprint: None,
+            content_hash: None,
+        })
+    }
+
+    #[inline]
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("archive meta is always serializable")
+    }
+
+    #[inline]
+    pub fn from_toml(content: &[u8]) -> std::result::Result<Self, toml::de::Error> {
+        toml::from_slice(content)
+    }
+}
+
+/// Name of the metadata sidecar of `archive_filename`.
+#[inline]
+pub fn meta_filename(archive_filename: &str) -> String {
+    format!("{archive_filename}.{META_EXT}")
+}
+
+/// Whether `filename` is a sidecar of an archive, not an archive.
+#[inline]
+pub fn is_sidecar(filename: &str) -> bool {
+    is_checksum(filename) || filename.ends_with(&format!(".{META_EXT}"))
+}
+
+/// Path of the metadata sidecar of the local archive at `archive_path`.
+#[inline]
+fn sidecar_path(archive_path: &Path) -> PathBuf {
+    archive_path.with_file_name(meta_filename(
+        &archive_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy(),
+    ))
+}
+
+/// Write the metadata sidecar of the local archive at `archive_path`.
+pub fn write_meta(archive_path: &Path, meta: &ArchiveMeta) -> io::Result<()> {
+    fs::write(sidecar_path(archive_path), meta.to_toml())
+}
+
+/// The metadata in the sidecar of the local archive at `archive_path`,
+/// `None` if it has none or it cannot be parsed.
+pub fn read_meta(archive_path: &Path) -> io::Result<Option<ArchiveMeta>> {
+    let path = sidecar_path(archive_path);
+    let content = match fs::read(&path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    match ArchiveMeta::from_toml(&content) {
+        Ok(meta) => Ok(Some(meta)),
+        Err(e) => {
+            warn!("invalid archive metadata {}: {e}", path.display());
+            Ok(None)
+        }
+    }
+}
+
+/// Like [`read_meta`], falling back to [`ArchiveMeta::legacy`].
+pub fn read_meta_or_legacy(archive_path: &Path) -> io::Result<Option<ArchiveMeta>> {
+    Ok(read_meta(archive_path)?.or_else(|| {
+        ArchiveMeta::legacy(
+            &archive_path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy(),
+        )
+    }))
+}
+
+/// Remove the metadata sidecar of the local archive at `archive_path`, if
+/// any.
+pub fn remove_meta(archive_path: &Path) -> io::Result<()> {
+    match fs::remove_file(sidecar_path(archive_path)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Move the metadata sidecar of the local archive at `from` along with the
+/// archive, which is renamed to `to`.
+pub fn rename_meta(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(sidecar_path(from), sidecar_path(to)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_meta_from_filename() {
+        let meta = ArchiveMeta::legacy("20240102_030405_my-pc.tar").unwrap();
+        let expected = NaiveDateTime::parse_from_str("20240102_030405", "%Y%m%d_%H%M%S")
+            .unwrap()
+            .and_local_timezone(Local)
+            .unwrap()
+            .to_utc();
+        assert_eq!(meta.created_at, expected);
+        assert_eq!(meta.device_name, "my-pc");
+        assert_eq!(meta.algorithm, ArchiveAlgo::Tar);
+        assert_eq!(meta.trigger, ArchiveTrigger::Unknown);
+
+        assert!(ArchiveMeta::legacy("my save.tar").is_none());
+        assert!(ArchiveMeta::legacy("20240102_030405_my-pc.zip").is_none());
+        assert!(is_sidecar(&meta_filename("20240102_030405_my-pc.tar")));
+    }
+
+    #[test]
+    fn sidecar_roundtrip() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let archive = dir.path().join("save.tar");
+        fs::write(&archive, "archive")?;
+        assert_eq!(read_meta(&archive)?, None);
+
+        let meta = ArchiveMeta {
+            created_at: Utc::now(),
+            device_uid: "uid".to_string(),
+            device_name: "pc".to_string(),
+            algorithm: ArchiveAlgo::Tar,
+            use_time: Duration::seconds(90),
+            trigger: ArchiveTrigger::Manual,
+            note: Some("before the boss".to_string()),
+            pinned: true,
+            fingerprint: Some("fingerprint".to_string()),
+            content_hash: None,
+        };
+        write_meta(&archive, &meta)?;
+        assert_eq!(read_meta(&archive)?, Some(meta.clone()));
+
+        let renamed = dir.path().join("renamed.tar");
+        fs::rename(&archive, &renamed)?;
+        rename_meta(&archive, &renamed)?;
+        assert_eq!(read_meta(&archive)?, None);
+        assert_eq!(read_meta_or_legacy(&renamed)?, Some(meta));
+
+        remove_meta(&renamed)?;
+        assert_eq!(read_meta_or_legacy(&renamed)?, None);
+        Ok(())
+    }
+}
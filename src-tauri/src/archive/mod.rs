@@ -1,27 +1,66 @@
+mod checksum;
+mod diff;
+mod filter;
+mod fingerprint;
+mod meta;
+mod retention;
+mod snapshot;
 mod squashfs;
+mod staging;
 mod tar;
 
 use std::{
-    fs, io,
-    path::{Path, PathBuf},
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    fs,
+    io::{self, Read as _, Seek as _},
+    path::{Component, Path, PathBuf},
 };
 
-use log::{error, info};
+pub use checksum::{
+    ArchiveVerification, ChecksumStatus, check_local, checksum_filename, format_checksum,
+    is_checksum, parse_checksum, read_checksum, remove_checksum, rename_checksum, sha256_of,
+    verify_local, write_checksum,
+};
+use chrono::{DateTime, Duration, Utc};
+use diff::FileSet;
+pub use diff::{FileChange, FileChangeKind, FileState};
+use filter::PathFilter;
+use fingerprint::{fingerprint, last_modified};
+use log::{error, info, warn};
+pub use meta::{
+    ArchiveMeta, ArchiveTrigger, is_sidecar, meta_filename, read_meta, read_meta_or_legacy,
+    remove_meta, rename_meta, write_meta,
+};
+pub use retention::RetentionPolicy;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+pub use snapshot::{
+    CHUNK_GC_GRACE, CHUNKS_DIR, SnapshotManifest, chunk_hash, gc_chunks, is_snapshot, write_chunk,
+};
+use snapshot::{SNAPSHOT_MAGIC, SnapshotArchiver};
 use squashfs::SquashfsArchiver;
+use staging::StagedRestore;
 use tar::TarArchiver;
 use ts_rs::TS;
 
-use crate::{bindings::resolve_var, error::Result};
+use crate::{
+    bindings::resolve_var,
+    db::{SavePathConfig, device::DEVICE_UID},
+    error::Result,
+};
 
 // region structure
 
-#[derive(Debug, Serialize, Deserialize, Clone, TS)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, TS)]
 #[ts(export)]
 #[serde(rename_all = "camelCase")]
 pub enum ArchiveAlgo {
     SquashfsZstd,
     Tar,
+    /// Content-addressed snapshot: files are split into chunks that are
+    /// stored once per game and shared between snapshots.
+    Snapshot,
 }
 
 impl ArchiveAlgo {
@@ -29,6 +68,16 @@ impl ArchiveAlgo {
         match self {
             ArchiveAlgo::SquashfsZstd => "squashfs",
             ArchiveAlgo::Tar => "tar",
+            ArchiveAlgo::Snapshot => "snapshot",
+        }
+    }
+
+    pub fn from_ext(ext: &str) -> Option<Self> {
+        match ext {
+            "squashfs" => Some(ArchiveAlgo::SquashfsZstd),
+            "tar" => Some(ArchiveAlgo::Tar),
+            "snapshot" => Some(ArchiveAlgo::Snapshot),
+            _ => None,
         }
     }
 }
@@ -36,11 +85,15 @@ impl ArchiveAlgo {
 #[derive(Debug, Serialize, Deserialize, Clone, TS)]
 #[ts(export)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct ArchiveConfig {
     pub algorithm: ArchiveAlgo,
     pub level: u8,
-    // currently not used
+    /// Archive the current saves before restoring an archive over them.
     pub backup_before_restore: bool,
+    /// Delete files that are not in the archive when restoring, instead of
+    /// only overwriting the ones that are.
+    pub mirror_restore: bool,
 }
 
 impl Default for ArchiveConfig {
@@ -49,6 +102,7 @@ impl Default for ArchiveConfig {
             algorithm: ArchiveAlgo::SquashfsZstd,
             level: 3,
             backup_before_restore: true,
+            mirror_restore: false,
         }
     }
 }
@@ -59,6 +113,13 @@ impl Default for ArchiveConfig {
 pub struct ArchiveInfo {
     pub name: String,
     pub size: u64,
+    /// Detected format, only reported for local archives.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<ArchiveAlgo>,
+    /// `None` if the archive has no metadata and its filename is not in the
+    /// format of older versions either.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<ArchiveMeta>,
 }
 
 impl ArchiveInfo {
@@ -72,6 +133,21 @@ impl ArchiveInfo {
             .to_string();
         self
     }
+
+    #[inline]
+    pub fn created_at(&self) -> Option<DateTime<Utc>> {
+        self.meta.as_ref().map(|m| m.created_at)
+    }
+}
+
+/// Sort archives from the oldest to the newest. Archives of unknown age come
+/// first.
+pub fn sort_by_age(archives: &mut [ArchiveInfo]) {
+    archives.sort_by(|a, b| {
+        a.created_at()
+            .cmp(&b.created_at())
+            .then_with(|| a.name.cmp(&b.name))
+    });
 }
 
 impl From<opendal::Entry> for ArchiveInfo {
@@ -80,6 +156,8 @@ impl From<opendal::Entry> for ArchiveInfo {
         Self {
             name,
             size: metadata.content_length(),
+            format: None,
+            meta: None,
         }
     }
 }
@@ -89,44 +167,305 @@ impl From<std::fs::DirEntry> for ArchiveInfo {
         Self {
             name: value.file_name().to_string_lossy().to_string(),
             size: value.metadata().map(|m| m.len()).unwrap_or_default(),
+            format: None,
+            meta: None,
+        }
+    }
+}
+
+/// A resolved save path and the name of its top-level entry in archives.
+#[derive(Debug, Clone)]
+pub struct SavePath {
+    /// Derived from the unresolved template, so that save paths with the same
+    /// file name do not collide and the name is the same on every device.
+    pub name: String,
+    /// The unresolved path from config.
+    pub template: String,
+    pub path: PathBuf,
+    /// Which files to archive, `None` for all of them.
+    pub filter: Option<PathFilter>,
+}
+
+impl SavePath {
+    pub fn new(template: &str, path: PathBuf) -> Self {
+        let hash = hex::encode(Sha256::digest(template.as_bytes()));
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+        Self {
+            name: format!("{}_{}", &hash[..8], file_name),
+            template: template.to_string(),
+            path,
+            filter: None,
+        }
+    }
+
+    /// Resolve a save path from config.
+    pub fn resolve(config: &SavePathConfig) -> Result<Self> {
+        let path = PathBuf::from(resolve_var(&config.path)?);
+        Ok(Self {
+            filter: PathFilter::new(config)?,
+            ..Self::new(&config.path, path)
+        })
+    }
+
+    fn relative(&self, path: &Path) -> Option<String> {
+        path.strip_prefix(&self.path).ok().map(to_entry_path)
+    }
+
+    /// Walk the files and dirs to archive, including the save path itself.
+    /// Links are not followed.
+    pub(crate) fn walk(&self) -> impl Iterator<Item = io::Result<walkdir::DirEntry>> + '_ {
+        walkdir::WalkDir::new(&self.path)
+            .follow_links(false)
+            .into_iter()
+            .filter_entry(|entry| {
+                let (Some(filter), Some(relative)) = (&self.filter, self.relative(entry.path()))
+                else {
+                    return true;
+                };
+                entry.depth() == 0 || filter.is_included(&relative, entry.file_type().is_dir())
+            })
+            .map(|entry| entry.map_err(io::Error::other))
+    }
+
+    /// Whether the file at `path`, inside the save path, is archived.
+    pub(crate) fn is_archived(&self, path: &Path) -> bool {
+        match (&self.filter, self.relative(path)) {
+            (Some(filter), Some(relative)) if !relative.is_empty() => {
+                filter.is_file_included(&relative)
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Name of the manifest at the root of squashfs and tar archives.
+pub const ARCHIVE_MANIFEST: &str = ".galgame-manifest.toml";
+
+/// Which save path each top-level entry of an archive belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveManifest {
+    pub paths: Vec<ManifestPath>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestPath {
+    pub name: String,
+    pub template: String,
+}
+
+impl ArchiveManifest {
+    pub fn new(paths: &[SavePath]) -> Self {
+        Self {
+            paths: paths
+                .iter()
+                .map(|p| ManifestPath {
+                    name: p.name.clone(),
+                    template: p.template.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        toml::to_string(self)
+            .map(String::into_bytes)
+            .map_err(io::Error::other)
+    }
+}
+
+/// Map the top-level entry names of an archive to the paths to extract them
+/// to. Archives written before save paths got stable names store them under
+/// their file name, which is accepted as a fallback.
+pub(crate) fn target_map(targets: &[SavePath]) -> HashMap<&OsStr, PathBuf> {
+    let mut map: HashMap<&OsStr, PathBuf> = targets
+        .iter()
+        .map(|t| (OsStr::new(&t.name), t.path.clone()))
+        .collect();
+    for target in targets {
+        if let Some(file_name) = target.path.file_name() {
+            map.entry(file_name).or_insert_with(|| target.path.clone());
         }
     }
+    map
+}
+
+/// A file or dir inside an archive.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveEntry {
+    /// `/`-separated path in the archive. The first component is the
+    /// [`SavePath::name`] of the save path it belongs to.
+    pub path: String,
+    pub is_dir: bool,
+    pub size: u64,
+    /// Unix timestamp in seconds.
+    pub mtime: u64,
+}
+
+/// `/`-separated form of a path in an archive, as used by [`ArchiveEntry`].
+pub(crate) fn to_entry_path(path: &Path) -> String {
+    path.components()
+        .filter_map(|c| match c {
+            Component::Normal(s) => Some(s.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Whether the entry at `path` is one of `selected`, or inside one of them.
+fn is_selected(selected: &[String], path: &str) -> bool {
+    selected.iter().any(|s| {
+        let s = s.trim_matches('/');
+        path.strip_prefix(s)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    })
+}
+
+fn not_found_in_archive(path: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("File not found in archive: {path}"),
+    )
 }
 
 // region interface
 
 pub trait Archive {
-    fn archive(
+    fn archive(&self, paths: &[SavePath], writer: impl io::Write + io::Seek) -> io::Result<()>;
+    fn extract(
         &self,
-        paths: Vec<impl AsRef<Path>>,
-        writer: impl io::Write + io::Seek,
+        reader: impl io::Read + io::Seek + Send,
+        targets: &[SavePath],
+    ) -> io::Result<()> {
+        self.extract_filtered(reader, targets, &|_| true)
+    }
+    /// Extract only the entries whose [`ArchiveEntry::path`] passes `filter`.
+    /// Only the save paths of those entries need a target.
+    fn extract_filtered(
+        &self,
+        reader: impl io::Read + io::Seek + Send,
+        targets: &[SavePath],
+        filter: &dyn Fn(&str) -> bool,
     ) -> io::Result<()>;
-    fn extract(
+    fn list(&self, reader: impl io::Read + io::Seek + Send) -> io::Result<Vec<ArchiveEntry>>;
+    /// Content of the file at `path`, see [`ArchiveEntry::path`].
+    fn read_file(&self, reader: impl io::Read + io::Seek + Send, path: &str)
+    -> io::Result<Vec<u8>>;
+    /// Call `visit` with every regular file in the archive and its content.
+    fn visit_files(
         &self,
         reader: impl io::Read + io::Seek + Send,
-        targets: Vec<impl AsRef<Path>>,
+        visit: &mut dyn FnMut(ArchiveEntry, &mut dyn io::Read) -> io::Result<()>,
     ) -> io::Result<()>;
 }
 
-impl Archive for ArchiveConfig {
-    fn archive(
+/// Archiver of one of the supported algorithms.
+pub(crate) enum Archiver {
+    SquashfsZstd(SquashfsArchiver),
+    Tar(TarArchiver),
+    Snapshot(SnapshotArchiver),
+}
+
+impl Archiver {
+    /// Archiver of `algorithm`, working on the archives of the game in
+    /// `game_backup_dir`.
+    pub(crate) fn new(algorithm: ArchiveAlgo, level: u8, game_backup_dir: &Path) -> Self {
+        match algorithm {
+            ArchiveAlgo::SquashfsZstd => Archiver::SquashfsZstd(SquashfsArchiver(level)),
+            ArchiveAlgo::Tar => Archiver::Tar(TarArchiver),
+            ArchiveAlgo::Snapshot => Archiver::Snapshot(SnapshotArchiver::new(game_backup_dir)),
+        }
+    }
+}
+
+impl ArchiveConfig {
+    /// Archiver for the configured algorithm, working on the archives of the
+    /// game in `game_backup_dir`.
+    #[inline]
+    pub(crate) fn archiver(&self, game_backup_dir: &Path) -> Archiver {
+        Archiver::new(self.algorithm, self.level, game_backup_dir)
+    }
+}
+
+const SQUASHFS_MAGIC: &[u8] = b"hsqs";
+const TAR_MAGIC: &[u8] = b"ustar";
+const TAR_MAGIC_OFFSET: usize = 257;
+
+/// Detect the format of the archive at `path` from its magic bytes, falling
+/// back to its extension. `None` if neither is recognized.
+pub fn detect_archive_format(path: &Path) -> io::Result<Option<ArchiveAlgo>> {
+    let mut head = Vec::with_capacity(512);
+    fs::File::open(path)?.take(512).read_to_end(&mut head)?;
+    let detected = if head.starts_with(SQUASHFS_MAGIC) {
+        Some(ArchiveAlgo::SquashfsZstd)
+    } else if head.starts_with(SNAPSHOT_MAGIC.as_bytes()) {
+        Some(ArchiveAlgo::Snapshot)
+    } else if head.get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + TAR_MAGIC.len()) == Some(TAR_MAGIC) {
+        Some(ArchiveAlgo::Tar)
+    } else {
+        None
+    };
+    Ok(detected.or_else(|| {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .and_then(ArchiveAlgo::from_ext)
+    }))
+}
+
+impl Archive for Archiver {
+    fn archive(&self, paths: &[SavePath], writer: impl io::Write + io::Seek) -> io::Result<()> {
+        match self {
+            Archiver::SquashfsZstd(a) => a.archive(paths, writer),
+            Archiver::Tar(a) => a.archive(paths, writer),
+            Archiver::Snapshot(a) => a.archive(paths, writer),
+        }
+    }
+    fn extract_filtered(
         &self,
-        paths: Vec<impl AsRef<Path>>,
-        writer: impl io::Write + io::Seek,
+        reader: impl io::Read + io::Seek + Send,
+        targets: &[SavePath],
+        filter: &dyn Fn(&str) -> bool,
     ) -> io::Result<()> {
-        match self.algorithm {
-            ArchiveAlgo::SquashfsZstd => SquashfsArchiver(self.level).archive(paths, writer),
-            ArchiveAlgo::Tar => TarArchiver.archive(paths, writer),
+        match self {
+            Archiver::SquashfsZstd(a) => a.extract_filtered(reader, targets, filter),
+            Archiver::Tar(a) => a.extract_filtered(reader, targets, filter),
+            Archiver::Snapshot(a) => a.extract_filtered(reader, targets, filter),
         }
     }
-    fn extract(
+    fn list(&self, reader: impl io::Read + io::Seek + Send) -> io::Result<Vec<ArchiveEntry>> {
+        match self {
+            Archiver::SquashfsZstd(a) => a.list(reader),
+            Archiver::Tar(a) => a.list(reader),
+            Archiver::Snapshot(a) => a.list(reader),
+        }
+    }
+    fn read_file(
+        &self,
+        reader: impl io::Read + io::Seek + Send,
+        path: &str,
+    ) -> io::Result<Vec<u8>> {
+        match self {
+            Archiver::SquashfsZstd(a) => a.read_file(reader, path),
+            Archiver::Tar(a) => a.read_file(reader, path),
+            Archiver::Snapshot(a) => a.read_file(reader, path),
+        }
+    }
+    fn visit_files(
         &self,
         reader: impl io::Read + io::Seek + Send,
-        targets: Vec<impl AsRef<Path>>,
+        visit: &mut dyn FnMut(ArchiveEntry, &mut dyn io::Read) -> io::Result<()>,
     ) -> io::Result<()> {
-        match self.algorithm {
-            ArchiveAlgo::SquashfsZstd => SquashfsArchiver(self.level).extract(reader, targets),
-            ArchiveAlgo::Tar => TarArchiver.extract(reader, targets),
+        match self {
+            Archiver::SquashfsZstd(a) => a.visit_files(reader, visit),
+            Archiver::Tar(a) => a.visit_files(reader, visit),
+            Archiver::Snapshot(a) => a.visit_files(reader, visit),
         }
     }
 }
@@ -138,25 +477,36 @@ pub fn archive_impl(
     device_name: &str,
     archive_conf: &ArchiveConfig,
     game_backup_dir: PathBuf,
-    paths: Vec<String>,
+    paths: Vec<SavePathConfig>,
+    trigger: ArchiveTrigger,
+    use_time: Duration,
 ) -> Result<String> {
     // 1. 解析路径
-    let target_paths: Vec<PathBuf> = paths
-        .iter()
-        .map(|s| resolve_var(s).map(PathBuf::from))
-        .collect::<Result<_>>()?;
+    let target_paths = resolve_save_paths(&paths)?;
+    let fingerprint_quick = fingerprint(&target_paths, false)?;
+    // hashing the content reads every file, which archiving does again, so it
+    // is skipped if an archive of the same saves already recorded it
+    let content_hash = match recorded_content_hash(&game_backup_dir, &fingerprint_quick)? {
+        Some(content_hash) => content_hash,
+        None => fingerprint(&target_paths, true)?,
+    };
 
     if !game_backup_dir.exists() {
         fs::create_dir_all(&game_backup_dir)?;
     }
 
-    let now = chrono::Local::now();
-    let timestamp = now.format("%Y%m%d_%H%M%S");
+    let now = Utc::now();
+    let timestamp = now.with_timezone(&chrono::Local).format("%Y%m%d_%H%M%S");
 
     let filename = format!(
-        "{}_{}.{}",
+        "{}_{}{}.{}",
         timestamp,
         device_name,
+        match trigger {
+            ArchiveTrigger::PreRestore => "-pre-restore",
+            ArchiveTrigger::InSession => "-session",
+            _ => "",
+        },
         archive_conf.algorithm.ext()
     );
     let file_path = game_backup_dir.join(&filename);
@@ -169,11 +519,34 @@ pub fn archive_impl(
         file_path.display()
     );
 
-    match archive_conf.archive(target_paths, file) {
-        Ok(_) => Ok(filename),
+    match archive_conf
+        .archiver(&game_backup_dir)
+        .archive(&target_paths, file)
+        .and_then(|()| write_checksum(&file_path))
+        .and_then(|_| {
+            write_meta(
+                &file_path,
+                &ArchiveMeta {
+                    created_at: now,
+                    device_uid: DEVICE_UID.to_string(),
+                    device_name: device_name.to_string(),
+                    algorithm: archive_conf.algorithm,
+                    use_time,
+                    trigger,
+                    note: None,
+                    pinned: false,
+                    fingerprint: Some(fingerprint_quick),
+                    content_hash: Some(content_hash),
+                },
+            )
+        }) {
+        Ok(()) => Ok(filename),
         Err(e) => {
             error!("Failed to archive saves: {e}");
-            if let Err(e) = fs::remove_file(&file_path) {
+            if let Err(e) = fs::remove_file(&file_path)
+                .and_then(|()| remove_checksum(&file_path))
+                .and_then(|()| remove_meta(&file_path))
+            {
                 error!("Failed to revert previous created archive file: {e}");
             }
             Err(e.into())
@@ -181,33 +554,247 @@ pub fn archive_impl(
     }
 }
 
-pub fn restore_impl(
-    archive_conf: &ArchiveConfig,
-    game_backup_dir: PathBuf,
-    archive_filename: String,
-    paths: Vec<String>,
-) -> Result<()> {
-    let target_paths: Vec<PathBuf> = paths
-        .iter()
-        .map(|s| resolve_var(s).map(PathBuf::from))
-        .collect::<Result<_>>()?;
+/// [`ArchiveMeta::content_hash`] of a local archive in `game_backup_dir` whose
+/// [`ArchiveMeta::fingerprint`] is `fingerprint`, i.e. of the same saves.
+fn recorded_content_hash(game_backup_dir: &Path, fingerprint: &str) -> io::Result<Option<String>> {
+    let entries = match fs::read_dir(game_backup_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() || is_sidecar(&entry.file_name().to_string_lossy()) {
+            continue;
+        }
+        if let Some(ArchiveMeta {
+            fingerprint: Some(recorded),
+            content_hash: Some(content_hash),
+            ..
+        }) = read_meta(&entry.path())?
+            && recorded == fingerprint
+        {
+            return Ok(Some(content_hash));
+        }
+    }
+    Ok(None)
+}
+
+/// Resolve the save paths to archive, dropping duplicates.
+fn resolve_save_paths(paths: &[SavePathConfig]) -> Result<Vec<SavePath>> {
+    let mut seen = HashSet::new();
+    let mut target_paths = vec![];
+    for config in paths {
+        let save_path = SavePath::resolve(config)?;
+        if seen.insert(save_path.name.clone()) {
+            target_paths.push(save_path);
+        } else {
+            warn!("duplicate save path {}, archived once", config.path);
+        }
+    }
+    Ok(target_paths)
+}
+
+/// Fingerprint of the current saves, comparable with
+/// [`ArchiveMeta::fingerprint`], or with [`ArchiveMeta::content_hash`] if
+/// `content`.
+pub fn fingerprint_saves(paths: &[SavePathConfig], content: bool) -> Result<String> {
+    Ok(fingerprint(&resolve_save_paths(paths)?, content)?)
+}
+
+/// When the current saves were last modified, `None` if there are no saves.
+pub fn saves_modified(paths: &[SavePathConfig]) -> Result<Option<DateTime<Utc>>> {
+    Ok(last_modified(&resolve_save_paths(paths)?)?.map(DateTime::from))
+}
 
-    let archive_path = game_backup_dir.join(&archive_filename);
+/// Open a local archive with the archiver of its format.
+fn open_archive(
+    archive_conf: &ArchiveConfig,
+    game_backup_dir: &Path,
+    archive_filename: &str,
+) -> Result<(Archiver, fs::File)> {
+    let archive_path = game_backup_dir.join(archive_filename);
 
     if !archive_path.exists() {
         return Err(io::Error::new(io::ErrorKind::NotFound, "Archive not found").into());
     }
 
+    // the archive may be created with other settings, or on another device
+    let algorithm = detect_archive_format(&archive_path)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Unknown archive format"))?;
     let file = fs::File::open(&archive_path)?;
+    Ok((
+        Archiver::new(algorithm, archive_conf.level, game_backup_dir),
+        file,
+    ))
+}
+
+/// Restore an archive over the save paths. The archive is verified against
+/// its checksum, then extracted to a staging area first and swapped in only
+/// if it was extracted completely. If `files` is given, only those entries
+/// (see [`ArchiveEntry::path`]) are restored and the other files are kept even
+/// in mirror mode.
+///
+/// Returns the filename of the pre-restore archive, if one was created.
+pub fn restore_impl(
+    device_name: &str,
+    archive_conf: &ArchiveConfig,
+    game_backup_dir: PathBuf,
+    archive_filename: String,
+    paths: Vec<SavePathConfig>,
+    files: Option<Vec<String>>,
+    use_time: Duration,
+) -> Result<Option<String>> {
+    let mut target_paths: Vec<SavePath> =
+        paths.iter().map(SavePath::resolve).collect::<Result<_>>()?;
+    if let Some(files) = &files {
+        // only touch the save paths the files belong to
+        let roots: HashSet<&str> = files
+            .iter()
+            .filter_map(|f| f.trim_start_matches('/').split('/').next())
+            .collect();
+        target_paths.retain(|p| {
+            roots.contains(p.name.as_str())
+                || p.path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| roots.contains(n))
+        });
+    }
+
+    let (archiver, file) = open_archive(archive_conf, &game_backup_dir, &archive_filename)?;
+    verify_local(&game_backup_dir.join(&archive_filename))?;
+
+    // save paths are mapped by name, so the missing ones can be left out
+    let existing: Vec<SavePathConfig> = paths
+        .into_iter()
+        .filter(|config| {
+            target_paths
+                .iter()
+                .any(|p| p.template == config.path && p.path.exists())
+        })
+        .collect();
+    let pre_restore = if !archive_conf.backup_before_restore || existing.is_empty() {
+        None
+    } else {
+        Some(archive_impl(
+            device_name,
+            archive_conf,
+            game_backup_dir.clone(),
+            existing,
+            ArchiveTrigger::PreRestore,
+            use_time,
+        )?)
+    };
 
     info!(
-        "restoring archive: from_path={}, to_paths={:?}",
-        archive_path.display(),
-        target_paths
+        "restoring archive: from={}, to_paths={:?}, files={:?}",
+        game_backup_dir.join(&archive_filename).display(),
+        target_paths,
+        files
     );
 
-    archive_conf.extract(file, target_paths)?;
+    let mirror = archive_conf.mirror_restore && files.is_none();
+    let staging = StagedRestore::prepare(&target_paths, mirror)?;
+    match &files {
+        Some(files) => archiver.extract_filtered(file, &staging.staged_targets(), &|path| {
+            is_selected(files, path)
+        })?,
+        None => archiver.extract(file, &staging.staged_targets())?,
+    }
+    staging.commit()?;
+
+    Ok(pre_restore)
+}
+
+/// List the entries of a local archive.
+pub fn list_entries_impl(
+    archive_conf: &ArchiveConfig,
+    game_backup_dir: &Path,
+    archive_filename: &str,
+) -> Result<Vec<ArchiveEntry>> {
+    let (archiver, file) = open_archive(archive_conf, game_backup_dir, archive_filename)?;
+    Ok(archiver.list(file)?)
+}
+
+/// Read a single file of a local archive.
+pub fn read_file_impl(
+    archive_conf: &ArchiveConfig,
+    game_backup_dir: &Path,
+    archive_filename: &str,
+    path: &str,
+) -> Result<Vec<u8>> {
+    let (archiver, file) = open_archive(archive_conf, game_backup_dir, archive_filename)?;
+    Ok(archiver.read_file(file, path.trim_matches('/'))?)
+}
+
+/// Changes from the local archive `old` to `new`. With `text_diff`, small
+/// text files that were modified come with a unified diff.
+pub fn diff_archives_impl(
+    archive_conf: &ArchiveConfig,
+    game_backup_dir: &Path,
+    old: &str,
+    new: &str,
+    text_diff: bool,
+) -> Result<Vec<FileChange>> {
+    let (archiver, file) = open_archive(archive_conf, game_backup_dir, old)?;
+    let old = FileSet::from_archive(&archiver, file, text_diff)?;
+    let (archiver, file) = open_archive(archive_conf, game_backup_dir, new)?;
+    let new = FileSet::from_archive(&archiver, file, text_diff)?;
+    Ok(diff::diff(old, new))
+}
 
+/// What restoring a local archive would change: the changes from the files
+/// currently in the save paths to the ones in the archive. Removed files are
+/// only deleted by a mirror restore.
+pub fn diff_with_saves_impl(
+    archive_conf: &ArchiveConfig,
+    game_backup_dir: &Path,
+    archive_filename: &str,
+    paths: Vec<SavePathConfig>,
+    text_diff: bool,
+) -> Result<Vec<FileChange>> {
+    let save_paths: Vec<SavePath> = paths.iter().map(SavePath::resolve).collect::<Result<_>>()?;
+    let (archiver, file) = open_archive(archive_conf, game_backup_dir, archive_filename)?;
+    let archived = FileSet::from_archive(&archiver, file, text_diff)?.normalize_names(&save_paths);
+    let live = FileSet::from_save_paths(&save_paths, text_diff)?;
+    Ok(diff::diff(live, archived))
+}
+
+/// Extract the selected entries of a local archive into `dest_dir`, each
+/// save path into a subdir named after its [`SavePath::name`]. Existing files
+/// are overwritten.
+pub fn extract_to_impl(
+    archive_conf: &ArchiveConfig,
+    game_backup_dir: &Path,
+    archive_filename: &str,
+    files: &[String],
+    dest_dir: &Path,
+) -> Result<()> {
+    let (archiver, mut file) = open_archive(archive_conf, game_backup_dir, archive_filename)?;
+    // targets come from the archive itself, so it does not matter whether
+    // the save paths are still in config
+    let roots: HashSet<String> = archiver
+        .list(&file)?
+        .into_iter()
+        .filter_map(|e| e.path.split('/').next().map(str::to_string))
+        .collect();
+    let targets: Vec<SavePath> = roots
+        .into_iter()
+        .map(|name| SavePath {
+            path: dest_dir.join(&name),
+            template: String::new(),
+            name,
+            filter: None,
+        })
+        .collect();
+    info!(
+        "extracting {files:?} of {archive_filename} to {}",
+        dest_dir.display()
+    );
+    fs::create_dir_all(dest_dir)?;
+    file.rewind()?;
+    archiver.extract_filtered(file, &targets, &|path| is_selected(files, path))?;
     Ok(())
 }
 
@@ -215,6 +802,30 @@ pub fn restore_impl(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_sort_by_age() {
+        let archive = |name: &str, meta: Option<ArchiveMeta>| ArchiveInfo {
+            name: name.to_string(),
+            size: 0,
+            format: None,
+            meta,
+        };
+        let mut newest = ArchiveMeta::legacy("20240101_000000_pc.tar").unwrap();
+        newest.created_at += Duration::days(1);
+        let mut archives = vec![
+            // renamed, so the filename tells nothing about its age
+            archive("a.tar", Some(newest)),
+            archive(
+                "20240101_000000_pc.tar",
+                ArchiveMeta::legacy("20240101_000000_pc.tar"),
+            ),
+            archive("unknown.tar", None),
+        ];
+        sort_by_age(&mut archives);
+        let names: Vec<&str> = archives.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["unknown.tar", "20240101_000000_pc.tar", "a.tar"]);
+    }
+
     fn test_archiver(archiver: impl Archive + Sized) -> io::Result<()> {
         // 1. Setup Source Environment
         let src_dir_1 = tempfile::tempdir()?;
@@ -229,7 +840,10 @@ mod tests {
         let subfile_path = dir2_path.join("sub.txt");
         fs::write(&subfile_path, "sub")?;
 
-        let paths_to_archive = vec![file1_path.clone(), dir2_path.clone()];
+        let paths_to_archive = vec![
+            SavePath::new("{a}/file1.txt", file1_path.clone()),
+            SavePath::new("{b}/data", dir2_path.clone()),
+        ];
         println!("paths_to_archive: {paths_to_archive:?}");
 
         // Using a file for the archive content to simulate real IO
@@ -243,18 +857,21 @@ mod tests {
             .create(true)
             .truncate(true)
             .open(&archive_file_path)?;
-        archiver.archive(paths_to_archive, &archive_file).unwrap();
+        archiver.archive(&paths_to_archive, &archive_file).unwrap();
 
         // 3. Prepare Restore Targets
         let dst_dir_2 = tempfile::tempdir()?;
         let dst_path_2 = dst_dir_2.path();
         let target_file = dst_path_2.join("file1.txt");
         let target_dir = dst_path_2.join("data");
-        let targets = vec![target_file.clone(), target_dir.clone()];
+        let targets = vec![
+            SavePath::new("{a}/file1.txt", target_file.clone()),
+            SavePath::new("{b}/data", target_dir.clone()),
+        ];
         println!("restore targets: {targets:?}");
 
         let mut reader = fs::File::open(&archive_file_path)?;
-        archiver.extract(&mut reader, targets).unwrap();
+        archiver.extract(&mut reader, &targets).unwrap();
 
         assert!(target_file.exists(), "Target file should exist");
         let content = fs::read_to_string(&target_file)?;
@@ -281,4 +898,259 @@ mod tests {
     fn test_squashfs_archiver() -> io::Result<()> {
         test_archiver(SquashfsArchiver(1))
     }
+
+    #[test]
+    fn test_detect_archive_format() -> io::Result<()> {
+        let src_dir = tempfile::tempdir()?;
+        let save = src_dir.path().join("save.dat");
+        fs::write(&save, "save")?;
+
+        let backup_dir = tempfile::tempdir()?;
+        for algorithm in [
+            ArchiveAlgo::SquashfsZstd,
+            ArchiveAlgo::Tar,
+            ArchiveAlgo::Snapshot,
+        ] {
+            // no extension, so only the content can tell
+            let archive_path = backup_dir.path().join(format!("{algorithm:?}"));
+            Archiver::new(algorithm, 1, backup_dir.path()).archive(
+                &[SavePath::new("save.dat", save.clone())],
+                fs::File::create(&archive_path)?,
+            )?;
+            assert_eq!(detect_archive_format(&archive_path)?, Some(algorithm));
+        }
+
+        let unknown = backup_dir.path().join("unknown.tar");
+        fs::write(&unknown, "")?;
+        assert_eq!(detect_archive_format(&unknown)?, Some(ArchiveAlgo::Tar));
+        Ok(())
+    }
+
+    #[test]
+    fn test_filtered_save_path() -> Result<()> {
+        let src_dir = tempfile::tempdir()?;
+        let dst_dir = tempfile::tempdir()?;
+        let save = src_dir.path().join("save");
+        fs::create_dir_all(save.join("cache"))?;
+        fs::write(save.join("1.sav"), "1")?;
+        fs::write(save.join("debug.log"), "log")?;
+        fs::write(save.join("cache").join("2.sav"), "2")?;
+        let source = SavePath {
+            filter: PathFilter::new(&SavePathConfig {
+                path: "{game}/save".to_string(),
+                include: vec!["*.sav".to_string()],
+                exclude: vec!["cache".to_string()],
+            })?,
+            ..SavePath::new("{game}/save", save)
+        };
+        let target = SavePath::new("{game}/save", dst_dir.path().join("save"));
+
+        let backup_dir = tempfile::tempdir()?;
+        for algorithm in [
+            ArchiveAlgo::SquashfsZstd,
+            ArchiveAlgo::Tar,
+            ArchiveAlgo::Snapshot,
+        ] {
+            let archive_path = backup_dir.path().join(format!("{algorithm:?}"));
+            let archiver = Archiver::new(algorithm, 1, backup_dir.path());
+            archiver.archive(
+                std::slice::from_ref(&source),
+                fs::File::create(&archive_path)?,
+            )?;
+            archiver.extract(
+                fs::File::open(&archive_path)?,
+                std::slice::from_ref(&target),
+            )?;
+            assert_eq!(fs::read_to_string(target.path.join("1.sav"))?, "1");
+            assert!(!target.path.join("debug.log").exists(), "{algorithm:?}");
+            assert!(!target.path.join("cache").exists(), "{algorithm:?}");
+            fs::remove_dir_all(&target.path)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_content_hash_is_reused_for_unchanged_saves() -> Result<()> {
+        let src_dir = tempfile::tempdir()?;
+        let save = src_dir.path().join("save");
+        fs::create_dir_all(&save)?;
+        fs::write(save.join("1.sav"), "1")?;
+        let paths = vec![SavePathConfig {
+            path: save.to_string_lossy().into_owned(),
+            ..Default::default()
+        }];
+        let backup_dir = tempfile::tempdir()?;
+        let conf = ArchiveConfig {
+            algorithm: ArchiveAlgo::Tar,
+            ..Default::default()
+        };
+        let archive = || {
+            archive_impl(
+                "test",
+                &conf,
+                backup_dir.path().to_path_buf(),
+                paths.clone(),
+                ArchiveTrigger::Manual,
+                Duration::zero(),
+            )
+        };
+
+        let first = archive()?;
+        let mut meta = read_meta(&backup_dir.path().join(&first))?.unwrap();
+        assert_eq!(
+            meta.content_hash.as_deref(),
+            Some(fingerprint_saves(&paths, true)?.as_str())
+        );
+        assert_eq!(
+            recorded_content_hash(backup_dir.path(), meta.fingerprint.as_deref().unwrap())?,
+            meta.content_hash
+        );
+
+        // a recorded hash is trusted as long as the quick fingerprint matches
+        meta.content_hash = Some("recorded".to_string());
+        write_meta(&backup_dir.path().join(&first), &meta)?;
+        let second = archive()?;
+        let reused = read_meta(&backup_dir.path().join(&second))?.unwrap();
+        assert_eq!(reused.content_hash.as_deref(), Some("recorded"));
+
+        fs::write(save.join("1.sav"), "changed")?;
+        assert_eq!(
+            recorded_content_hash(backup_dir.path(), &fingerprint_saves(&paths, false)?)?,
+            None
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_same_file_names_do_not_collide() -> io::Result<()> {
+        let src_dir = tempfile::tempdir()?;
+        let dst_dir = tempfile::tempdir()?;
+        let save_path = |root: &Path, i: usize| {
+            SavePath::new(
+                &format!("{{game}}/{i}/savedata"),
+                root.join(i.to_string()).join("savedata"),
+            )
+        };
+        let sources = [save_path(src_dir.path(), 1), save_path(src_dir.path(), 2)];
+        for (i, source) in sources.iter().enumerate() {
+            fs::create_dir_all(&source.path)?;
+            fs::write(source.path.join("slot"), i.to_string())?;
+        }
+        let targets = [save_path(dst_dir.path(), 1), save_path(dst_dir.path(), 2)];
+
+        let backup_dir = tempfile::tempdir()?;
+        for algorithm in [
+            ArchiveAlgo::SquashfsZstd,
+            ArchiveAlgo::Tar,
+            ArchiveAlgo::Snapshot,
+        ] {
+            let archive_path = backup_dir.path().join(format!("{algorithm:?}"));
+            let archiver = Archiver::new(algorithm, 1, backup_dir.path());
+            archiver.archive(&sources, fs::File::create(&archive_path)?)?;
+            archiver.extract(fs::File::open(&archive_path)?, &targets)?;
+            for (i, target) in targets.iter().enumerate() {
+                assert_eq!(
+                    fs::read_to_string(target.path.join("slot"))?,
+                    i.to_string(),
+                    "{algorithm:?}"
+                );
+            }
+            fs::remove_dir_all(dst_dir.path())?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_browse_and_extract_single_file() -> io::Result<()> {
+        let src_dir = tempfile::tempdir()?;
+        let source = SavePath::new("{game}/savedata", src_dir.path().join("savedata"));
+        fs::create_dir_all(source.path.join("slots"))?;
+        fs::write(source.path.join("slots/1.sav"), "slot 1")?;
+        fs::write(source.path.join("slots/2.sav"), "slot 2")?;
+        fs::write(source.path.join("system.dat"), "system")?;
+
+        let backup_dir = tempfile::tempdir()?;
+        let slot1 = format!("{}/slots/1.sav", source.name);
+        for algorithm in [
+            ArchiveAlgo::SquashfsZstd,
+            ArchiveAlgo::Tar,
+            ArchiveAlgo::Snapshot,
+        ] {
+            let archive_path = backup_dir.path().join(format!("{algorithm:?}"));
+            let archiver = Archiver::new(algorithm, 1, backup_dir.path());
+            archiver.archive(
+                std::slice::from_ref(&source),
+                fs::File::create(&archive_path)?,
+            )?;
+
+            let entries = archiver.list(fs::File::open(&archive_path)?)?;
+            let entry = entries.iter().find(|e| e.path == slot1);
+            assert_eq!(entry.map(|e| e.size), Some(6), "{algorithm:?}: {entries:?}");
+            assert!(entries.iter().any(|e| e.path == source.name && e.is_dir));
+            assert_eq!(
+                archiver.read_file(fs::File::open(&archive_path)?, &slot1)?,
+                b"slot 1"
+            );
+            assert!(
+                archiver
+                    .read_file(fs::File::open(&archive_path)?, "missing")
+                    .is_err()
+            );
+
+            let dst_dir = tempfile::tempdir()?;
+            let target = SavePath::new("{game}/savedata", dst_dir.path().join("savedata"));
+            archiver.extract_filtered(
+                fs::File::open(&archive_path)?,
+                std::slice::from_ref(&target),
+                &|path| is_selected(std::slice::from_ref(&slot1), path),
+            )?;
+            assert_eq!(
+                fs::read_to_string(target.path.join("slots/1.sav"))?,
+                "slot 1"
+            );
+            assert!(!target.path.join("slots/2.sav").exists(), "{algorithm:?}");
+            assert!(!target.path.join("system.dat").exists(), "{algorithm:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_selected() {
+        let selected = ["a/slots".to_string(), "b/system.dat".to_string()];
+        assert!(is_selected(&selected, "a/slots"));
+        assert!(is_selected(&selected, "a/slots/1.sav"));
+        assert!(!is_selected(&selected, "a/slots2"));
+        assert!(!is_selected(&selected, "a"));
+        assert!(is_selected(&selected, "b/system.dat"));
+    }
+
+    #[test]
+    fn test_old_layout_restores_by_file_name() -> io::Result<()> {
+        let src_dir = tempfile::tempdir()?;
+        let save_dir = src_dir.path().join("savedata");
+        fs::create_dir(&save_dir)?;
+        fs::write(save_dir.join("slot"), "old layout")?;
+
+        // archives used to store save paths under their file name
+        let archive_path = src_dir.path().join("old.tar");
+        let mut builder = ::tar::Builder::new(fs::File::create(&archive_path)?);
+        builder.append_dir_all("savedata", &save_dir)?;
+        builder.finish()?;
+        drop(builder);
+
+        let dst_dir = tempfile::tempdir()?;
+        let target = SavePath::new("{game}/savedata", dst_dir.path().join("savedata"));
+        TarArchiver.extract(
+            fs::File::open(&archive_path)?,
+            std::slice::from_ref(&target),
+        )?;
+        assert_eq!(fs::read_to_string(target.path.join("slot"))?, "old layout");
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_archiver() -> io::Result<()> {
+        let game_backup_dir = tempfile::tempdir()?;
+        test_archiver(SnapshotArchiver::new(game_backup_dir.path()))
+    }
 }
@@ -0,0 +1,196 @@
+//! Retention of archives: which archives to delete once there are too many.
+//!
+//! Besides the newest archives, one archive per day, week and month can be
+//! kept, like the grandfather-father-son scheme of backup tools. Pinned
+//! archives and archives of unknown age are never evicted.
+
+use chrono::{DateTime, Datelike as _, Local};
+
+use super::ArchiveInfo;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Number of the newest archives to keep.
+    pub keep_last: u32,
+    /// Number of days to keep the newest archive of, counting only the days
+    /// that have archives. The same for weeks and months below.
+    pub keep_daily: u32,
+    pub keep_weekly: u32,
+    pub keep_monthly: u32,
+}
+
+impl RetentionPolicy {
+    /// Whether the policy keeps everything.
+    #[inline]
+    pub fn is_unlimited(&self) -> bool {
+        self.keep_last == 0
+            && self.keep_daily == 0
+            && self.keep_weekly == 0
+            && self.keep_monthly == 0
+    }
+
+    /// The archives to delete, from the oldest to the newest.
+    pub fn evicted<'a>(&self, archives: &'a [ArchiveInfo]) -> Vec<&'a ArchiveInfo> {
+        if self.is_unlimited() {
+            return vec![];
+        }
+        let mut dated: Vec<(&ArchiveInfo, DateTime<Local>)> = archives
+            .iter()
+            .filter_map(|a| match &a.meta {
+                Some(meta) if !meta.pinned => Some((a, meta.created_at.with_timezone(&Local))),
+                _ => None,
+            })
+            .collect();
+        // newest first, so that the archives of a day are next to each other and
+        // the first of them is the one kept
+        dated.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| b.0.name.cmp(&a.0.name)));
+
+        let mut keep = vec![false; dated.len()];
+        keep.iter_mut()
+            .take(self.keep_last as usize)
+            .for_each(|k| *k = true);
+        keep_one_per(&dated, &mut keep, self.keep_daily, |t| {
+            (t.year(), t.ordinal())
+        });
+        keep_one_per(&dated, &mut keep, self.keep_weekly, |t| {
+            let week = t.iso_week();
+            (week.year(), week.week())
+        });
+        keep_one_per(&dated, &mut keep, self.keep_monthly, |t| {
+            (t.year(), t.month())
+        });
+
+        dated
+            .into_iter()
+            .zip(keep)
+            .filter(|(_, keep)| !keep)
+            .map(|((archive, _), _)| archive)
+            .rev()
+            .collect()
+    }
+}
+
+/// Mark the newest archive of each of the `count` newest periods as kept.
+/// `dated` must be sorted from the newest to the oldest.
+fn keep_one_per<K: PartialEq>(
+    dated: &[(&ArchiveInfo, DateTime<Local>)],
+    keep: &mut [bool],
+    count: u32,
+    period: impl Fn(&DateTime<Local>) -> K,
+) {
+    let mut last = None;
+    let mut kept = 0;
+    for (i, (_, time)) in dated.iter().enumerate() {
+        if kept >= count {
+            break;
+        }
+        let key = period(time);
+        if last.as_ref() != Some(&key) {
+            keep[i] = true;
+            kept += 1;
+            last = Some(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone as _};
+
+    use super::*;
+    use crate::archive::{ArchiveAlgo, ArchiveMeta, ArchiveTrigger};
+
+    fn archive(name: &str, created_at: DateTime<Local>, pinned: bool) -> ArchiveInfo {
+        ArchiveInfo {
+            name: name.to_string(),
+            size: 0,
+            format: None,
+            meta: Some(ArchiveMeta {
+                created_at: created_at.to_utc(),
+                device_uid: String::new(),
+                device_name: String::new(),
+                algorithm: ArchiveAlgo::Tar,
+                use_time: Duration::zero(),
+                trigger: ArchiveTrigger::AutoUpload,
+                note: None,
+                pinned,
+                fingerprint: None,
+                content_hash: None,
+            }),
+        }
+    }
+
+    fn evicted(policy: RetentionPolicy, archives: &[ArchiveInfo]) -> Vec<&str> {
+        policy
+            .evicted(archives)
+            .into_iter()
+            .map(|a| a.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn keeps_one_per_period() {
+        // 2024-01-01 is a Monday
+        let at = |d: u32, h: u32| Local.with_ymd_and_hms(2024, 1, d, h, 0, 0).unwrap();
+        let archives = vec![
+            archive("d1-a", at(1, 8), false),
+            archive("d1-b", at(1, 20), false),
+            archive("d2-a", at(2, 8), false),
+            archive("d2-b", at(2, 20), false),
+            archive("d8", at(8, 8), false),
+            archive("d9-a", at(9, 8), false),
+            archive("d9-b", at(9, 20), false),
+        ];
+
+        let last_two = RetentionPolicy {
+            keep_last: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            evicted(last_two, &archives),
+            ["d1-a", "d1-b", "d2-a", "d2-b", "d8"]
+        );
+
+        let daily = RetentionPolicy {
+            keep_daily: 3,
+            ..Default::default()
+        };
+        assert_eq!(evicted(daily, &archives), ["d1-a", "d1-b", "d2-a", "d9-a"]);
+
+        let weekly = RetentionPolicy {
+            keep_last: 1,
+            keep_weekly: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            evicted(weekly, &archives),
+            ["d1-a", "d1-b", "d2-a", "d8", "d9-a"]
+        );
+
+        let monthly = RetentionPolicy {
+            keep_monthly: 12,
+            ..Default::default()
+        };
+        assert_eq!(evicted(monthly, &archives).len(), archives.len() - 1);
+
+        assert!(evicted(RetentionPolicy::default(), &archives).is_empty());
+    }
+
+    #[test]
+    fn never_evicts_pinned_or_unknown() {
+        let at = |d: u32| Local.with_ymd_and_hms(2024, 1, d, 0, 0, 0).unwrap();
+        let mut unknown = archive("renamed", at(1), false);
+        unknown.meta = None;
+        let archives = vec![
+            archive("pinned", at(1), true),
+            unknown,
+            archive("old", at(2), false),
+            archive("new", at(3), false),
+        ];
+        let policy = RetentionPolicy {
+            keep_last: 1,
+            ..Default::default()
+        };
+        assert_eq!(evicted(policy, &archives), ["old"]);
+    }
+}
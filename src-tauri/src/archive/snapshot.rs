@@ -0,0 +1,590 @@
+//! Content-addressed, deduplicated snapshots.
+//!
+//! Instead of packing everything into a new archive, a snapshot splits every
+//! save file into chunks named by their SHA-256 and stores each chunk once in
+//! a chunk directory shared by all snapshots of the game. The "archive" file
+//! itself is only a small manifest listing the files and their chunks, so a
+//! new snapshot costs nothing but the chunks that actually changed.
+
+use std::{
+    collections::HashSet,
+    ffi::OsStr,
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    path::{Component, Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{ArchiveEntry, SavePath, not_found_in_archive, target_map};
+
+/// First line of every manifest.
+pub const SNAPSHOT_MAGIC: &str = "# galgame-manager snapshot v1\n";
+/// Name of the chunk directory, next to the manifests of a game.
+pub const CHUNKS_DIR: &str = "chunks";
+const CHUNK_SIZE: usize = 1024 * 1024;
+/// Extension of the temp file a chunk is written to.
+const PART_EXTENSION: &str = "part";
+/// Unreferenced chunks younger than this are never collected, locally or on
+/// remote, since they may belong to a snapshot, an upload or a pull whose
+/// manifest is not written yet.
+pub const CHUNK_GC_GRACE: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EntryKind {
+    File,
+    Dir,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotEntry {
+    /// `/`-separated path relative to the root, empty for the root itself.
+    pub path: String,
+    pub kind: EntryKind,
+    pub size: u64,
+    /// Unix timestamp in seconds.
+    pub mtime: u64,
+    /// Hashes of the file content chunks, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chunks: Vec<String>,
+}
+
+/// A top-level save path in the snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotRoot {
+    /// See [`SavePath::name`].
+    pub name: String,
+    #[serde(default)]
+    pub template: String,
+    pub entries: Vec<SnapshotEntry>,
+}
+
+impl SnapshotRoot {
+    /// Path of `entry` in the snapshot, see [`ArchiveEntry::path`].
+    pub fn entry_path(&self, entry: &SnapshotEntry) -> String {
+        if entry.path.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{}/{}", self.name, entry.path)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotManifest {
+    pub created: DateTime<Utc>,
+    pub roots: Vec<SnapshotRoot>,
+}
+
+impl SnapshotManifest {
+    pub fn from_reader(mut reader: impl Read) -> io::Result<Self> {
+        let mut content = String::new();
+        reader.read_to_string(&mut content)?;
+        Self::from_str(&content)
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(content: &str) -> io::Result<Self> {
+        let content = content
+            .strip_prefix(SNAPSHOT_MAGIC)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Not a snapshot manifest"))?;
+        toml::from_str(content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        let content = toml::to_string(self).map_err(io::Error::other)?;
+        writer.write_all(SNAPSHOT_MAGIC.as_bytes())?;
+        writer.write_all(content.as_bytes())?;
+        writer.flush()
+    }
+
+    /// All distinct chunks referenced by this snapshot.
+    pub fn chunks(&self) -> HashSet<&str> {
+        self.roots
+            .iter()
+            .flat_map(|r| &r.entries)
+            .flat_map(|e| &e.chunks)
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Total size of the files in this snapshot.
+    pub fn size(&self) -> u64 {
+        self.roots
+            .iter()
+            .flat_map(|r| &r.entries)
+            .map(|e| e.size)
+            .sum()
+    }
+}
+
+/// Whether `filename` is a snapshot manifest.
+#[inline]
+pub fn is_snapshot(filename: &str) -> bool {
+    filename.ends_with(&format!(".{}", super::ArchiveAlgo::Snapshot.ext()))
+}
+
+#[inline]
+pub fn chunk_hash(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Write `data` to `path` through a temp file, so that a chunk file is either
+/// complete or absent.
+pub fn write_chunk(path: &Path, data: &[u8]) -> io::Result<()> {
+    let part = path.with_extension(PART_EXTENSION);
+    fs::write(&part, data)?;
+    fs::rename(&part, path)
+}
+
+fn read_chunk(reader: &mut impl Read, buf: &mut Vec<u8>) -> io::Result<usize> {
+    buf.clear();
+    reader.take(CHUNK_SIZE as u64).read_to_end(buf)
+}
+
+/// Whether an entry path stays inside its root when joined to it, i.e. it
+/// only has plain components. A crafted manifest could otherwise write
+/// anywhere with `..` or an absolute path.
+fn is_inside_root(path: &str) -> bool {
+    Path::new(path)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+}
+
+fn mtime_of(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub(crate) struct SnapshotArchiver {
+    chunk_dir: PathBuf,
+}
+
+impl SnapshotArchiver {
+    /// Snapshots of the game whose archives live in `game_backup_dir`.
+    pub fn new(game_backup_dir: &Path) -> Self {
+        Self {
+            chunk_dir: game_backup_dir.join(CHUNKS_DIR),
+        }
+    }
+
+    /// Split the file at `path` into chunks, storing the missing ones.
+    fn store_file(&self, path: &Path) -> io::Result<Vec<String>> {
+        let mut file = File::open(path)?;
+        let mut buf = Vec::with_capacity(CHUNK_SIZE);
+        let mut chunks = vec![];
+        while read_chunk(&mut file, &mut buf)? > 0 {
+            let hash = chunk_hash(&buf);
+            let chunk_path = self.chunk_dir.join(&hash);
+            if !chunk_path.exists() {
+                write_chunk(&chunk_path, &buf)?;
+            }
+            chunks.push(hash);
+        }
+        Ok(chunks)
+    }
+
+    /// Reassemble a file from its chunks, verifying each of them.
+    fn restore_file(&self, entry: &SnapshotEntry, dest: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(dest)?);
+        self.read_chunks(entry, &mut writer)?;
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        let _ = file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(entry.mtime));
+        Ok(())
+    }
+
+    /// Write the content of a file to `writer`, verifying each chunk.
+    fn read_chunks(&self, entry: &SnapshotEntry, writer: &mut impl Write) -> io::Result<()> {
+        for hash in &entry.chunks {
+            let data = fs::read(self.chunk_dir.join(hash))?;
+            if chunk_hash(&data) != *hash {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Corrupted chunk: {hash}"),
+                ));
+            }
+            writer.write_all(&data)?;
+        }
+        Ok(())
+    }
+}
+
+impl super::Archive for SnapshotArchiver {
+    fn archive(&self, paths: &[SavePath], writer: impl io::Write + io::Seek) -> io::Result<()> {
+        fs::create_dir_all(&self.chunk_dir)?;
+        let mut roots = vec![];
+        for save_path in paths {
+            let root_path = save_path.path.as_path();
+
+            let mut entries = vec![];
+            for entry in save_path.walk() {
+                let entry = entry?;
+                let relative = entry
+                    .path()
+                    .strip_prefix(root_path)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                let metadata = entry.metadata().map_err(io::Error::other)?;
+                let (kind, chunks) = if entry.file_type().is_dir() {
+                    (EntryKind::Dir, vec![])
+                } else if entry.file_type().is_file() {
+                    (EntryKind::File, self.store_file(entry.path())?)
+                } else {
+                    // symlinks and special files are not supported
+                    warn!("skip unsupported file: {}", entry.path().display());
+                    continue;
+                };
+                entries.push(SnapshotEntry {
+                    path: relative,
+                    kind,
+                    size: if kind == EntryKind::File {
+                        metadata.len()
+                    } else {
+                        0
+                    },
+                    mtime: mtime_of(&metadata),
+                    chunks,
+                });
+            }
+            roots.push(SnapshotRoot {
+                name: save_path.name.clone(),
+                template: save_path.template.clone(),
+                entries,
+            });
+        }
+
+        SnapshotManifest {
+            created: Utc::now(),
+            roots,
+        }
+        .write_to(BufWriter::new(writer))
+    }
+
+    fn extract_filtered(
+        &self,
+        reader: impl io::Read + io::Seek + Send,
+        targets: &[SavePath],
+        filter: &dyn Fn(&str) -> bool,
+    ) -> io::Result<()> {
+        let manifest = SnapshotManifest::from_reader(reader)?;
+        let target_map = target_map(targets);
+        let mut selected = vec![];
+        for root in &manifest.roots {
+            let entries: Vec<_> = root
+                .entries
+                .iter()
+                .filter(|entry| filter(&root.entry_path(entry)))
+                .collect();
+            if entries.is_empty() {
+                continue;
+            }
+            if let Some(entry) = entries.iter().find(|e| !is_inside_root(&e.path)) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid path in snapshot: {}", entry.path),
+                ));
+            }
+            let target = target_map.get(OsStr::new(&root.name)).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("No target for snapshot root: {}", root.name),
+                )
+            })?;
+            selected.push((target, entries));
+        }
+        // check all chunks first, so that a snapshot with missing chunks does
+        // not touch anything
+        if let Some(missing) = selected
+            .iter()
+            .flat_map(|(_, entries)| entries.iter().flat_map(|e| &e.chunks))
+            .find(|hash| !self.chunk_dir.join(hash).exists())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Chunk not found: {missing}"),
+            ));
+        }
+
+        for (target, entries) in selected {
+            for entry in entries {
+                let dest = if entry.path.is_empty() {
+                    target.to_path_buf()
+                } else {
+                    target.join(&entry.path)
+                };
+                match entry.kind {
+                    EntryKind::Dir => fs::create_dir_all(&dest)?,
+                    EntryKind::File => {
+                        if let Some(parent) = dest.parent() {
+                            fs::create_dir_all(parent)?;
+                        }
+                        self.restore_file(entry, &dest)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn list(&self, reader: impl io::Read + io::Seek + Send) -> io::Result<Vec<ArchiveEntry>> {
+        let manifest = SnapshotManifest::from_reader(reader)?;
+        Ok(manifest
+            .roots
+            .iter()
+            .flat_map(|root| {
+                root.entries.iter().map(|entry| ArchiveEntry {
+                    path: root.entry_path(entry),
+                    is_dir: entry.kind == EntryKind::Dir,
+                    size: entry.size,
+                    mtime: entry.mtime,
+                })
+            })
+            .collect())
+    }
+
+    fn read_file(
+        &self,
+        reader: impl io::Read + io::Seek + Send,
+        path: &str,
+    ) -> io::Result<Vec<u8>> {
+        let manifest = SnapshotManifest::from_reader(reader)?;
+        let entry = manifest
+            .roots
+            .iter()
+            .flat_map(|root| root.entries.iter().map(move |entry| (root, entry)))
+            .find(|(root, entry)| entry.kind == EntryKind::File && root.entry_path(entry) == path)
+            .map(|(_, entry)| entry)
+            .ok_or_else(|| not_found_in_archive(path))?;
+        let mut buf = Vec::with_capacity(entry.size as usize);
+        self.read_chunks(entry, &mut buf)?;
+        Ok(buf)
+    }
+
+    fn visit_files(
+        &self,
+        reader: impl io::Read + io::Seek + Send,
+        visit: &mut dyn FnMut(ArchiveEntry, &mut dyn io::Read) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let manifest = SnapshotManifest::from_reader(reader)?;
+        for root in &manifest.roots {
+            for entry in &root.entries {
+                if entry.kind != EntryKind::File {
+                    continue;
+                }
+                let mut buf = Vec::with_capacity(entry.size as usize);
+                self.read_chunks(entry, &mut buf)?;
+                let info = ArchiveEntry {
+                    path: root.entry_path(entry),
+                    is_dir: false,
+                    size: entry.size,
+                    mtime: entry.mtime,
+                };
+                visit(info, &mut buf.as_slice())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether the chunk file with `metadata` was written within
+/// [`CHUNK_GC_GRACE`]. Chunks of unknown age count as recent.
+fn is_recent_chunk(metadata: &fs::Metadata) -> bool {
+    metadata.modified().map_or(true, |modified| {
+        modified.elapsed().is_ok_and(|age| age < CHUNK_GC_GRACE)
+    })
+}
+
+/// Delete the chunks in `game_backup_dir` that no snapshot refers to anymore,
+/// except the ones younger than [`CHUNK_GC_GRACE`] and the partial ones
+/// [`write_chunk`] is still writing. Returns the number of deleted chunks.
+///
+/// Nothing is deleted if any manifest cannot be read, since its chunks would
+/// be lost.
+pub fn gc_chunks(game_backup_dir: &Path) -> io::Result<usize> {
+    let chunk_dir = game_backup_dir.join(CHUNKS_DIR);
+    if !chunk_dir.exists() {
+        return Ok(0);
+    }
+    let mut manifests = vec![];
+    for entry in fs::read_dir(game_backup_dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() && is_snapshot(&entry.file_name().to_string_lossy()) {
+            manifests.push(SnapshotManifest::from_reader(File::open(entry.path())?)?);
+        }
+    }
+    let referenced: HashSet<&str> = manifests.iter().flat_map(|m| m.chunks()).collect();
+
+    let mut deleted = 0;
+    for entry in fs::read_dir(&chunk_dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let metadata = entry.metadata()?;
+        if metadata.is_file()
+            && path.extension() != Some(OsStr::new(PART_EXTENSION))
+            && !referenced.contains(entry.file_name().to_string_lossy().as_ref())
+            && !is_recent_chunk(&metadata)
+        {
+            fs::remove_file(path)?;
+            deleted += 1;
+        }
+    }
+    if deleted > 0 {
+        info!(
+            "removed {deleted} unreferenced chunk(s) from {}",
+            chunk_dir.display()
+        );
+    }
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::Archive;
+
+    #[test]
+    fn unchanged_files_are_stored_once() -> io::Result<()> {
+        let src = tempfile::tempdir()?;
+        let save_dir = src.path().join("save");
+        fs::create_dir(&save_dir)?;
+        fs::write(save_dir.join("slot1"), "slot1")?;
+        fs::write(save_dir.join("slot2"), vec![7; CHUNK_SIZE + 1])?;
+
+        let backup = tempfile::tempdir()?;
+        let archiver = SnapshotArchiver::new(backup.path());
+        let chunk_count = || {
+            fs::read_dir(backup.path().join(CHUNKS_DIR))
+                .unwrap()
+                .count()
+        };
+
+        let save_paths = [SavePath::new("{game}/save", save_dir.clone())];
+        let first = backup.path().join("1.snapshot");
+        archiver.archive(&save_paths, File::create(&first)?)?;
+        assert_eq!(chunk_count(), 3);
+
+        fs::write(save_dir.join("slot1"), "changed")?;
+        let second = backup.path().join("2.snapshot");
+        archiver.archive(&save_paths, File::create(&second)?)?;
+        assert_eq!(chunk_count(), 4);
+
+        let dst = tempfile::tempdir()?;
+        let target = dst.path().join("save");
+        archiver.extract(
+            File::open(&first)?,
+            &[SavePath::new("{game}/save", target.clone())],
+        )?;
+        assert_eq!(fs::read_to_string(target.join("slot1"))?, "slot1");
+        assert_eq!(fs::read(target.join("slot2"))?, vec![7; CHUNK_SIZE + 1]);
+
+        fs::remove_file(&first)?;
+        // unreferenced chunks are kept for a while
+        assert_eq!(gc_chunks(backup.path())?, 0);
+        age_chunks(backup.path())?;
+        assert_eq!(gc_chunks(backup.path())?, 1);
+        assert_eq!(chunk_count(), 3);
+        Ok(())
+    }
+
+    /// Make the chunks in `game_backup_dir` older than [`CHUNK_GC_GRACE`].
+    fn age_chunks(game_backup_dir: &Path) -> io::Result<()> {
+        let old = SystemTime::now() - CHUNK_GC_GRACE * 2;
+        for entry in fs::read_dir(game_backup_dir.join(CHUNKS_DIR))? {
+            File::options()
+                .write(true)
+                .open(entry?.path())?
+                .set_modified(old)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn gc_keeps_partial_chunks() -> io::Result<()> {
+        let backup = tempfile::tempdir()?;
+        let chunk_dir = backup.path().join(CHUNKS_DIR);
+        fs::create_dir(&chunk_dir)?;
+        let hash = chunk_hash(b"chunk");
+        write_chunk(&chunk_dir.join(&hash), b"chunk")?;
+        let part = chunk_dir.join(&hash).with_extension(PART_EXTENSION);
+        fs::write(&part, "half")?;
+        age_chunks(backup.path())?;
+
+        assert_eq!(gc_chunks(backup.path())?, 1);
+        assert!(!chunk_dir.join(&hash).exists());
+        assert!(part.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn missing_chunk_fails_before_writing() -> io::Result<()> {
+        let src = tempfile::tempdir()?;
+        let file = src.path().join("system.dat");
+        fs::write(&file, "data")?;
+
+        let backup = tempfile::tempdir()?;
+        let archiver = SnapshotArchiver::new(backup.path());
+        let manifest = backup.path().join("1.snapshot");
+        archiver.archive(
+            &[SavePath::new("system.dat", file.clone())],
+            File::create(&manifest)?,
+        )?;
+        fs::remove_dir_all(backup.path().join(CHUNKS_DIR))?;
+
+        let dst = tempfile::tempdir()?;
+        let target = dst.path().join("system.dat");
+        let targets = [SavePath::new("system.dat", target.clone())];
+        assert!(archiver.extract(File::open(&manifest)?, &targets).is_err());
+        assert!(!target.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn paths_outside_the_target_are_rejected() -> io::Result<()> {
+        let backup = tempfile::tempdir()?;
+        let archiver = SnapshotArchiver::new(backup.path());
+        let dst = tempfile::tempdir()?;
+        let target = dst.path().join("save");
+        let targets = [SavePath::new("{game}/save", target.clone())];
+
+        for path in ["../escaped", "/tmp/escaped", "a/../../escaped"] {
+            let manifest = SnapshotManifest {
+                created: Utc::now(),
+                roots: vec![SnapshotRoot {
+                    name: targets[0].name.clone(),
+                    template: targets[0].template.clone(),
+                    entries: vec![SnapshotEntry {
+                        path: path.to_string(),
+                        kind: EntryKind::File,
+                        size: 0,
+                        mtime: 0,
+                        chunks: vec![],
+                    }],
+                }],
+            };
+            let mut data = vec![];
+            manifest.write_to(&mut data)?;
+            let err = archiver
+                .extract(io::Cursor::new(data), &targets)
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{path}");
+        }
+        assert!(!dst.path().join("escaped").exists());
+        assert!(!target.exists());
+        Ok(())
+    }
+}
@@ -4,7 +4,7 @@ use std::{
     collections::HashMap,
     ffi::OsStr,
     fs::{self, File},
-    io::{self, BufReader, BufWriter},
+    io::{self, BufReader, BufWriter, Cursor, Read as _},
     path::{Component, Path, PathBuf},
     time::{Duration, SystemTime},
 };
@@ -15,8 +15,11 @@ use backhand::{
     compression::{CompressionOptions, Compressor, Zstd},
     kind::{self, Kind},
 };
-use pathdiff::diff_paths;
-use walkdir::WalkDir;
+
+use super::{
+    ARCHIVE_MANIFEST, ArchiveEntry, ArchiveManifest, SavePath, not_found_in_archive, target_map,
+    to_entry_path,
+};
 
 pub(crate) struct SquashfsArchiver(pub(crate) u8);
 
@@ -91,11 +94,7 @@ impl SquashfsArchiver {
 }
 
 impl super::Archive for SquashfsArchiver {
-    fn archive(
-        &self,
-        paths: Vec<impl AsRef<Path>>,
-        writer: impl io::Write + io::Seek,
-    ) -> io::Result<()> {
+    fn archive(&self, paths: &[SavePath], writer: impl io::Write + io::Seek) -> io::Result<()> {
         let mut fs = FilesystemWriter::default();
         fs.set_current_time();
         fs.set_block_size(DEFAULT_BLOCK_SIZE);
@@ -110,27 +109,41 @@ impl super::Archive for SquashfsArchiver {
         let compressor = FilesystemCompressor::new(Compressor::Zstd, Some(compression_options))?;
         fs.set_compressor(compressor);
 
-        // 遍历输入的顶层路径
-        for root_path in paths {
-            let root_path = root_path.as_ref();
+        let manifest = ArchiveManifest::new(paths).to_bytes()?;
+        let manifest_header = NodeHeader {
+            permissions: 0o644,
+            uid: 0,
+            gid: 0,
+            mtime: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_secs() as u32)
+                .unwrap_or(0),
+        };
+        fs.push_file(Cursor::new(manifest), ARCHIVE_MANIFEST, manifest_header)?;
 
-            // 计算父目录，用于生成归档内的相对路径
-            // 例如：输入 /a/b/data，parent 是 /a/b，归档内路径应为 /data/...
-            let parent_dir = root_path.parent().ok_or_else(|| {
-                io::Error::new(io::ErrorKind::InvalidInput, "Invalid path: no parent")
-            })?;
+        // 遍历输入的顶层路径
+        for save_path in paths {
+            let root_path = save_path.path.as_path();
+            // 归档内的顶层名称为 save path 的稳定名称
+            // 例如：输入 /a/b/data，归档内路径应为 /{name}/...
+            let root_name = Path::new(&save_path.name);
 
-            // 使用 WalkDir 递归遍历（包括目录本身）
+            // 递归遍历（包括目录本身），跳过 include/exclude 规则排除的文件
             // 归档链接本身，而不是链接指向的内容
-            for entry in WalkDir::new(root_path).follow_links(false) {
-                let entry = entry.map_err(io::Error::other)?;
+            for entry in save_path.walk() {
+                let entry = entry?;
                 let src_path = entry.path();
 
-                // 计算归档内的路径： / + (src_path - parent_dir)
-                // 例：src=/usr/bin/tool, parent=/usr, rel=bin/tool, archive=/bin/tool
-                let relative_path = diff_paths(src_path, parent_dir).ok_or_else(|| {
-                    io::Error::new(io::ErrorKind::InvalidData, "Path diff failed")
-                })?;
+                // 计算归档内的路径： / + name + (src_path - root_path)
+                // 例：src=/usr/bin/tool, root=/usr/bin, rel={name}/tool
+                let rest = src_path
+                    .strip_prefix(root_path)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                let relative_path = if rest.as_os_str().is_empty() {
+                    root_name.to_path_buf()
+                } else {
+                    root_name.join(rest)
+                };
 
                 let header = Self::create_header(src_path)?;
 
@@ -154,33 +167,36 @@ impl super::Archive for SquashfsArchiver {
         Ok(())
     }
 
-    fn extract(
+    fn extract_filtered(
         &self,
         reader: impl io::Read + io::Seek + Send,
-        targets: Vec<impl AsRef<Path>>,
+        targets: &[SavePath],
+        filter: &dyn Fn(&str) -> bool,
     ) -> io::Result<()> {
         let mut buf_reader = BufReader::new(reader);
         let fs = FilesystemReader::from_reader(&mut buf_reader)?;
 
-        // 构建映射表：目标文件名 -> 完整目标路径
-        // 假设：Archive 中的顶层目录名 与 targets 中的文件名一一对应
-        let mut target_map: HashMap<&OsStr, PathBuf> = HashMap::new();
-        for target in &targets {
-            let target_path = target.as_ref();
-            if let Some(name) = target_path.file_name() {
-                target_map.insert(name, target_path.to_path_buf());
-            }
-        }
+        // 构建映射表：归档内顶层名称 -> 完整目标路径
+        // 旧归档以文件名为顶层名称，也一并映射
+        let target_map = target_map(targets);
+        let manifest_path = Path::new("/").join(ARCHIVE_MANIFEST);
 
         // 遍历镜像中的所有节点
         for node in fs.files() {
             let path_in_image = &node.fullpath;
-            // skip root dir
-            if path_in_image == Path::new("/") {
+            // skip root dir and manifest
+            if path_in_image == Path::new("/") || *path_in_image == manifest_path {
+                continue;
+            }
+            if !filter(&to_entry_path(path_in_image)) {
                 continue;
             }
 
             let dest_path = self.get_dest_from_fullpath(&node.fullpath, &target_map)?;
+            // 仅提取部分文件时，父目录节点可能被跳过
+            if let Some(parent) = dest_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
 
             // 处理不同类型的节点
             match &node.inner {
@@ -220,6 +236,71 @@ impl super::Archive for SquashfsArchiver {
 
         Ok(())
     }
+
+    fn list(&self, reader: impl io::Read + io::Seek + Send) -> io::Result<Vec<ArchiveEntry>> {
+        let mut buf_reader = BufReader::new(reader);
+        let fs = FilesystemReader::from_reader(&mut buf_reader)?;
+        let manifest_path = Path::new("/").join(ARCHIVE_MANIFEST);
+
+        Ok(fs
+            .files()
+            .filter(|node| node.fullpath != Path::new("/") && node.fullpath != manifest_path)
+            .map(|node| ArchiveEntry {
+                path: to_entry_path(&node.fullpath),
+                is_dir: matches!(node.inner, InnerNode::Dir(_)),
+                size: match &node.inner {
+                    InnerNode::File(file_info) => file_info.file_len() as u64,
+                    _ => 0,
+                },
+                mtime: node.header.mtime as u64,
+            })
+            .collect())
+    }
+
+    fn read_file(
+        &self,
+        reader: impl io::Read + io::Seek + Send,
+        path: &str,
+    ) -> io::Result<Vec<u8>> {
+        let mut buf_reader = BufReader::new(reader);
+        let fs = FilesystemReader::from_reader(&mut buf_reader)?;
+
+        for node in fs.files() {
+            if let InnerNode::File(file_info) = &node.inner
+                && to_entry_path(&node.fullpath) == path
+            {
+                let mut buf = Vec::with_capacity(file_info.file_len());
+                fs.file(file_info).reader().read_to_end(&mut buf)?;
+                return Ok(buf);
+            }
+        }
+        Err(not_found_in_archive(path))
+    }
+
+    fn visit_files(
+        &self,
+        reader: impl io::Read + io::Seek + Send,
+        visit: &mut dyn FnMut(ArchiveEntry, &mut dyn io::Read) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let mut buf_reader = BufReader::new(reader);
+        let fs = FilesystemReader::from_reader(&mut buf_reader)?;
+        let manifest_path = Path::new("/").join(ARCHIVE_MANIFEST);
+
+        for node in fs.files() {
+            if let InnerNode::File(file_info) = &node.inner
+                && node.fullpath != manifest_path
+            {
+                let info = ArchiveEntry {
+                    path: to_entry_path(&node.fullpath),
+                    is_dir: false,
+                    size: file_info.file_len() as u64,
+                    mtime: node.header.mtime as u64,
+                };
+                visit(info, &mut fs.file(file_info).reader())?;
+            }
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
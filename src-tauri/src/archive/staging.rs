@@ -0,0 +1,384 @@
+//! Transactional restore.
+//!
+//! Archives are never extracted over live saves directly. Each target is
+//! staged next to itself (so that renames stay on the same filesystem), and
+//! only after the whole archive has been extracted are the staged copies
+//! swapped in. If a swap fails, the already swapped targets are put back.
+//! Old saves that cannot be put back are kept in the staging dir, and never
+//! deleted by a later restore.
+
+use std::{
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use log::{error, warn};
+use walkdir::WalkDir;
+
+use super::SavePath;
+
+/// Name of the staging dir created in the parent of each target.
+const STAGING_DIR: &str = ".galgame-restore";
+
+struct Staged {
+    target: SavePath,
+    staged: PathBuf,
+    /// Where the live target is moved to while swapping.
+    old: PathBuf,
+    /// Whether the files excluded from archiving are copied from the live
+    /// target once the archive is extracted.
+    keep_excluded: bool,
+    moved_old: bool,
+    swapped: bool,
+}
+
+pub(crate) struct StagedRestore {
+    entries: Vec<Staged>,
+}
+
+fn remove_all(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(m) if m.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// `dir/<name><suffix>`, numbered if it is taken, e.g. by the old saves a
+/// failed rollback kept.
+fn unused_path(dir: &Path, name: &OsStr, suffix: &str) -> PathBuf {
+    (0..)
+        .map(|i| {
+            let mut name = name.to_os_string();
+            name.push(suffix);
+            if i > 0 {
+                name.push(format!(".{i}"));
+            }
+            dir.join(name)
+        })
+        .find(|path| fs::symlink_metadata(path).is_err())
+        .expect("some path is unused")
+}
+
+/// Copy the file or dir `src` to `dst` recursively, skipping the entries
+/// `keep` rejects. Files already in `dst` are kept.
+fn copy_all(src: &Path, dst: &Path, keep: impl Fn(&Path) -> bool) -> io::Result<()> {
+    if !src.is_dir() {
+        // a save path can be a single file
+        if keep(src) && fs::symlink_metadata(dst).is_err() {
+            fs::copy(src, dst)?;
+        }
+        return Ok(());
+    }
+    for entry in WalkDir::new(src).follow_links(false) {
+        let entry = entry.map_err(io::Error::other)?;
+        if !keep(entry.path()) {
+            continue;
+        }
+        let dest = dst.join(
+            entry
+                .path()
+                .strip_prefix(src)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        );
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&dest)?;
+        } else if entry.file_type().is_file() {
+            if fs::symlink_metadata(&dest).is_ok() {
+                continue;
+            }
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(entry.path(), &dest)?;
+        }
+    }
+    Ok(())
+}
+
+impl StagedRestore {
+    /// Prepare staging for `targets`. Unless `mirror` is set, the staged
+    /// copies start as copies of the live targets, so that files missing in
+    /// the archive are kept. Even if it is set, files excluded from archiving
+    /// by the save path's rules are kept.
+    pub fn prepare(targets: &[SavePath], mirror: bool) -> io::Result<Self> {
+        let mut this = Self { entries: vec![] };
+        for save_path in targets {
+            let target = &save_path.path;
+            let (Some(parent), Some(name)) = (target.parent(), target.file_name()) else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Invalid restore target: {}", target.display()),
+                ));
+            };
+            let staging_dir = parent.join(STAGING_DIR);
+            let staged = staging_dir.join(name);
+            let old = unused_path(&staging_dir, name, ".old");
+            if old.extension() != Some(OsStr::new("old")) {
+                warn!(
+                    "old saves kept by a failed restore are in {}",
+                    staging_dir.display()
+                );
+            }
+            this.entries.push(Staged {
+                target: save_path.clone(),
+                staged: staged.clone(),
+                old,
+                keep_excluded: mirror && save_path.filter.is_some(),
+                moved_old: false,
+                swapped: false,
+            });
+
+            // leftovers of an interrupted restore
+            remove_all(&staged)?;
+            fs::create_dir_all(&staging_dir)?;
+            if !mirror && target.exists() {
+                copy_all(target, &staged, |_| true)?;
+            }
+        }
+        Ok(this)
+    }
+
+    /// Targets to extract the archive to, in the same order as the targets.
+    pub fn staged_targets(&self) -> Vec<SavePath> {
+        self.entries
+            .iter()
+            .map(|e| SavePath {
+                path: e.staged.clone(),
+                ..e.target.clone()
+            })
+            .collect()
+    }
+
+    /// Swap the staged copies in. Targets without a staged copy (not in the
+    /// archive) are left untouched. On error, everything is rolled back.
+    pub fn commit(mut self) -> io::Result<()> {
+        self.copy_excluded()?;
+        if let Err(e) = self.swap() {
+            error!("failed to swap in restored saves, rolling back: {e}");
+            self.rollback();
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Copy the files excluded from archiving into the staged copies that the
+    /// archive was extracted to.
+    fn copy_excluded(&self) -> io::Result<()> {
+        for entry in &self.entries {
+            let target = &entry.target;
+            if entry.keep_excluded
+                && fs::symlink_metadata(&entry.staged).is_ok()
+                && target.path.exists()
+            {
+                copy_all(&target.path, &entry.staged, |path| {
+                    !target.is_archived(path)
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn swap(&mut self) -> io::Result<()> {
+        for entry in &mut self.entries {
+            if fs::symlink_metadata(&entry.staged).is_err() {
+                continue;
+            }
+            let target = &entry.target.path;
+            if fs::symlink_metadata(target).is_ok() {
+                fs::rename(target, &entry.old)?;
+                entry.moved_old = true;
+            }
+            fs::rename(&entry.staged, target)?;
+            entry.swapped = true;
+        }
+        Ok(())
+    }
+
+    fn rollback(&mut self) {
+        for entry in self.entries.iter_mut().rev() {
+            let target = &entry.target.path;
+            if entry.swapped {
+                if let Err(e) = remove_all(target) {
+                    error!("rollback: failed to remove {}: {e}", target.display());
+                    continue;
+                }
+                entry.swapped = false;
+            }
+            if entry.moved_old
+                && let Err(e) = fs::rename(&entry.old, target)
+            {
+                error!(
+                    "rollback: failed to move {} back to {}: {e}",
+                    entry.old.display(),
+                    target.display()
+                );
+                continue;
+            }
+            entry.moved_old = false;
+        }
+    }
+}
+
+impl Drop for StagedRestore {
+    /// Remove the staging dirs, keeping the old saves if a rollback failed.
+    fn drop(&mut self) {
+        for entry in &self.entries {
+            if let Err(e) = remove_all(&entry.staged) {
+                warn!("failed to clean up {}: {e}", entry.staged.display());
+            }
+            if !entry.moved_old || entry.swapped {
+                _ = remove_all(&entry.old);
+            }
+            if let Some(dir) = entry.staged.parent() {
+                // fails if not empty, e.g. shared with other targets
+                _ = fs::remove_dir(dir);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{archive::filter::PathFilter, db::SavePathConfig};
+
+    fn setup() -> io::Result<(tempfile::TempDir, SavePath)> {
+        let dir = tempfile::tempdir()?;
+        let save = dir.path().join("save");
+        fs::create_dir(&save)?;
+        fs::write(save.join("slot1"), "old")?;
+        fs::write(save.join("slot9"), "newer slot")?;
+        Ok((dir, SavePath::new("{game}/save", save)))
+    }
+
+    #[test]
+    fn overlay_keeps_extra_files() -> io::Result<()> {
+        let (dir, save) = setup()?;
+        let staging = StagedRestore::prepare(std::slice::from_ref(&save), false)?;
+        fs::write(staging.staged_targets()[0].path.join("slot1"), "restored")?;
+        staging.commit()?;
+
+        assert_eq!(fs::read_to_string(save.path.join("slot1"))?, "restored");
+        assert!(save.path.join("slot9").exists());
+        assert!(!dir.path().join(STAGING_DIR).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn mirror_removes_extra_files() -> io::Result<()> {
+        let (dir, save) = setup()?;
+        let staging = StagedRestore::prepare(std::slice::from_ref(&save), true)?;
+        let staged = staging.staged_targets()[0].path.clone();
+        fs::create_dir(&staged)?;
+        fs::write(staged.join("slot1"), "restored")?;
+        staging.commit()?;
+
+        assert_eq!(fs::read_to_string(save.path.join("slot1"))?, "restored");
+        assert!(!save.path.join("slot9").exists());
+        assert!(!dir.path().join(STAGING_DIR).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn mirror_keeps_excluded_files() -> crate::error::Result<()> {
+        let (_dir, mut save) = setup()?;
+        save.filter = PathFilter::new(&SavePathConfig {
+            path: save.template.clone(),
+            include: vec![],
+            exclude: vec!["slot9".to_string()],
+        })?;
+        let staging = StagedRestore::prepare(std::slice::from_ref(&save), true)?;
+        let staged = staging.staged_targets()[0].path.clone();
+        fs::create_dir_all(&staged)?;
+        fs::write(staged.join("slot1"), "restored")?;
+        staging.commit()?;
+
+        assert_eq!(fs::read_to_string(save.path.join("slot1"))?, "restored");
+        assert_eq!(fs::read_to_string(save.path.join("slot9"))?, "newer slot");
+        Ok(())
+    }
+
+    #[test]
+    fn mirror_skips_targets_not_in_the_archive() -> crate::error::Result<()> {
+        let (_dir, mut save) = setup()?;
+        save.filter = PathFilter::new(&SavePathConfig {
+            path: save.template.clone(),
+            include: vec![],
+            exclude: vec!["slot9".to_string()],
+        })?;
+        let staging = StagedRestore::prepare(std::slice::from_ref(&save), true)?;
+        staging.commit()?;
+
+        assert_eq!(fs::read_to_string(save.path.join("slot1"))?, "old");
+        assert_eq!(fs::read_to_string(save.path.join("slot9"))?, "newer slot");
+        Ok(())
+    }
+
+    #[test]
+    fn old_saves_kept_by_a_failed_rollback_survive() -> io::Result<()> {
+        let (dir, save) = setup()?;
+        let kept = dir.path().join(STAGING_DIR).join("save.old");
+        fs::create_dir_all(&kept)?;
+        fs::write(kept.join("slot1"), "kept")?;
+
+        let staging = StagedRestore::prepare(std::slice::from_ref(&save), false)?;
+        fs::write(staging.staged_targets()[0].path.join("slot1"), "restored")?;
+        staging.commit()?;
+
+        assert_eq!(fs::read_to_string(save.path.join("slot1"))?, "restored");
+        assert_eq!(fs::read_to_string(kept.join("slot1"))?, "kept");
+        assert_eq!(fs::read_dir(dir.path().join(STAGING_DIR))?.count(), 1);
+        Ok(())
+    }
+
+    fn setup_file() -> io::Result<(tempfile::TempDir, SavePath)> {
+        let dir = tempfile::tempdir()?;
+        let save = dir.path().join("system.dat");
+        fs::write(&save, "old")?;
+        Ok((dir, SavePath::new("{game}/system.dat", save)))
+    }
+
+    #[test]
+    fn overlay_restores_file_targets() -> io::Result<()> {
+        let (dir, save) = setup_file()?;
+        let staging = StagedRestore::prepare(std::slice::from_ref(&save), false)?;
+        let staged = staging.staged_targets()[0].path.clone();
+        assert_eq!(fs::read_to_string(&staged)?, "old");
+        fs::write(&staged, "restored")?;
+        staging.commit()?;
+
+        assert_eq!(fs::read_to_string(&save.path)?, "restored");
+        assert!(!dir.path().join(STAGING_DIR).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn mirror_restores_file_targets() -> io::Result<()> {
+        let (dir, save) = setup_file()?;
+        let staging = StagedRestore::prepare(std::slice::from_ref(&save), true)?;
+        fs::write(&staging.staged_targets()[0].path, "restored")?;
+        staging.commit()?;
+
+        assert_eq!(fs::read_to_string(&save.path)?, "restored");
+        assert!(!dir.path().join(STAGING_DIR).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn aborted_restore_leaves_saves_untouched() -> io::Result<()> {
+        let (dir, save) = setup()?;
+        let staging = StagedRestore::prepare(std::slice::from_ref(&save), false)?;
+        fs::write(
+            staging.staged_targets()[0].path.join("slot1"),
+            "half written",
+        )?;
+        drop(staging);
+
+        assert_eq!(fs::read_to_string(save.path.join("slot1"))?, "old");
+        assert!(!dir.path().join(STAGING_DIR).exists());
+        Ok(())
+    }
+}
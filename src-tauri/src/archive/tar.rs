@@ -1,22 +1,38 @@
 use std::{
-    fs, io,
+    fs,
+    io::{self, Read as _},
     path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
-use tar::Builder;
+use tar::{Builder, Header};
+
+use super::{
+    ARCHIVE_MANIFEST, ArchiveEntry, ArchiveManifest, SavePath, not_found_in_archive, target_map,
+    to_entry_path,
+};
 
 pub(crate) struct TarArchiver;
 
 impl super::Archive for TarArchiver {
-    fn archive(
-        &self,
-        paths: Vec<impl AsRef<Path>>,
-        writer: impl io::Write + io::Seek,
-    ) -> io::Result<()> {
+    fn archive(&self, paths: &[SavePath], writer: impl io::Write + io::Seek) -> io::Result<()> {
         let mut builder = Builder::new(writer);
 
-        for path in paths {
-            let path = path.as_ref();
+        let manifest = ArchiveManifest::new(paths).to_bytes()?;
+        let mut header = Header::new_gnu();
+        header.set_size(manifest.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        );
+        header.set_cksum();
+        builder.append_data(&mut header, ARCHIVE_MANIFEST, manifest.as_slice())?;
+
+        for save_path in paths {
+            let path = save_path.path.as_path();
             if path.file_name().is_none() {
                 return Err(io::Error::new(
                     io::ErrorKind::InvalidInput,
@@ -32,10 +48,19 @@ impl super::Archive for TarArchiver {
             }
 
             let metadata = fs::metadata(path)?;
-            let name = path.file_name().unwrap();
-
-            // SAFETY: We've already checked that the filename exists.
-            if metadata.is_dir() {
+            let name = &save_path.name;
+
+            if metadata.is_dir() && save_path.filter.is_some() {
+                // only the entries passing the include/exclude rules
+                for entry in save_path.walk() {
+                    let entry = entry?;
+                    let relative = entry
+                        .path()
+                        .strip_prefix(path)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    builder.append_path_with_name(entry.path(), Path::new(name).join(relative))?;
+                }
+            } else if metadata.is_dir() {
                 builder.append_dir_all(name, path)?;
             } else {
                 builder.append_path_with_name(path, name)?;
@@ -45,22 +70,27 @@ impl super::Archive for TarArchiver {
         Ok(())
     }
 
-    fn extract(
+    fn extract_filtered(
         &self,
         reader: impl io::Read + io::Seek + Send,
-        targets: Vec<impl AsRef<Path>>,
+        targets: &[SavePath],
+        filter: &dyn Fn(&str) -> bool,
     ) -> io::Result<()> {
         let mut archive = tar::Archive::new(reader);
         let entries = archive.entries()?;
-        let mut targets_iter = targets.into_iter();
+        let target_map = target_map(targets);
 
         // State to track the current top-level entry being processed.
         // (prefix_in_tar, target_path_on_disk)
-        let mut current_mapping: Option<(PathBuf, PathBuf)> = None;
+        let mut current_mapping: Option<(PathBuf, Option<PathBuf>)> = None;
 
         for entry in entries {
             let mut entry = entry?;
             let entry_path = entry.path()?.into_owned();
+            if entry_path == Path::new(ARCHIVE_MANIFEST) {
+                current_mapping = None;
+                continue;
+            }
 
             // Determine if the current entry is a child of the currently processing
             // top-level entry
@@ -71,55 +101,117 @@ impl super::Archive for TarArchiver {
                 false
             };
 
-            if is_child {
-                // It is a child file/dir of the current top-level directory
-                if let Some((ref prefix, ref target)) = current_mapping {
-                    // Calculate relative path: "dir/subdir/file" - "dir" = "subdir/file"
-                    let relative = entry_path.strip_prefix(prefix).map_err(|e| {
-                        io::Error::new(
-                            io::ErrorKind::InvalidData,
-                            format!("Path strip prefix error: {}", e),
-                        )
-                    })?;
-
-                    // Construct destination: "/target/path" + "subdir/file"
-                    let dest = target.join(relative);
-
-                    // Ensure parent directory exists (unpack handles the file/dir itself)
-                    if let Some(parent) = dest.parent() {
-                        fs::create_dir_all(parent)?;
-                    }
-
-                    // Extract directly to location
-                    entry.unpack(&dest)?;
-                }
-            } else {
-                // It is a new top-level entry (either a file or a new directory root)
-                let target = targets_iter.next().ok_or_else(|| {
+            if !is_child {
+                // It is a new top-level entry (either a file or a new directory root).
+                // entry_path here is the name stored in tar (e.g., "dir_name" or "file.txt").
+                // Its target is only required if something in it is extracted.
+                let target = entry_path
+                    .file_name()
+                    .and_then(|name| target_map.get(name))
+                    .cloned();
+                current_mapping = Some((entry_path.clone(), target));
+            }
+
+            if !filter(&to_entry_path(&entry_path)) {
+                continue;
+            }
+            let Some((prefix, Some(target))) = &current_mapping else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("No target for archive entry: {}", entry_path.display()),
+                ));
+            };
+
+            // Construct destination: "/target/path" + "subdir/file"
+            // If entry is the top-level "dir/", unpacking to "/tmp/target" creates
+            // directory "/tmp/target". If entry is "file.txt", unpacking to
+            // "/tmp/target.txt" creates file "/tmp/target.txt".
+            let dest = if is_child {
+                // Calculate relative path: "dir/subdir/file" - "dir" = "subdir/file"
+                let relative = entry_path.strip_prefix(prefix).map_err(|e| {
                     io::Error::new(
                         io::ErrorKind::InvalidData,
-                        "Archive contains more top-level entries than targets provided",
+                        format!("Path strip prefix error: {}", e),
                     )
                 })?;
-                let target = target.as_ref().to_path_buf();
+                target.join(relative)
+            } else {
+                target.clone()
+            };
 
-                // Update the current mapping
-                // entry_path here is the name stored in tar (e.g., "dir_name" or "file.txt")
-                current_mapping = Some((entry_path.clone(), target.clone()));
+            // Ensure parent directory exists (unpack handles the file/dir itself)
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
 
-                // Ensure parent directory of the target exists
-                if let Some(parent) = target.parent() {
-                    fs::create_dir_all(parent)?;
-                }
+            // Extract directly to location
+            entry.unpack(&dest)?;
+        }
 
-                // Extract the entry directly to the target path.
-                // If entry is "dir/", unpacking to "/tmp/target" creates directory
-                // "/tmp/target". If entry is "file.txt", unpacking to
-                // "/tmp/target.txt" creates file "/tmp/target.txt".
-                entry.unpack(&target)?;
+        Ok(())
+    }
+
+    fn list(&self, reader: impl io::Read + io::Seek + Send) -> io::Result<Vec<ArchiveEntry>> {
+        let mut archive = tar::Archive::new(reader);
+        let mut ret = vec![];
+        for entry in archive.entries()? {
+            let entry = entry?;
+            let path = entry.path()?;
+            if path == Path::new(ARCHIVE_MANIFEST) {
+                continue;
             }
+            let header = entry.header();
+            ret.push(ArchiveEntry {
+                path: to_entry_path(&path),
+                is_dir: header.entry_type().is_dir(),
+                size: if header.entry_type().is_file() {
+                    entry.size()
+                } else {
+                    0
+                },
+                mtime: header.mtime().unwrap_or_default(),
+            });
         }
+        Ok(ret)
+    }
 
+    fn read_file(
+        &self,
+        reader: impl io::Read + io::Seek + Send,
+        path: &str,
+    ) -> io::Result<Vec<u8>> {
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if entry.header().entry_type().is_file() && to_entry_path(&entry.path()?) == path {
+                let mut buf = Vec::with_capacity(entry.size() as usize);
+                entry.read_to_end(&mut buf)?;
+                return Ok(buf);
+            }
+        }
+        Err(not_found_in_archive(path))
+    }
+
+    fn visit_files(
+        &self,
+        reader: impl io::Read + io::Seek + Send,
+        visit: &mut dyn FnMut(ArchiveEntry, &mut dyn io::Read) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.into_owned();
+            if path == Path::new(ARCHIVE_MANIFEST) || !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let info = ArchiveEntry {
+                path: to_entry_path(&path),
+                is_dir: false,
+                size: entry.size(),
+                mtime: entry.header().mtime().unwrap_or_default(),
+            };
+            visit(info, &mut entry)?;
+        }
         Ok(())
     }
 }
@@ -1,19 +1,49 @@
-use std::{fs, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
 
-use chrono::Utc;
 use config_file2::Storable;
-use log::info;
+use log::{info, warn};
 use tauri::{AppHandle, Manager as _};
 
 use crate::{
-    archive::{ArchiveInfo, archive_impl, restore_impl},
-    db::{CONFIG, Config, device::DEVICE_UID},
+    archive::{
+        ArchiveEntry, ArchiveInfo, ArchiveMeta, ArchiveTrigger, ArchiveVerification,
+        ChecksumStatus, FileChange, archive_impl, check_local, detect_archive_format,
+        diff_archives_impl, diff_with_saves_impl, extract_to_impl, gc_chunks, is_snapshot,
+        list_entries_impl, read_checksum, read_file_impl, read_meta_or_legacy, remove_checksum,
+        remove_meta, rename_checksum, rename_meta, restore_impl, write_meta,
+    },
+    db::{
+        CONFIG, Config, Game,
+        backup::{self, BackupReason, ConfigBackup},
+        device::{DEVICE_UID, Device},
+        edit::edit_config,
+        merge::ConflictResolutions,
+        migrate,
+        revision::VersionVector,
+        secrets::{self, keep_local_secrets},
+        settings::Settings,
+    },
     error::{Error, Result},
     exec::{GAME_LOOP_HANDLES, launch_game_with_plugins},
     logging::LogLevel,
-    plugin::{SaveUploadDispatcher, Transaction},
-    sync::{MyOperation, UploadConfigStatus},
-    utils::list_dir_all,
+    plugin::{
+        AutoUploadGameConfig, PluginInstance, PluginMetadatas, RetentionPreview,
+        SaveUploadDispatcher, Transaction,
+    },
+    sync::{
+        MergeOutcome, MyOperation, UploadConfigStatus,
+        history::{self, ConflictResolution, SaveConflict, SyncRecord},
+        scheduler::{self, ConfigSyncEvent},
+        snapshot::ConfigSnapshot,
+        status::{
+            ArchiveSyncState, GameSyncStatus, ReconcileDirection, ReconcileResult, same_archive,
+        },
+    },
+    utils::{self, list_dir_all},
 };
 
 #[tauri::command]
@@ -24,14 +54,143 @@ pub fn get_config() -> Result<Config> {
 
 // called from frontend, do not use it in other places
 #[tauri::command]
-pub fn save_config(new_config: Config) -> Result<()> {
+pub fn save_config(mut new_config: Config, expected_revision: Option<VersionVector>) -> Result<()> {
     let mut lock = CONFIG.lock();
+    lock.check_revision(expected_revision.as_ref())?;
+    secrets::extract_secrets(&mut new_config.settings.storage)?;
+    // revisions are only counted by the backend, the frontend may hold stale
+    // ones
+    new_config.revision = std::mem::take(&mut lock.revision);
+    new_config.synced_revision = std::mem::take(&mut lock.synced_revision);
     *lock = new_config;
-    lock.last_updated = Utc::now();
+    lock.touch();
     lock.store()?;
     Ok(())
 }
 
+// The edits below are only made if the config is still at `expected_revision`,
+// give or take writes of backend-owned fields, see `edit_config`. They return
+// the new revision.
+
+#[tauri::command]
+pub fn add_game(
+    app: AppHandle,
+    game: Game,
+    expected_revision: Option<VersionVector>,
+) -> Result<VersionVector> {
+    edit_config(&app, expected_revision.as_ref(), |config| {
+        config.add_game(game);
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub fn update_game(
+    app: AppHandle,
+    game: Game,
+    expected_revision: Option<VersionVector>,
+) -> Result<VersionVector> {
+    edit_config(&app, expected_revision.as_ref(), |config| {
+        config.update_game(game)
+    })
+}
+
+#[tauri::command]
+pub fn delete_game(
+    app: AppHandle,
+    game_id: u32,
+    expected_revision: Option<VersionVector>,
+) -> Result<VersionVector> {
+    edit_config(&app, expected_revision.as_ref(), |config| {
+        config.delete_game(game_id).map(drop)
+    })
+}
+
+#[tauri::command]
+pub fn reorder_games(
+    app: AppHandle,
+    game_ids: Vec<u32>,
+    expected_revision: Option<VersionVector>,
+) -> Result<VersionVector> {
+    edit_config(&app, expected_revision.as_ref(), |config| {
+        config.reorder_games(&game_ids)
+    })
+}
+
+#[tauri::command]
+pub fn set_game_plugins(
+    app: AppHandle,
+    game_id: u32,
+    plugins: Vec<PluginInstance>,
+    expected_revision: Option<VersionVector>,
+) -> Result<VersionVector> {
+    edit_config(&app, expected_revision.as_ref(), |config| {
+        config.set_game_plugins(game_id, plugins)
+    })
+}
+
+#[tauri::command]
+pub fn update_settings(
+    app: AppHandle,
+    mut settings: Settings,
+    expected_revision: Option<VersionVector>,
+) -> Result<VersionVector> {
+    edit_config(&app, expected_revision.as_ref(), |config| {
+        // only once the edit is accepted, a refused one keeps the old secrets
+        secrets::extract_secrets(&mut settings.storage)?;
+        config.settings = settings;
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub fn update_plugin_metadatas(
+    app: AppHandle,
+    plugin_metadatas: PluginMetadatas,
+    expected_revision: Option<VersionVector>,
+) -> Result<VersionVector> {
+    edit_config(&app, expected_revision.as_ref(), |config| {
+        config.plugin_metadatas = plugin_metadatas;
+        Ok(())
+    })
+}
+
+/// Put back the local config that applying a synced remote config replaced.
+#[tauri::command]
+pub fn restore_previous_config(
+    app: AppHandle,
+    previous: Config,
+    expected_revision: Option<VersionVector>,
+) -> Result<VersionVector> {
+    edit_config(&app, expected_revision.as_ref(), |config| {
+        config.restore_previous(previous);
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub fn update_device(
+    app: AppHandle,
+    device: Device,
+    expected_revision: Option<VersionVector>,
+) -> Result<VersionVector> {
+    edit_config(&app, expected_revision.as_ref(), |config| {
+        config.update_device(device);
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub fn secrets_encrypted() -> bool {
+    secrets::is_encrypted()
+}
+
+/// Encrypt or decrypt the local secrets store with a key in the OS keyring.
+#[tauri::command]
+pub fn set_secrets_encrypted(encrypted: bool) -> Result<()> {
+    secrets::set_encrypted(encrypted)
+}
+
 #[tauri::command]
 pub fn device_id() -> &'static str {
     *DEVICE_UID
@@ -73,15 +232,26 @@ pub fn list_local_archive(app: AppHandle, game_id: u32) -> Result<Vec<ArchiveInf
     if !game_backup_dir.exists() {
         return Ok(vec![]);
     }
-    Ok(list_dir_all(game_backup_dir)?)
+    let mut archives = list_dir_all(&game_backup_dir)?;
+    for archive in &mut archives {
+        archive.format = detect_archive_format(&game_backup_dir.join(&archive.name))
+            .ok()
+            .flatten();
+    }
+    Ok(archives)
 }
 
 #[tauri::command]
 pub fn delete_local_archive(app: AppHandle, game_id: u32, archive_filename: String) -> Result<()> {
     let game_backup_dir = game_backup_dir(&app, game_id)?;
-    let archive_path = game_backup_dir.join(archive_filename);
+    let archive_path = game_backup_dir.join(&archive_filename);
     fs::remove_file(&archive_path)?;
+    remove_checksum(&archive_path)?;
+    remove_meta(&archive_path)?;
     info!("delete local archive: {}", archive_path.display());
+    if is_snapshot(&archive_filename) {
+        gc_chunks(&game_backup_dir)?;
+    }
     Ok(())
 }
 
@@ -104,6 +274,8 @@ pub fn rename_local_archive(
     let archive_path = game_backup_dir.join(archive_filename);
     let new_archive_path = game_backup_dir.join(new_archive_filename);
     fs::rename(&archive_path, &new_archive_path)?;
+    rename_checksum(&archive_path, &new_archive_path)?;
+    rename_meta(&archive_path, &new_archive_path)?;
     info!(
         "rename local archive: {} -> {}",
         archive_path.display(),
@@ -118,28 +290,291 @@ pub fn archive(app: AppHandle, game_id: u32) -> Result<String> {
 
     let lock = CONFIG.lock();
     let archive_conf = lock.settings.archive.clone();
-    let paths = lock.get_game_by_id(game_id)?.save_paths.clone();
-    let device_name = lock
-        .get_device()
-        .map(|d| d.name.clone())
-        .unwrap_or(format!("Unknown{}", lock.devices.len()));
+    let game = lock.get_game_by_id(game_id)?;
+    let (paths, use_time) = (game.save_paths.clone(), game.use_time);
+    let device_name = lock.device_name();
     drop(lock);
 
     // logged inner
-    archive_impl(&device_name, &archive_conf, game_backup_dir, paths)
+    archive_impl(
+        &device_name,
+        &archive_conf,
+        game_backup_dir,
+        paths,
+        ArchiveTrigger::Manual,
+        use_time,
+    )
+}
+
+/// Update the metadata of a local archive. Archives of older versions get
+/// their metadata from their filename first.
+fn update_local_meta(
+    app: &AppHandle,
+    game_id: u32,
+    archive_filename: &str,
+    update: impl FnOnce(&mut ArchiveMeta),
+) -> Result<()> {
+    let archive_path = game_backup_dir(app, game_id)?.join(archive_filename);
+    let mut meta = read_meta_or_legacy(&archive_path)?.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no metadata for archive {archive_filename}"),
+        )
+    })?;
+    update(&mut meta);
+    write_meta(&archive_path, &meta)?;
+    Ok(())
+}
+
+/// Set the note of a local archive, or clear it if `note` is empty.
+#[tauri::command]
+pub fn set_local_archive_note(
+    app: AppHandle,
+    game_id: u32,
+    archive_filename: String,
+    note: String,
+) -> Result<()> {
+    update_local_meta(&app, game_id, &archive_filename, |meta| {
+        meta.note = Some(note).filter(|n| !n.is_empty())
+    })
+}
+
+/// Pin a local archive so that it is never pruned, or unpin it.
+#[tauri::command]
+pub fn set_local_archive_pinned(
+    app: AppHandle,
+    game_id: u32,
+    archive_filename: String,
+    pinned: bool,
+) -> Result<()> {
+    update_local_meta(&app, game_id, &archive_filename, |meta| {
+        meta.pinned = pinned
+    })
+}
+
+/// Restore an archive over the save paths of a game. Returns the filename of
+/// the archive taken of the current saves beforehand, if any.
+#[tauri::command(async)]
+pub async fn extract(
+    app: AppHandle,
+    game_id: u32,
+    archive_filename: String,
+) -> Result<Option<String>> {
+    extract_files(app, game_id, archive_filename, None).await
+}
+
+/// Restore some entries of an archive over the save paths of a game, or all
+/// of them if `files` is `None`. See [`extract`].
+#[tauri::command(async)]
+pub async fn extract_files(
+    app: AppHandle,
+    game_id: u32,
+    archive_filename: String,
+    files: Option<Vec<String>>,
+) -> Result<Option<String>> {
+    let game_backup_dir = game_backup_dir(&app, game_id)?;
+
+    let (archive_conf, paths, use_time, device_name) = {
+        let lock = CONFIG.lock();
+        let game = lock.get_game_by_id(game_id)?;
+        (
+            lock.settings.archive.clone(),
+            game.save_paths.clone(),
+            game.use_time,
+            lock.device_name(),
+        )
+    };
+
+    let archive_path = game_backup_dir.join(&archive_filename);
+    let restores_all = files.is_none();
+    // logged inner
+    let pre_restore = restore_impl(
+        &device_name,
+        &archive_conf,
+        game_backup_dir,
+        archive_filename,
+        paths,
+        files,
+        use_time,
+    )?;
+    if restores_all {
+        record_restore(&app, game_id, &device_name, &archive_path).await;
+    }
+    Ok(pre_restore)
 }
 
+/// Record in the save history on remote that this device restored the local
+/// archive at `archive_path`, if storage is configured.
+///
+/// Best-effort: the saves are restored already, so errors are only logged.
+async fn record_restore(app: &AppHandle, game_id: u32, device_name: &str, archive_path: &Path) {
+    if CONFIG.lock().settings.storage.is_not_set() {
+        return;
+    }
+    let res = match (
+        build_operator_with_varmap(app),
+        SyncRecord::of_local(device_name, archive_path),
+    ) {
+        (Ok(op), Ok(record)) => history::record_restore(&*op, game_id, record).await,
+        (Err(e), _) | (_, Err(e)) => Err(e),
+    };
+    if let Err(e) = res {
+        warn!(
+            "record restore of {} in save history failed: {e}",
+            archive_path.display()
+        );
+    }
+}
+
+/// The conflict restoring a local archive over the saves of a game would
+/// cause, if any. `None` if storage is not configured.
+#[tauri::command(async)]
+pub async fn check_restore_conflict(
+    app: AppHandle,
+    game_id: u32,
+    archive_filename: String,
+) -> Result<Option<SaveConflict>> {
+    let game_backup_dir = game_backup_dir(&app, game_id)?;
+    let (paths, device_name) = {
+        let lock = CONFIG.lock();
+        if lock.settings.storage.is_not_set() {
+            return Ok(None);
+        }
+        let game = lock.get_game_by_id(game_id)?;
+        (game.save_paths.clone(), lock.device_name())
+    };
+    let Some(meta) = read_meta_or_legacy(&game_backup_dir.join(&archive_filename))? else {
+        return Ok(None);
+    };
+    let archive = SyncRecord::of_creator(&archive_filename, &meta);
+    let current = SyncRecord::of_saves(&device_name, &game_backup_dir, &paths)?;
+    let op = build_operator_with_varmap(&app)?;
+    history::check_restore(&*op, game_id, &current, &archive).await
+}
+
+/// Resolve a save conflict returned by [`upload_archive`] or
+/// [`check_restore_conflict`].
+#[tauri::command(async)]
+pub async fn resolve_save_conflict(
+    app: AppHandle,
+    conflict: SaveConflict,
+    resolution: ConflictResolution,
+) -> Result<()> {
+    let SaveConflict {
+        game_id,
+        local,
+        remote,
+    } = conflict;
+    info!("resolving save conflict of game {game_id} with {resolution:?}");
+    match resolution {
+        ConflictResolution::KeepRemote => {
+            if !game_backup_dir(&app, game_id)?
+                .join(&remote.archive)
+                .exists()
+            {
+                pull_archive(app.clone(), game_id, remote.archive.clone()).await?;
+            }
+            extract(app, game_id, remote.archive).await?;
+        }
+        ConflictResolution::KeepLocal => {
+            upload_archive(app, game_id, local.archive, Some(true)).await?;
+        }
+        ConflictResolution::KeepBoth => {
+            update_local_meta(&app, game_id, &local.archive, |meta| meta.pinned = true)?;
+            upload_archive(app.clone(), game_id, local.archive, Some(true)).await?;
+            build_operator_with_varmap(&app)?
+                .set_archive_pinned(game_id, &remote.archive, true)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Extract some entries of an archive into `dest_dir` instead of the save
+/// paths.
 #[tauri::command]
-pub fn extract(app: AppHandle, game_id: u32, archive_filename: String) -> Result<()> {
+pub fn extract_files_to(
+    app: AppHandle,
+    game_id: u32,
+    archive_filename: String,
+    files: Vec<String>,
+    dest_dir: String,
+) -> Result<()> {
     let game_backup_dir = game_backup_dir(&app, game_id)?;
+    let archive_conf = CONFIG.lock().settings.archive.clone();
+    extract_to_impl(
+        &archive_conf,
+        &game_backup_dir,
+        &archive_filename,
+        &files,
+        &PathBuf::from(dest_dir),
+    )
+}
 
+/// Compare two local archives of a game.
+#[tauri::command]
+pub fn diff_archives(
+    app: AppHandle,
+    game_id: u32,
+    old_archive_filename: String,
+    new_archive_filename: String,
+    text_diff: bool,
+) -> Result<Vec<FileChange>> {
+    let game_backup_dir = game_backup_dir(&app, game_id)?;
+    let archive_conf = CONFIG.lock().settings.archive.clone();
+    diff_archives_impl(
+        &archive_conf,
+        &game_backup_dir,
+        &old_archive_filename,
+        &new_archive_filename,
+        text_diff,
+    )
+}
+
+/// Compare the current saves of a game with a local archive, i.e. what
+/// restoring it would change.
+#[tauri::command]
+pub fn diff_archive_with_saves(
+    app: AppHandle,
+    game_id: u32,
+    archive_filename: String,
+    text_diff: bool,
+) -> Result<Vec<FileChange>> {
+    let game_backup_dir = game_backup_dir(&app, game_id)?;
     let lock = CONFIG.lock();
     let archive_conf = lock.settings.archive.clone();
     let paths = lock.get_game_by_id(game_id)?.save_paths.clone();
     drop(lock);
+    diff_with_saves_impl(
+        &archive_conf,
+        &game_backup_dir,
+        &archive_filename,
+        paths,
+        text_diff,
+    )
+}
 
-    // logged inner
-    restore_impl(&archive_conf, game_backup_dir, archive_filename, paths)
+#[tauri::command]
+pub fn list_archive_entries(
+    app: AppHandle,
+    game_id: u32,
+    archive_filename: String,
+) -> Result<Vec<ArchiveEntry>> {
+    let game_backup_dir = game_backup_dir(&app, game_id)?;
+    let archive_conf = CONFIG.lock().settings.archive.clone();
+    list_entries_impl(&archive_conf, &game_backup_dir, &archive_filename)
+}
+
+#[tauri::command]
+pub fn read_archive_file(
+    app: AppHandle,
+    game_id: u32,
+    archive_filename: String,
+    path: String,
+) -> Result<Vec<u8>> {
+    let game_backup_dir = game_backup_dir(&app, game_id)?;
+    let archive_conf = CONFIG.lock().settings.archive.clone();
+    read_file_impl(&archive_conf, &game_backup_dir, &archive_filename, &path)
 }
 
 // region sync
@@ -164,25 +599,77 @@ pub async fn list_archive(app: AppHandle, game_id: u32) -> Result<Vec<ArchiveInf
         .await
 }
 
+/// Upload a local archive. Returns the save conflict instead if the saves on
+/// remote diverged, unless `force`.
 #[tauri::command(async)]
-pub async fn upload_archive(app: AppHandle, game_id: u32, archive_filename: String) -> Result<()> {
+pub async fn upload_archive(
+    app: AppHandle,
+    game_id: u32,
+    archive_filename: String,
+    force: Option<bool>,
+) -> Result<Option<SaveConflict>> {
     info!(
         "uploading archive: game_id={}, archive_filename={}",
         game_id, archive_filename
     );
 
+    let op = build_operator_with_varmap(&app)?;
+    upload_recorded(
+        &app,
+        &*op,
+        game_id,
+        &archive_filename,
+        force.unwrap_or_default(),
+    )
+    .await
+}
+
+/// Upload a local archive and record it in the save history. Unless `force`
+/// is set, nothing is uploaded if it conflicts with the remote saves.
+async fn upload_recorded(
+    app: &AppHandle,
+    op: &(dyn MyOperation + Send + Sync),
+    game_id: u32,
+    archive_filename: &str,
+    force: bool,
+) -> Result<Option<SaveConflict>> {
+    let record = SyncRecord::of_local(
+        &CONFIG.lock().device_name(),
+        &game_backup_dir(app, game_id)?.join(archive_filename),
+    )?;
+    if !force && let Some(conflict) = history::check_upload(op, game_id, &record).await? {
+        warn!("save conflict on upload: {conflict}");
+        return Ok(Some(conflict));
+    }
+
+    upload_with_hooks(app, op, game_id, archive_filename).await?;
+
+    if let Err(e) = history::record_upload(op, game_id, record).await {
+        warn!("record upload of {archive_filename} in save history failed: {e}");
+    }
+    Ok(None)
+}
+
+/// Upload a local archive between the save upload hooks of the plugins of
+/// the game.
+async fn upload_with_hooks(
+    app: &AppHandle,
+    op: &(dyn MyOperation + Send + Sync),
+    game_id: u32,
+    archive_filename: &str,
+) -> Result<()> {
     let tx = Transaction::new();
-    let save_dispatcher = SaveUploadDispatcher::new(&app, game_id, tx.clone())?;
+    let save_dispatcher = SaveUploadDispatcher::new(app, game_id, tx.clone())?;
 
-    if let Err(e) = save_dispatcher.dispatch_before(&archive_filename).await {
+    if let Err(e) = save_dispatcher.dispatch_before(archive_filename).await {
         tx.rollback();
         return Err(e);
     }
 
-    if let Err(e) = build_operator_with_varmap(&app)?
+    if let Err(e) = op
         .upload_archive(
             game_id,
-            &archive_filename,
+            archive_filename,
             &app.path().app_local_data_dir()?.join("backup"),
         )
         .await
@@ -191,8 +678,7 @@ pub async fn upload_archive(app: AppHandle, game_id: u32, archive_filename: Stri
         return Err(e);
     }
 
-    save_dispatcher.dispatch_after(&archive_filename).await;
-
+    save_dispatcher.dispatch_after(archive_filename).await;
     Ok(())
 }
 
@@ -221,6 +707,185 @@ pub async fn pull_archive(app: AppHandle, game_id: u32, archive_filename: String
         .await
 }
 
+/// The ids of the game `game_id`, or of every game with save paths if it is
+/// `None`.
+fn synced_game_ids(game_id: Option<u32>) -> Result<Vec<u32>> {
+    let lock = CONFIG.lock();
+    match game_id {
+        Some(id) => Ok(vec![lock.get_game_by_id(id)?.id]),
+        None => Ok(lock
+            .games
+            .iter()
+            .filter(|g| !g.save_paths.is_empty())
+            .map(|g| g.id)
+            .collect()),
+    }
+}
+
+async fn game_sync_status(
+    app: &AppHandle,
+    op: &(dyn MyOperation + Send + Sync),
+    game_id: u32,
+) -> Result<GameSyncStatus> {
+    let game_backup_dir = game_backup_dir(app, game_id)?;
+    let local = if game_backup_dir.exists() {
+        list_dir_all(&game_backup_dir)?
+    } else {
+        vec![]
+    };
+    let remote = op.list_archive(game_id).await?;
+
+    let mut checksums = HashMap::new();
+    for archive in local
+        .iter()
+        .filter(|l| remote.iter().any(|r| r.name == l.name))
+    {
+        let local_checksum = read_checksum(&game_backup_dir.join(&archive.name))?;
+        let remote_checksum = op.archive_checksum(game_id, &archive.name).await?;
+        checksums.insert(archive.name.clone(), (local_checksum, remote_checksum));
+    }
+    Ok(GameSyncStatus::new(game_id, local, remote, |l, r| {
+        let (local_checksum, remote_checksum) = &checksums[&l.name];
+        same_archive(l, local_checksum.as_deref(), r, remote_checksum.as_deref())
+    }))
+}
+
+/// Compare the local and remote archives of a game, or of every game with
+/// save paths if `game_id` is `None`.
+#[tauri::command(async)]
+pub async fn sync_status(app: AppHandle, game_id: Option<u32>) -> Result<Vec<GameSyncStatus>> {
+    let op = build_operator_with_varmap(&app)?;
+    let mut ret = vec![];
+    for game_id in synced_game_ids(game_id)? {
+        ret.push(game_sync_status(&app, &*op, game_id).await?);
+    }
+    Ok(ret)
+}
+
+/// Upload the local-only archives and/or pull the remote-only ones of a game,
+/// or of every game with save paths if `game_id` is `None`. Snapshots taken
+/// while a game was running are not uploaded, and archives that differ on
+/// both sides are left alone.
+///
+/// Uploads that would cause a save conflict are skipped. A failed copy does
+/// not stop the others; the outcome of each is returned.
+#[tauri::command(async)]
+pub async fn reconcile(
+    app: AppHandle,
+    game_id: Option<u32>,
+    direction: ReconcileDirection,
+) -> Result<Vec<ReconcileResult>> {
+    let op = build_operator_with_varmap(&app)?;
+    let backup_dir = app.path().app_local_data_dir()?.join("backup");
+    let mut ret = vec![];
+    for game_id in synced_game_ids(game_id)? {
+        let status = game_sync_status(&app, &*op, game_id).await?;
+        if direction.uploads() {
+            for archive in status
+                .archives
+                .iter()
+                .filter(|a| a.state == ArchiveSyncState::LocalOnly && !a.in_session)
+            {
+                let res = match upload_recorded(&app, &*op, game_id, &archive.name, false).await {
+                    Ok(Some(conflict)) => Err(Error::SaveConflict(Box::new(conflict))),
+                    res => res.map(|_| ()),
+                };
+                if let Err(e) = &res {
+                    warn!("reconcile: upload of {} failed: {e}", archive.name);
+                }
+                ret.push(ReconcileResult {
+                    game_id,
+                    name: archive.name.clone(),
+                    uploaded: true,
+                    error: res.err().map(|e| e.to_string()),
+                });
+            }
+        }
+        if direction.pulls() {
+            for name in status.names(ArchiveSyncState::RemoteOnly) {
+                let res = op.pull_archive(game_id, name, &backup_dir).await;
+                if let Err(e) = &res {
+                    warn!("reconcile: pull of {name} failed: {e}");
+                }
+                ret.push(ReconcileResult {
+                    game_id,
+                    name: name.to_string(),
+                    uploaded: false,
+                    error: res.err().map(|e| e.to_string()),
+                });
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// Check the local and remote archives of a game against their checksums.
+/// Remote archives are only checked if storage is configured.
+#[tauri::command(async)]
+pub async fn verify_archives(app: AppHandle, game_id: u32) -> Result<Vec<ArchiveVerification>> {
+    let game_backup_dir = game_backup_dir(&app, game_id)?;
+    let mut ret = vec![];
+    if game_backup_dir.exists() {
+        for archive in list_dir_all(&game_backup_dir)? {
+            ret.push(ArchiveVerification {
+                status: check_local(&game_backup_dir.join(&archive.name))?,
+                name: archive.name,
+                remote: false,
+            });
+        }
+    }
+
+    if !CONFIG.lock().settings.storage.is_not_set() {
+        let op = build_operator_with_varmap(&app)?;
+        for archive in op.list_archive(game_id).await? {
+            ret.push(ArchiveVerification {
+                status: op.check_archive(game_id, &archive.name).await?,
+                name: archive.name,
+                remote: true,
+            });
+        }
+    }
+
+    let mismatched = ret
+        .iter()
+        .filter(|v| v.status == ChecksumStatus::Mismatch)
+        .count();
+    if mismatched > 0 {
+        warn!("{mismatched} archive(s) of game {game_id} do not match their checksum");
+    }
+    Ok(ret)
+}
+
+/// Set the note of a remote archive, or clear it if `note` is empty.
+#[tauri::command(async)]
+pub async fn set_remote_archive_note(
+    app: AppHandle,
+    game_id: u32,
+    archive_filename: String,
+    note: String,
+) -> Result<()> {
+    build_operator_with_varmap(&app)?
+        .set_archive_note(
+            game_id,
+            &archive_filename,
+            Some(note).filter(|n| !n.is_empty()),
+        )
+        .await
+}
+
+/// Pin a remote archive so that it is never pruned, or unpin it.
+#[tauri::command(async)]
+pub async fn set_remote_archive_pinned(
+    app: AppHandle,
+    game_id: u32,
+    archive_filename: String,
+    pinned: bool,
+) -> Result<()> {
+    build_operator_with_varmap(&app)?
+        .set_archive_pinned(game_id, &archive_filename, pinned)
+        .await
+}
+
 #[tauri::command(async)]
 pub async fn rename_remote_archive(
     app: AppHandle,
@@ -233,6 +898,46 @@ pub async fn rename_remote_archive(
         .await
 }
 
+/// Preview which archives the auto upload retention policy would delete for
+/// a game, without deleting anything. Uses `config` if given, otherwise the
+/// config of the game, or the plugin defaults if the game does not use the
+/// plugin. Remote archives are only listed if storage is configured.
+#[tauri::command(async)]
+pub async fn preview_retention(
+    app: AppHandle,
+    game_id: u32,
+    config: Option<AutoUploadGameConfig>,
+) -> Result<RetentionPreview> {
+    let (config, storage_set) = {
+        let lock = CONFIG.lock();
+        let config = match config {
+            Some(config) => config,
+            None => lock
+                .get_game_by_id(game_id)?
+                .plugins
+                .iter()
+                .find_map(|p| match p {
+                    PluginInstance::AutoUpload { config } => Some(*config),
+                    _ => None,
+                })
+                .unwrap_or(lock.plugin_metadatas.auto_upload.config_defaults),
+        };
+        (config, !lock.settings.storage.is_not_set())
+    };
+    let op = if storage_set {
+        Some(build_operator_with_varmap(&app)?)
+    } else {
+        None
+    };
+    crate::plugin::preview_retention(
+        &config,
+        &game_backup_dir(&app, game_id)?,
+        op.as_deref(),
+        game_id,
+    )
+    .await
+}
+
 /// Operator needs to be cleaned every time the config of storage backend is
 /// changed
 #[tauri::command]
@@ -246,14 +951,24 @@ pub async fn upload_config(app: AppHandle, safe: bool) -> Result<UploadConfigSta
     let op = build_operator_with_varmap(&app)?;
     let res = op.upload_config(&app, safe).await?;
     #[cfg(feature = "config-daily-backup")]
-    if matches!(res, UploadConfigStatus::Uploaded)
-        && let Err(e) = op.replicate_config().await
-    {
-        log::error!("Failed to replicate config: {e}");
+    if matches!(res, UploadConfigStatus::Uploaded) {
+        if let Err(e) = op.replicate_config().await {
+            log::error!("Failed to replicate config: {e}");
+        }
+        let keep = CONFIG.lock().settings.config_snapshot_retention;
+        if let Err(e) = op.prune_config_snapshots(keep).await {
+            log::error!("Failed to prune config snapshots: {e}");
+        }
     }
     Ok(res)
 }
 
+/// The last background config sync, if any happened yet.
+#[tauri::command]
+pub fn last_config_sync() -> Option<ConfigSyncEvent> {
+    scheduler::last_event()
+}
+
 // currently not used. please use apply_remote_config instead.
 #[tauri::command(async)]
 pub async fn get_remote_config(app: AppHandle) -> Result<Option<Config>> {
@@ -264,6 +979,23 @@ pub async fn get_remote_config(app: AppHandle) -> Result<Option<Config>> {
     build_operator_with_varmap(&app)?.get_remote_config().await
 }
 
+/// Three-way merge the remote config into the local one. Conflicting fields
+/// are taken from `resolutions` (keyed by conflict path); if any conflict is
+/// left, nothing is applied and the conflicts are returned.
+#[tauri::command(async)]
+pub async fn merge_remote_config(
+    app: AppHandle,
+    resolutions: ConflictResolutions,
+) -> Result<MergeOutcome> {
+    info!(
+        "merge_remote_config triggered, {} resolution(s)",
+        resolutions.len()
+    );
+    build_operator_with_varmap(&app)?
+        .merge_remote_config(&app, &resolutions)
+        .await
+}
+
 #[tauri::command(async)]
 pub async fn apply_remote_config(app: AppHandle, safe: bool) -> Result<(Option<Config>, bool)> {
     build_operator_with_varmap(&app)?
@@ -271,6 +1003,92 @@ pub async fn apply_remote_config(app: AppHandle, safe: bool) -> Result<(Option<C
         .await
 }
 
+/// Daily snapshots of the remote config, newest first.
+#[tauri::command(async)]
+pub async fn list_config_snapshots(app: AppHandle) -> Result<Vec<ConfigSnapshot>> {
+    build_operator_with_varmap(&app)?
+        .list_config_snapshots()
+        .await
+}
+
+#[tauri::command(async)]
+pub async fn get_config_snapshot(app: AppHandle, name: String) -> Result<Config> {
+    build_operator_with_varmap(&app)?
+        .get_config_snapshot(&name)
+        .await
+}
+
+/// Unified diff from the local config to the remote snapshot called `name`.
+#[tauri::command(async)]
+pub async fn diff_config_snapshot(app: AppHandle, name: String) -> Result<String> {
+    let snapshot = build_operator_with_varmap(&app)?
+        .get_config_snapshot(&name)
+        .await?;
+    Ok(utils::diff(&CONFIG.lock(), &snapshot))
+}
+
+/// Replace the local config with the remote snapshot called `name`, after
+/// backing the local config up. The restored config is a new local change,
+/// uploaded with the next sync. Returns the previous config.
+#[tauri::command(async)]
+pub async fn restore_config_snapshot(app: AppHandle, name: String) -> Result<Config> {
+    let snapshot = build_operator_with_varmap(&app)?
+        .get_config_snapshot(&name)
+        .await?;
+    restore_config(&app, snapshot, &format!("config snapshot {name}"))
+}
+
+/// Replace the local config with `restored`, see [`restore_config_snapshot`].
+fn restore_config(app: &AppHandle, mut restored: Config, from: &str) -> Result<Config> {
+    let mut lock = CONFIG.lock();
+    let backup = backup::backup_config(&lock, BackupReason::Restore)?;
+    info!(
+        "restoring {from}, local config backed up to {}",
+        backup.display()
+    );
+    keep_local_secrets(&mut restored.settings.storage, &lock.settings.storage);
+    // newer than both, so that sync does not take it for a stale config
+    restored.revision = lock.revision.join(&restored.revision);
+    restored.synced_revision = lock.synced_revision.clone();
+    restored.last_sync = lock.last_sync;
+    let previous = std::mem::replace(&mut *lock, restored);
+    lock.save_and_emit(app)?;
+    Ok(previous)
+}
+
+/// Delete the remote config snapshots beyond
+/// [`Settings::config_snapshot_retention`].
+/// Returns the names of the deleted ones.
+#[tauri::command(async)]
+pub async fn prune_config_snapshots(app: AppHandle) -> Result<Vec<String>> {
+    let keep = CONFIG.lock().settings.config_snapshot_retention;
+    build_operator_with_varmap(&app)?
+        .prune_config_snapshots(keep)
+        .await
+}
+
+#[tauri::command]
+pub fn list_config_backups() -> Result<Vec<ConfigBackup>> {
+    backup::list_backups()
+}
+
+/// Unified diff from the local config to the local backup called `name`.
+/// Backups that do not load are compared as text.
+#[tauri::command]
+pub fn diff_config_backup(name: String) -> Result<String> {
+    let backup = backup::read_backup(&name)?;
+    let current = toml::to_string(&*CONFIG.lock())?;
+    Ok(utils::diff_text(&current, &backup))
+}
+
+/// Replace the local config with the local backup called `name`, like
+/// [`restore_config_snapshot`]. Returns the previous config.
+#[tauri::command]
+pub fn restore_config_backup(app: AppHandle, name: String) -> Result<Config> {
+    let backup = migrate(toml::from_str(&backup::read_backup(&name)?)?);
+    restore_config(&app, backup, &format!("config backup {name}"))
+}
+
 // region exec
 
 #[tauri::command(async)]
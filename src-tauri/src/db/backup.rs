@@ -0,0 +1,307 @@
+//! Local backups of the config in [`BACKUP_DIR`], named
+//! `config_YYYYMMDDhhmmssSSS_<reason>.toml` after the local time they were
+//! taken. Only the [`MAX_BACKUPS`] newest ones are kept.
+
+use std::{
+    cmp::Reverse,
+    fs::{self, File},
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    sync::LazyLock as Lazy,
+};
+
+use chrono::{Duration, Local, NaiveDateTime};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::{CONFIG_DIR, Config};
+use crate::error::{Error, Result};
+
+pub static BACKUP_DIR: Lazy<PathBuf> = Lazy::new(|| CONFIG_DIR.join("backups"));
+
+/// Number of backups kept, older ones are deleted when a new one is taken.
+pub const MAX_BACKUPS: usize = 20;
+/// A [`BackupReason::Daily`] backup is taken on save if there is no backup
+/// newer than this.
+const BACKUP_INTERVAL: Duration = Duration::days(1);
+
+const PREFIX: &str = "config_";
+const SUFFIX: &str = ".toml";
+const TIME_FORMAT: &str = "%Y%m%d%H%M%S%3f";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub enum BackupReason {
+    /// The config on disk before the first save of the day.
+    Daily,
+    /// A config that failed to load.
+    Broken,
+    /// The local config before a snapshot or a backup was restored.
+    Restore,
+    /// The local config before a remote config was applied.
+    Remote,
+}
+
+impl BackupReason {
+    const ALL: [Self; 4] = [Self::Daily, Self::Broken, Self::Restore, Self::Remote];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Daily => "daily",
+            Self::Broken => "broken",
+            Self::Restore => "restore",
+            Self::Remote => "remote",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigBackup {
+    pub name: String,
+    /// Local time the backup was taken.
+    pub created_at: NaiveDateTime,
+    pub reason: BackupReason,
+    pub size: u64,
+}
+
+/// Name of a backup taken at `time`.
+#[inline]
+pub fn backup_name(time: NaiveDateTime, reason: BackupReason) -> String {
+    format!(
+        "{PREFIX}{}_{}{SUFFIX}",
+        time.format(TIME_FORMAT),
+        reason.as_str()
+    )
+}
+
+/// When and why the backup called `name` was taken, `None` if it is not a
+/// backup.
+pub fn parse_backup_name(name: &str) -> Option<(NaiveDateTime, BackupReason)> {
+    let (time, reason) = name
+        .strip_prefix(PREFIX)?
+        .strip_suffix(SUFFIX)?
+        .split_once('_')?;
+    let time = NaiveDateTime::parse_from_str(time, TIME_FORMAT).ok()?;
+    let reason = BackupReason::ALL
+        .into_iter()
+        .find(|r| r.as_str() == reason)?;
+    (backup_name(time, reason) == name).then_some((time, reason))
+}
+
+/// Write `content` to a temporary file next to `path`, flush it to disk and
+/// rename it over `path`, so that a crash leaves either the old or the new
+/// file, never half of one.
+#[inline]
+pub fn write_atomic(path: &Path, content: &[u8]) -> io::Result<()> {
+    write_atomic_with(path, content, false)
+}
+
+/// Like [`write_atomic`], but the new file is only readable by the current
+/// user, whatever the permissions of the file it replaces.
+#[inline]
+pub fn write_atomic_private(path: &Path, content: &[u8]) -> io::Result<()> {
+    write_atomic_with(path, content, true)
+}
+
+fn write_atomic_with(path: &Path, content: &[u8], private: bool) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    let mut file = File::create(&tmp)?;
+    #[cfg(unix)]
+    if private {
+        use std::os::unix::fs::PermissionsExt;
+        // also covers a temporary file left over by a crash
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    #[cfg(not(unix))]
+    let _ = private;
+    file.write_all(content)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&tmp, path)?;
+
+    // persist the rename itself
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Backups in `dir`, newest first.
+fn list_in(dir: &Path) -> io::Result<Vec<ConfigBackup>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    let mut backups = vec![];
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let Some((created_at, reason)) = parse_backup_name(&name) else {
+            continue;
+        };
+        backups.push(ConfigBackup {
+            name,
+            created_at,
+            reason,
+            size: entry.metadata()?.len(),
+        });
+    }
+    backups.sort_by_key(|b| Reverse(b.created_at));
+    Ok(backups)
+}
+
+/// Backups beyond the `keep` newest ones.
+fn to_rotate(backups: &[ConfigBackup], keep: usize) -> &[ConfigBackup] {
+    &backups[keep.min(backups.len())..]
+}
+
+/// Path of a new backup in `dir`, after deleting the backups that would be
+/// beyond [`MAX_BACKUPS`] with it.
+fn new_backup_in(dir: &Path, reason: BackupReason) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    for old in to_rotate(&list_in(dir)?, MAX_BACKUPS - 1) {
+        if let Err(e) = fs::remove_file(dir.join(&old.name)) {
+            warn!("failed to delete old config backup {}: {e}", old.name);
+        }
+    }
+    Ok(dir.join(backup_name(Local::now().naive_local(), reason)))
+}
+
+/// Backups of the local config, newest first.
+#[inline]
+pub fn list_backups() -> Result<Vec<ConfigBackup>> {
+    Ok(list_in(&BACKUP_DIR)?)
+}
+
+/// Content of the backup called `name`.
+pub fn read_backup(name: &str) -> Result<String> {
+    if parse_backup_name(name).is_none() {
+        return Err(Error::InvalidPath);
+    }
+    Ok(fs::read_to_string(BACKUP_DIR.join(name))?)
+}
+
+/// Copy `config` to a new backup and return its path.
+pub fn backup_config(config: &Config, reason: BackupReason) -> Result<PathBuf> {
+    let path = new_backup_in(&BACKUP_DIR, reason)?;
+    write_atomic_private(&path, toml::to_string(config)?.as_bytes())?;
+    Ok(path)
+}
+
+/// Move the file at `path`, which failed to load, to a new backup and return
+/// its path.
+pub fn backup_broken(path: &Path) -> Result<PathBuf> {
+    let backup = new_backup_in(&BACKUP_DIR, BackupReason::Broken)?;
+    write_atomic_private(&backup, &fs::read(path)?)?;
+    fs::remove_file(path)?;
+    Ok(backup)
+}
+
+/// Copy the file at `path` to a new [`BackupReason::Daily`] backup, unless
+/// there is a backup in `dir` newer than [`BACKUP_INTERVAL`].
+pub(super) fn backup_if_due(dir: &Path, path: &Path) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let now = Local::now().naive_local();
+    if list_in(dir)?
+        .first()
+        .is_some_and(|b| now - b.created_at < BACKUP_INTERVAL)
+    {
+        return Ok(());
+    }
+    let backup = new_backup_in(dir, BackupReason::Daily)?;
+    write_atomic_private(&backup, &fs::read(path)?)?;
+    info!("config backed up to {}", backup.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn time(day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 3, day)
+            .unwrap()
+            .and_hms_milli_opt(8, 5, 30, 42)
+            .unwrap()
+    }
+
+    #[test]
+    fn parses_backup_names() {
+        let name = backup_name(time(9), BackupReason::Remote);
+        assert_eq!(name, "config_20240309080530042_remote.toml");
+        assert_eq!(
+            parse_backup_name(&name),
+            Some((time(9), BackupReason::Remote))
+        );
+        for name in [
+            "config.toml",
+            "config.toml.bak",
+            "config_20240309.toml",
+            "config_20240309080530042.toml",
+            "config_20240309080530042_other.toml",
+            "config_20241309080530042_daily.toml",
+            "../config_20240309080530042_daily.toml",
+        ] {
+            assert_eq!(parse_backup_name(name), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn keeps_the_newest_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        for day in [2, 4, 1, 3] {
+            fs::write(
+                dir.path().join(backup_name(time(day), BackupReason::Daily)),
+                "",
+            )
+            .unwrap();
+        }
+        fs::write(dir.path().join("notes.txt"), "").unwrap();
+
+        let backups = list_in(dir.path()).unwrap();
+        assert_eq!(
+            backups.iter().map(|b| b.created_at).collect::<Vec<_>>(),
+            [time(4), time(3), time(2), time(1)]
+        );
+        assert_eq!(to_rotate(&backups, 3), &backups[3..]);
+        assert!(to_rotate(&backups, MAX_BACKUPS).is_empty());
+        assert!(list_in(&dir.path().join("missing")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn writes_atomically() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        write_atomic(&path, b"a = 1").unwrap();
+        write_atomic(&path, b"a = 2").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a = 2");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn private_writes_replace_readable_files() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secrets.toml");
+        fs::write(&path, "").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        write_atomic_private(&path, b"a = 1").unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
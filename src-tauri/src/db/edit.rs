@@ -0,0 +1,338 @@
+//! Granular edits of the local config.
+//!
+//! The frontend used to send the whole [`Config`] back to be saved, losing
+//! anything the backend wrote in the meantime, like the play time of a
+//! running game. Now each edit patches [`CONFIG`] under its lock, and is only
+//! made if the config is still at the revision the frontend last saw.
+//!
+//! Writes of fields only the backend owns, see [`save_owned_write`], never
+//! conflict with an edit, so an edit made before them is rebased onto them
+//! instead of being refused.
+
+use std::{collections::VecDeque, sync::LazyLock};
+
+use chrono::Utc;
+use parking_lot::Mutex;
+use tauri::AppHandle;
+
+use super::{
+    CONFIG, Config, Game, device::Device, revision::VersionVector, secrets::keep_local_secrets,
+};
+use crate::{
+    error::{Error, Result},
+    plugin::PluginInstance,
+};
+
+/// Number of [`OWNED_WRITES`] remembered.
+const MAX_OWNED_WRITES: usize = 64;
+
+/// Revisions before and after the recent writes of backend-owned fields.
+static OWNED_WRITES: LazyLock<Mutex<VecDeque<(VersionVector, VersionVector)>>> =
+    LazyLock::new(Default::default);
+
+/// Apply `edit` to the local config and save it, unless the config is not at
+/// `expected_revision` anymore, or only differs by writes of backend-owned
+/// fields. Returns the new revision.
+pub fn edit_config(
+    app: &AppHandle,
+    expected_revision: Option<&VersionVector>,
+    edit: impl FnOnce(&mut Config) -> Result<()>,
+) -> Result<VersionVector> {
+    let mut lock = CONFIG.lock();
+    lock.check_edit_revision(expected_revision)?;
+    edit(&mut lock)?;
+    lock.save_and_emit(app)?;
+    Ok(lock.revision.clone())
+}
+
+/// Save `config` after a write of fields that only the backend writes and
+/// that no edit touches, such as the play time of a game.
+pub fn save_owned_write(config: &mut Config, app: &AppHandle, from: VersionVector) -> Result<()> {
+    config.save_and_emit(app)?;
+    record_owned_write(from, config.revision.clone());
+    Ok(())
+}
+
+fn record_owned_write(from: VersionVector, to: VersionVector) {
+    let mut writes = OWNED_WRITES.lock();
+    if writes.len() == MAX_OWNED_WRITES {
+        writes.pop_front();
+    }
+    writes.push_back((from, to));
+}
+
+/// `game` with the backend-owned fields of `current`, like the play time.
+fn keep_owned_fields(game: Game, current: &Game) -> Game {
+    Game {
+        added_time: current.added_time,
+        use_time: current.use_time,
+        last_played_time: current.last_played_time,
+        last_upload_time: current.last_upload_time,
+        ..game
+    }
+}
+
+/// Whether `from` leads to `to` through owned writes only.
+fn owned_writes_between(from: &VersionVector, to: &VersionVector) -> bool {
+    let writes = OWNED_WRITES.lock();
+    let mut current = from;
+    while current != to {
+        match writes.iter().find(|(before, _)| before == current) {
+            Some((_, after)) => current = after,
+            None => return false,
+        }
+    }
+    true
+}
+
+impl Config {
+    /// Fails with [`Error::ConfigOutdated`] if `expected` is given and is not
+    /// the current revision.
+    pub fn check_revision(&self, expected: Option<&VersionVector>) -> Result<()> {
+        match expected {
+            Some(expected) if *expected != self.revision => Err(Error::ConfigOutdated),
+            _ => Ok(()),
+        }
+    }
+
+    /// Like [`Self::check_revision`], but the config may have moved on from
+    /// `expected` by writes of backend-owned fields.
+    pub fn check_edit_revision(&self, expected: Option<&VersionVector>) -> Result<()> {
+        match expected {
+            Some(expected) if !owned_writes_between(expected, &self.revision) => {
+                Err(Error::ConfigOutdated)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Add `game` with a new id. Plugins marked `auto_add` are attached unless
+    /// the game already has one of them. Returns the id.
+    pub fn add_game(&mut self, mut game: Game) -> u32 {
+        game.id = self.games.iter().map(|g| g.id).max().unwrap_or_default() + 1;
+        game.added_time = Utc::now();
+        for instance in self.plugin_metadatas.auto_add_instances() {
+            if !game
+                .plugins
+                .iter()
+                .any(|p| p.handler_key() == instance.handler_key())
+            {
+                game.plugins.push(instance);
+            }
+        }
+        let id = game.id;
+        self.games.push(game);
+        id
+    }
+
+    /// Replace the user-editable fields of the game with the id of `game`.
+    /// The fields the backend keeps, like the play time, stay as they are.
+    pub fn update_game(&mut self, game: Game) -> Result<()> {
+        let current = self.get_game_by_id_mut(game.id)?;
+        *current = keep_owned_fields(game, current);
+        Ok(())
+    }
+
+    /// Replace the config with `previous`, e.g. to undo applying a remote
+    /// config. The local secrets, the revisions and the backend-owned fields
+    /// of the games still here are kept.
+    pub fn restore_previous(&mut self, mut previous: Config) {
+        keep_local_secrets(&mut previous.settings.storage, &self.settings.storage);
+        for game in &mut previous.games {
+            if let Ok(current) = self.get_game_by_id(game.id) {
+                *game = keep_owned_fields(std::mem::take(game), current);
+            }
+        }
+        previous.revision = std::mem::take(&mut self.revision);
+        previous.synced_revision = std::mem::take(&mut self.synced_revision);
+        previous.last_sync = self.last_sync;
+        *self = previous;
+    }
+
+    pub fn delete_game(&mut self, id: u32) -> Result<Game> {
+        let index = self
+            .games
+            .iter()
+            .position(|g| g.id == id)
+            .ok_or(Error::GameNotFound)?;
+        Ok(self.games.remove(index))
+    }
+
+    /// Put the games in the order of `ids`. Games not in `ids` keep their
+    /// order after the listed ones.
+    pub fn reorder_games(&mut self, ids: &[u32]) -> Result<()> {
+        for &id in ids {
+            self.get_game_by_id(id)?;
+        }
+        self.games
+            .sort_by_key(|g| ids.iter().position(|&id| id == g.id).unwrap_or(ids.len()));
+        Ok(())
+    }
+
+    pub fn set_game_plugins(&mut self, id: u32, plugins: Vec<PluginInstance>) -> Result<()> {
+        self.get_game_by_id_mut(id)?.plugins = plugins;
+        Ok(())
+    }
+
+    /// Replace the device with the uid of `device`, or add it.
+    pub fn update_device(&mut self, device: Device) {
+        match self.devices.iter_mut().find(|d| d.uid == device.uid) {
+            Some(d) => *d = device,
+            None => self.devices.push(device),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        let mut config = Config::default();
+        for name in ["a", "b", "c"] {
+            config.add_game(Game {
+                name: name.to_string(),
+                ..Default::default()
+            });
+        }
+        config
+    }
+
+    fn names(config: &Config) -> Vec<&str> {
+        config.games.iter().map(|g| g.name.as_str()).collect()
+    }
+
+    #[test]
+    fn checks_the_revision() {
+        let mut config = config();
+        let seen = config.revision.clone();
+        assert!(config.check_revision(Some(&seen)).is_ok());
+        config.revision.bump_device("other");
+        assert!(matches!(
+            config.check_revision(Some(&seen)),
+            Err(Error::ConfigOutdated)
+        ));
+        assert!(config.check_revision(None).is_ok());
+    }
+
+    #[test]
+    fn edits_are_rebased_onto_owned_writes() {
+        let mut config = config();
+        config.revision.bump_device("rebase");
+        let seen = config.revision.clone();
+        for _ in 0..2 {
+            let from = config.revision.clone();
+            config.revision.bump_device("rebase");
+            record_owned_write(from, config.revision.clone());
+        }
+        assert!(config.check_edit_revision(Some(&seen)).is_ok());
+        assert!(config.check_revision(Some(&seen)).is_err());
+
+        config.revision.bump_device("rebase");
+        assert!(matches!(
+            config.check_edit_revision(Some(&seen)),
+            Err(Error::ConfigOutdated)
+        ));
+    }
+
+    #[test]
+    fn updates_keep_the_play_time() {
+        let mut config = config();
+        let played = config.get_game_by_id_mut(1).unwrap();
+        played.use_time = chrono::Duration::hours(3);
+        played.last_played_time = Some(Utc::now());
+        let played = played.clone();
+
+        config
+            .update_game(Game {
+                id: 1,
+                name: "renamed".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        let game = config.get_game_by_id(1).unwrap();
+        assert_eq!(game.name, "renamed");
+        assert_eq!(game.use_time, played.use_time);
+        assert_eq!(game.last_played_time, played.last_played_time);
+        assert_eq!(game.added_time, played.added_time);
+    }
+
+    #[test]
+    fn restoring_keeps_the_play_time_and_secrets() {
+        let previous = config();
+        let mut config = config();
+        config.revision.bump();
+        config.settings.storage.s3.secret_key = "secret:s3".to_string();
+        let played = config.get_game_by_id_mut(1).unwrap();
+        played.name = "renamed".to_string();
+        played.use_time = chrono::Duration::hours(3);
+        let revision = config.revision.clone();
+
+        config.restore_previous(previous);
+        let game = config.get_game_by_id(1).unwrap();
+        assert_eq!(game.name, "a");
+        assert_eq!(game.use_time, chrono::Duration::hours(3));
+        assert_eq!(config.settings.storage.s3.secret_key, "secret:s3");
+        assert_eq!(config.revision, revision);
+    }
+
+    #[test]
+    fn edits_games_by_id() {
+        let mut config = config();
+        assert_eq!(
+            config.games.iter().map(|g| g.id).collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+
+        config.delete_game(2).unwrap();
+        assert_eq!(config.add_game(Game::default()), 4);
+        assert!(matches!(config.delete_game(2), Err(Error::GameNotFound)));
+
+        let mut game = config.get_game_by_id(3).unwrap().clone();
+        game.name = "renamed".to_string();
+        config.update_game(game).unwrap();
+        assert_eq!(names(&config), ["a", "renamed", ""]);
+
+        config.reorder_games(&[4, 3]).unwrap();
+        assert_eq!(names(&config), ["", "renamed", "a"]);
+        assert!(config.reorder_games(&[5]).is_err());
+        assert_eq!(names(&config), ["", "renamed", "a"]);
+    }
+
+    #[test]
+    fn new_games_get_the_auto_add_plugins() {
+        let mut config = Config::default();
+        config.plugin_metadatas.execute.auto_add = true;
+        config.plugin_metadatas.wine.auto_add = true;
+        let id = config.add_game(Game {
+            plugins: vec![PluginInstance::Wine {
+                config: Default::default(),
+            }],
+            ..Default::default()
+        });
+
+        let keys: Vec<_> = config
+            .get_game_by_id(id)
+            .unwrap()
+            .plugins
+            .iter()
+            .map(|p| p.handler_key())
+            .collect();
+        assert_eq!(keys, ["wine", "execute"]);
+    }
+
+    #[test]
+    fn updates_devices_by_uid() {
+        let mut config = Config::default();
+        let mut device = Device {
+            name: "desktop".to_string(),
+            uid: "a".to_string(),
+            ..Default::default()
+        };
+        config.update_device(device.clone());
+        device.name = "laptop".to_string();
+        config.update_device(device);
+        assert_eq!(config.devices.len(), 1);
+        assert_eq!(config.devices[0].name, "laptop");
+    }
+}
@@ -0,0 +1,478 @@
+//! Three-way merge of [`Config`].
+//!
+//! The config that was last synced with remote is kept locally as the merge
+//! *base*. When both the local and the remote copy changed since then, the two
+//! sides are merged against the base: games (by id) and devices (by uid) are
+//! merged item by item, settings section by section. Fields changed on only
+//! one side are taken from that side, and `use_time` deltas from both sides
+//! are added up. Everything else is reported as a [`ConfigConflict`] that the
+//! UI can resolve field by field.
+
+use std::{collections::HashMap, fmt::Display, fs, path::PathBuf, sync::LazyLock};
+
+use chrono::Duration;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::{CONFIG_DIR, Config, Game, device::Device, settings::Settings};
+use crate::error::Result;
+
+/// The config as it was at the last successful sync.
+pub static BASE_CONFIG_PATH: LazyLock<PathBuf> =
+    LazyLock::new(|| CONFIG_DIR.join("config.base.toml"));
+
+/// Which side wins a conflicting field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum MergeSide {
+    Local,
+    Remote,
+}
+
+/// A field that was changed differently on both sides since the last sync.
+///
+/// Values are rendered as TOML; `None` means the item does not exist on that
+/// side (e.g. a game deleted on one device and edited on the other).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct ConfigConflict {
+    /// Dotted path of the field, e.g. `games.42.savePaths` or
+    /// `settings.storage`. Used as the key of a resolution.
+    pub path: String,
+    pub base: Option<String>,
+    pub local: Option<String>,
+    pub remote: Option<String>,
+}
+
+/// User choices for conflicts, keyed by [`ConfigConflict::path`].
+pub type ConflictResolutions = HashMap<String, MergeSide>;
+
+/// Result of [`merge_config`]. If `conflicts` is not empty, the conflicting
+/// fields in `merged` hold the local value.
+#[derive(Debug, Clone)]
+pub struct MergeResult {
+    pub merged: Config,
+    pub conflicts: Vec<ConfigConflict>,
+}
+
+/// Load the base config written by the last successful sync.
+pub fn load_base_config() -> Option<Config> {
+    let content = fs::read_to_string(BASE_CONFIG_PATH.as_path()).ok()?;
+    match toml::from_str(&content) {
+        Ok(c) => Some(c),
+        Err(e) => {
+            warn!("broken base config, ignoring: {e}");
+            None
+        }
+    }
+}
+
+/// The base of a merge of `local` and `remote`: the config of the last sync,
+/// or [`fallback_base`] if there is none, e.g. after upgrading from a version
+/// that did not keep it.
+pub fn merge_base(local: &Config, remote: &Config) -> Config {
+    load_base_config().unwrap_or_else(|| fallback_base(local, remote))
+}
+
+/// A stand-in base for configs that were never synced through a merge. Fields
+/// that differ are taken from the side written last, items only on one side
+/// are kept, and play time is the larger of both sides.
+pub fn fallback_base(local: &Config, remote: &Config) -> Config {
+    let (older, newer) = if local.last_updated >= remote.last_updated {
+        (remote, local)
+    } else {
+        (local, remote)
+    };
+    let mut base = older.clone();
+    base.games.retain_mut(|g| {
+        let Some(n) = newer.games.iter().find(|n| n.id == g.id) else {
+            return false;
+        };
+        g.use_time = g.use_time.min(n.use_time);
+        true
+    });
+    base.devices
+        .retain(|d| newer.devices.iter().any(|n| n.uid == d.uid));
+    base
+}
+
+/// Remember `config` as the base of the next merge.
+pub fn store_base_config(config: &Config) -> Result<()> {
+    let content = toml::to_string(config)?;
+    fs::write(BASE_CONFIG_PATH.as_path(), content)?;
+    Ok(())
+}
+
+/// Three-way merge `local` and `remote` against their common `base`.
+///
+/// Conflicts listed in `resolutions` are resolved to the chosen side and not
+/// reported again.
+pub fn merge_config(
+    base: &Config,
+    local: &Config,
+    remote: &Config,
+    resolutions: &ConflictResolutions,
+) -> MergeResult {
+    let mut ctx = MergeCtx {
+        resolutions,
+        conflicts: vec![],
+    };
+
+    let games = merge_keyed(
+        "games",
+        &base.games,
+        &local.games,
+        &remote.games,
+        |g| g.id,
+        merge_game,
+        &mut ctx,
+    );
+    let devices = merge_keyed(
+        "devices",
+        &base.devices,
+        &local.devices,
+        &remote.devices,
+        |d: &Device| d.uid.clone(),
+        |path, b, l, r, ctx| ctx.field(path, b, l, r),
+        &mut ctx,
+    );
+    let settings = merge_settings(&base.settings, &local.settings, &remote.settings, &mut ctx);
+    let plugin_metadatas = ctx.field(
+        "pluginMetadatas".to_string(),
+        Some(&base.plugin_metadatas),
+        &local.plugin_metadatas,
+        &remote.plugin_metadatas,
+    );
+
+    let merged = Config {
+        db_version: local.db_version.max(remote.db_version),
+        last_updated: local.last_updated.max(remote.last_updated),
+        revision: local.revision.join(&remote.revision),
+        games,
+        devices,
+        settings,
+        plugin_metadatas,
+        ..local.clone()
+    };
+    MergeResult {
+        merged,
+        conflicts: ctx.conflicts,
+    }
+}
+
+struct MergeCtx<'a> {
+    resolutions: &'a ConflictResolutions,
+    conflicts: Vec<ConfigConflict>,
+}
+
+impl MergeCtx<'_> {
+    /// Three-way merge a single value, compared by its serialized form.
+    fn field<T: Serialize + Clone>(
+        &mut self,
+        path: String,
+        base: Option<&T>,
+        local: &T,
+        remote: &T,
+    ) -> T {
+        let (b, l, r) = (base.and_then(repr), repr(local), repr(remote));
+        if l == r || r == b {
+            return local.clone();
+        }
+        if l == b {
+            return remote.clone();
+        }
+        match self.resolve(path, b, l, r) {
+            MergeSide::Local => local.clone(),
+            MergeSide::Remote => remote.clone(),
+        }
+    }
+
+    /// Look up the user's choice for `path`, or record a conflict and keep the
+    /// local side.
+    fn resolve(
+        &mut self,
+        path: String,
+        base: Option<toml::Value>,
+        local: Option<toml::Value>,
+        remote: Option<toml::Value>,
+    ) -> MergeSide {
+        if let Some(side) = self.resolutions.get(&path) {
+            return *side;
+        }
+        self.conflicts.push(ConfigConflict {
+            path,
+            base: base.map(|v| v.to_string()),
+            local: local.map(|v| v.to_string()),
+            remote: remote.map(|v| v.to_string()),
+        });
+        MergeSide::Local
+    }
+}
+
+/// Serialized form used to compare values. `None` for values TOML cannot
+/// represent on their own, i.e. `Option::None`.
+fn repr<T: Serialize>(value: &T) -> Option<toml::Value> {
+    toml::Value::try_from(value).ok()
+}
+
+/// Merge two lists of items identified by `key`. Local order is kept; items
+/// only present on remote are appended in remote order.
+///
+/// An item deleted on one side is dropped if the other side left it unchanged,
+/// otherwise the deletion is a conflict on `{prefix}.{key}`.
+fn merge_keyed<T, K>(
+    prefix: &str,
+    base: &[T],
+    local: &[T],
+    remote: &[T],
+    key: impl Fn(&T) -> K,
+    mut merge_item: impl FnMut(String, Option<&T>, &T, &T, &mut MergeCtx) -> T,
+    ctx: &mut MergeCtx,
+) -> Vec<T>
+where
+    T: Serialize + Clone,
+    K: Eq + Display,
+{
+    let find = |items: &[T], k: &K| items.iter().position(|i| key(i) == *k);
+
+    let mut merged = vec![];
+    for l in local {
+        let k = key(l);
+        let path = format!("{prefix}.{k}");
+        let b = find(base, &k).map(|i| &base[i]);
+        match (b, find(remote, &k).map(|i| &remote[i])) {
+            (b, Some(r)) => merged.push(merge_item(path, b, l, r, ctx)),
+            // added locally
+            (None, None) => merged.push(l.clone()),
+            // deleted on remote
+            (Some(b), None) => {
+                let (b_repr, l_repr) = (repr(b), repr(l));
+                if b_repr != l_repr && ctx.resolve(path, b_repr, l_repr, None) == MergeSide::Local {
+                    merged.push(l.clone());
+                }
+            }
+        }
+    }
+    for r in remote {
+        let k = key(r);
+        if find(local, &k).is_some() {
+            continue;
+        }
+        match find(base, &k).map(|i| &base[i]) {
+            // added on remote
+            None => merged.push(r.clone()),
+            // deleted locally
+            Some(b) => {
+                let (b_repr, r_repr) = (repr(b), repr(r));
+                let path = format!("{prefix}.{k}");
+                if b_repr != r_repr && ctx.resolve(path, b_repr, None, r_repr) == MergeSide::Remote
+                {
+                    merged.push(r.clone());
+                }
+            }
+        }
+    }
+    merged
+}
+
+fn merge_game(
+    path: String,
+    base: Option<&Game>,
+    local: &Game,
+    remote: &Game,
+    ctx: &mut MergeCtx,
+) -> Game {
+    let f = |name: &str| format!("{path}.{name}");
+    // Play time is accumulated on every device independently, so both deltas
+    // count. Without a base (added on both sides) the larger one wins.
+    let use_time = match base {
+        Some(b) => (local.use_time + remote.use_time - b.use_time).max(Duration::zero()),
+        None => local.use_time.max(remote.use_time),
+    };
+    Game {
+        id: local.id,
+        name: ctx.field(f("name"), base.map(|b| &b.name), &local.name, &remote.name),
+        excutable_path: ctx.field(
+            f("excutablePath"),
+            base.map(|b| &b.excutable_path),
+            &local.excutable_path,
+            &remote.excutable_path,
+        ),
+        save_paths: ctx.field(
+            f("savePaths"),
+            base.map(|b| &b.save_paths),
+            &local.save_paths,
+            &remote.save_paths,
+        ),
+        image_url: ctx.field(
+            f("imageUrl"),
+            base.map(|b| &b.image_url),
+            &local.image_url,
+            &remote.image_url,
+        ),
+        image_sha256: ctx.field(
+            f("imageSha256"),
+            base.map(|b| &b.image_sha256),
+            &local.image_sha256,
+            &remote.image_sha256,
+        ),
+        added_time: local.added_time.min(remote.added_time),
+        use_time,
+        last_played_time: local.last_played_time.max(remote.last_played_time),
+        last_upload_time: local.last_upload_time.max(remote.last_upload_time),
+        plugins: ctx.field(
+            f("plugins"),
+            base.map(|b| &b.plugins),
+            &local.plugins,
+            &remote.plugins,
+        ),
+    }
+}
+
+fn merge_settings(
+    base: &Settings,
+    local: &Settings,
+    remote: &Settings,
+    ctx: &mut MergeCtx,
+) -> Settings {
+    macro_rules! section {
+        ($field:ident, $name:literal) => {
+            ctx.field(
+                concat!("settings.", $name).to_string(),
+                Some(&base.$field),
+                &local.$field,
+                &remote.$field,
+            )
+        };
+    }
+    Settings {
+        storage: section!(storage, "storage"),
+        archive: section!(archive, "archive"),
+        appearance: section!(appearance, "appearance"),
+        launch: section!(launch, "launch"),
+        auto_sync_interval: section!(auto_sync_interval, "autoSyncInterval"),
+        auto_sync: section!(auto_sync, "autoSync"),
+        sync_io_timeout_secs: section!(sync_io_timeout_secs, "syncIoTimeoutSecs"),
+        sync_non_io_timeout_secs: section!(sync_non_io_timeout_secs, "syncNonIoTimeoutSecs"),
+        config_snapshot_retention: section!(config_snapshot_retention, "configSnapshotRetention"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+
+    fn game(id: u32, name: &str, use_secs: i64) -> Game {
+        Game {
+            id,
+            name: name.to_string(),
+            use_time: Duration::seconds(use_secs),
+            ..Default::default()
+        }
+    }
+
+    fn config(games: Vec<Game>) -> Config {
+        Config {
+            games,
+            ..Default::default()
+        }
+    }
+
+    fn merge(base: &Config, local: &Config, remote: &Config) -> MergeResult {
+        merge_config(base, local, remote, &Default::default())
+    }
+
+    #[test]
+    fn one_sided_changes_are_taken() {
+        let base = config(vec![game(1, "a", 0), game(2, "b", 0)]);
+        let local = config(vec![game(1, "a2", 0), game(2, "b", 0)]);
+        let remote = config(vec![game(1, "a", 0), game(2, "b2", 0), game(3, "c", 0)]);
+        let res = merge(&base, &local, &remote);
+        assert!(res.conflicts.is_empty());
+        let names: Vec<_> = res.merged.games.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, ["a2", "b2", "c"]);
+    }
+
+    #[test]
+    fn use_time_deltas_are_added() {
+        let base = config(vec![game(1, "a", 100)]);
+        let local = config(vec![game(1, "a", 130)]);
+        let remote = config(vec![game(1, "a", 150)]);
+        let res = merge(&base, &local, &remote);
+        assert!(res.conflicts.is_empty());
+        assert_eq!(res.merged.games[0].use_time, Duration::seconds(180));
+    }
+
+    #[test]
+    fn both_sides_changed_is_a_conflict() {
+        let base = config(vec![game(1, "a", 0)]);
+        let local = config(vec![game(1, "local", 0)]);
+        let remote = config(vec![game(1, "remote", 0)]);
+        let res = merge(&base, &local, &remote);
+        assert_eq!(res.conflicts.len(), 1);
+        assert_eq!(res.conflicts[0].path, "games.1.name");
+        assert_eq!(res.merged.games[0].name, "local");
+
+        let resolutions = HashMap::from([("games.1.name".to_string(), MergeSide::Remote)]);
+        let res = merge_config(&base, &local, &remote, &resolutions);
+        assert!(res.conflicts.is_empty());
+        assert_eq!(res.merged.games[0].name, "remote");
+    }
+
+    #[test]
+    fn deletion_vs_edit_is_a_conflict() {
+        let base = config(vec![game(1, "a", 0), game(2, "b", 0)]);
+        // game 1 deleted locally and untouched remotely; game 2 deleted locally
+        // but edited remotely
+        let local = config(vec![]);
+        let remote = config(vec![game(1, "a", 0), game(2, "b2", 0)]);
+        let res = merge(&base, &local, &remote);
+        assert!(res.merged.games.is_empty());
+        assert_eq!(res.conflicts.len(), 1);
+        assert_eq!(res.conflicts[0].path, "games.2");
+        assert_eq!(res.conflicts[0].local, None);
+    }
+
+    #[test]
+    fn without_base_the_newer_side_wins() {
+        let mut local = config(vec![game(1, "local", 100), game(2, "only local", 0)]);
+        let mut remote = config(vec![game(1, "remote", 150), game(3, "only remote", 0)]);
+        local.settings.auto_sync_interval = 60;
+        local.last_updated = DateTime::from_timestamp(100, 0).unwrap();
+        remote.last_updated = DateTime::from_timestamp(200, 0).unwrap();
+
+        let res = merge(&fallback_base(&local, &remote), &local, &remote);
+        assert!(res.conflicts.is_empty());
+        let names: Vec<_> = res.merged.games.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, ["remote", "only local", "only remote"]);
+        assert_eq!(res.merged.games[0].use_time, Duration::seconds(150));
+        assert_eq!(
+            res.merged.settings.auto_sync_interval,
+            remote.settings.auto_sync_interval
+        );
+
+        local.last_updated = DateTime::from_timestamp(300, 0).unwrap();
+        let res = merge(&fallback_base(&local, &remote), &local, &remote);
+        assert!(res.conflicts.is_empty());
+        assert_eq!(res.merged.games[0].name, "local");
+        assert_eq!(res.merged.settings.auto_sync_interval, 60);
+    }
+
+    #[test]
+    fn settings_are_merged_per_section() {
+        let base = Config::default();
+        let mut local = base.clone();
+        local.settings.auto_sync_interval = 60;
+        let mut remote = base.clone();
+        remote.settings.archive.level = 9;
+        let res = merge(&base, &local, &remote);
+        assert!(res.conflicts.is_empty());
+        assert_eq!(res.merged.settings.auto_sync_interval, 60);
+        assert_eq!(res.merged.settings.archive.level, 9);
+    }
+}
@@ -1,14 +1,22 @@
 use serde::{Deserialize, Deserializer};
 
-use super::{Config, settings::LocalConfig};
+use super::{
+    Config, SavePathConfig, revision::VersionVector, secrets::extract_secrets,
+    settings::LocalConfig,
+};
+
+/// Version of the configs written by this build.
+pub const DB_VERSION: u32 = 2;
 
 impl Default for Config {
     #[allow(deprecated)]
     fn default() -> Self {
         Self {
-            db_version: 1,
+            db_version: DB_VERSION,
             last_updated: Default::default(),
             last_sync: Default::default(),
+            revision: Default::default(),
+            synced_revision: Default::default(),
             last_uploaded: Default::default(),
             games: Default::default(),
             devices: Default::default(),
@@ -47,6 +55,31 @@ where
     }
 }
 
+/// Save paths used to be plain strings, without include/exclude rules.
+pub fn deserialize_save_paths_compat<'de, D>(
+    deserializer: D,
+) -> Result<Vec<SavePathConfig>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum SavePathOrString {
+        // 兼容旧配置：save_paths = ["..."]
+        Path(String),
+        Config(SavePathConfig),
+    }
+
+    let paths = Vec::<SavePathOrString>::deserialize(deserializer)?;
+    Ok(paths
+        .into_iter()
+        .map(|p| match p {
+            SavePathOrString::Path(path) => SavePathConfig::from(path),
+            SavePathOrString::Config(config) => config,
+        })
+        .collect())
+}
+
 #[allow(deprecated)]
 pub fn migrate(mut config: Config) -> Config {
     if config.db_version == 0 {
@@ -55,9 +88,34 @@ pub fn migrate(mut config: Config) -> Config {
         }
         config.db_version = 1;
     }
+    if config.db_version == 1 {
+        // Configs were compared by timestamps before revisions. Keep comparing
+        // them like that until a device writes again.
+        config.revision = VersionVector::legacy(config.last_updated);
+        config.synced_revision = config
+            .last_sync
+            .map(VersionVector::legacy)
+            .unwrap_or_default();
+        config.db_version = 2;
+    }
     config
 }
 
+/// Move plaintext storage credentials out of `config` into the local secrets
+/// store. This is idempotent and also catches configs written by older
+/// clients, so it runs on every load instead of behind a `db_version` bump.
+///
+/// Returns whether the config changed and needs to be stored.
+pub fn migrate_secrets(config: &mut Config) -> bool {
+    match extract_secrets(&mut config.settings.storage) {
+        Ok(moved) => moved,
+        Err(e) => {
+            log::error!("failed to move secrets out of config: {e}");
+            false
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use chrono::{DateTime, Utc};
@@ -72,6 +130,8 @@ mod tests {
                 .unwrap()
                 .with_timezone(&Utc),
             last_sync: None,
+            revision: Default::default(),
+            synced_revision: Default::default(),
             last_uploaded: None,
             games: vec![],
             devices: vec![],
@@ -89,7 +149,7 @@ mod tests {
         let mut cfg = base_v0_config();
         cfg.last_uploaded = Some(ts);
         let migrated = migrate(cfg);
-        assert_eq!(migrated.db_version, 1);
+        assert_eq!(migrated.db_version, DB_VERSION);
         assert_eq!(migrated.last_sync, Some(ts));
         assert_eq!(migrated.last_uploaded, None);
     }
@@ -109,18 +169,35 @@ mod tests {
         cfg.last_sync = Some(ts_sync);
         cfg.last_uploaded = Some(ts_uploaded);
         let migrated = migrate(cfg);
-        assert_eq!(migrated.db_version, 1);
+        assert_eq!(migrated.db_version, DB_VERSION);
         assert_eq!(migrated.last_sync, Some(ts_sync));
         assert_eq!(migrated.last_uploaded, Some(ts_uploaded));
     }
 
     #[test]
-    fn migrate_is_idempotent_for_v1() {
+    fn migrate_v1_derives_revisions_from_timestamps() {
+        let mut cfg = base_v0_config();
+        cfg.db_version = 1;
+        let updated = cfg.last_updated;
+        cfg.last_sync = Some(updated - chrono::Duration::hours(1));
+        let migrated = migrate(cfg);
+        assert_eq!(migrated.revision, VersionVector::legacy(updated));
+        assert!(migrated.is_dirty());
+
+        let mut cfg = base_v0_config();
+        cfg.db_version = 1;
+        cfg.last_sync = Some(updated);
+        assert!(!migrate(cfg).is_dirty());
+    }
+
+    #[test]
+    fn migrate_is_idempotent_for_current_version() {
         // Already-migrated configs pass through unchanged.
-        let cfg = Config::default();
-        assert_eq!(cfg.db_version, 1);
+        let mut cfg = Config::default();
+        cfg.revision.bump_device("a");
+        assert_eq!(cfg.db_version, DB_VERSION);
         let migrated = migrate(cfg.clone());
-        assert_eq!(migrated.db_version, 1);
+        assert_eq!(migrated.db_version, DB_VERSION);
         assert_eq!(migrated.last_sync, cfg.last_sync);
     }
 
@@ -149,4 +226,27 @@ mod tests {
         let w: Wrap = toml::from_str(toml_str).unwrap();
         assert_eq!(w.local.path, "/new");
     }
+
+    #[test]
+    fn deserialize_save_paths_accepts_strings_and_structs() {
+        let toml_str = r#"savePaths = ["/legacy", { path = "/new", exclude = ["*.log"] }]"#;
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Wrap {
+            #[serde(deserialize_with = "deserialize_save_paths_compat")]
+            save_paths: Vec<SavePathConfig>,
+        }
+        let w: Wrap = toml::from_str(toml_str).unwrap();
+        assert_eq!(
+            w.save_paths,
+            [
+                SavePathConfig::from("/legacy".to_string()),
+                SavePathConfig {
+                    path: "/new".to_string(),
+                    include: vec![],
+                    exclude: vec!["*.log".to_string()],
+                },
+            ]
+        );
+    }
 }
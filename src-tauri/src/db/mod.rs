@@ -1,8 +1,17 @@
+pub mod backup;
 pub mod device;
+pub mod edit;
+pub mod merge;
 mod migration;
+pub mod revision;
+pub mod secrets;
 pub mod settings;
 
-use std::{fs, path::PathBuf, sync::LazyLock as Lazy};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::LazyLock as Lazy,
+};
 
 use chrono::{DateTime, Duration, Utc};
 use config_file2::{LoadConfigFile, Storable};
@@ -16,7 +25,11 @@ use tauri::{AppHandle, Emitter as _};
 use ts_rs::TS;
 
 use crate::{
-    db::{device::VarMap, migration::migrate},
+    db::{
+        device::VarMap,
+        migration::{deserialize_save_paths_compat, migrate_secrets},
+        revision::VersionVector,
+    },
     error::{Error, Result},
     plugin::{
         PluginInstance, PluginMetadatas, deserialize_metadatas_fallback,
@@ -36,6 +49,8 @@ pub static CONFIG_DIR: Lazy<PathBuf> = Lazy::new(|| {
     dir
 });
 
+pub(crate) use migration::migrate;
+
 pub static CONFIG_FILENAME: &str = "config.toml";
 pub static CONFIG_PATH: Lazy<PathBuf> = Lazy::new(|| CONFIG_DIR.join(CONFIG_FILENAME));
 
@@ -47,18 +62,34 @@ pub static CONFIG: Lazy<Mutex<Config>> = Lazy::new(|| {
             // back the broken file up so the user can recover it manually, then
             // start from a clean default.
             log::error!("failed to load config, using default: {e}");
-            let backup = CONFIG_PATH.with_extension("toml.bak");
-            let _ = fs::rename(CONFIG_PATH.as_path(), &backup);
+            match backup::backup_broken(&CONFIG_PATH) {
+                Ok(backup) => log::error!("broken config moved to {}", backup.display()),
+                Err(e) => log::error!("failed to back up broken config: {e}"),
+            }
             Config::default()
         }
     };
-    Mutex::new(migrate(config))
+    let mut config = migrate(config);
+    // the file on disk still has the credentials in plaintext, never back it
+    // up
+    if migrate_secrets(&mut config)
+        && let Err(e) = config.write_to(&CONFIG_PATH, None)
+    {
+        log::error!("failed to store config after moving out secrets: {e}");
+    }
+    Mutex::new(config)
 });
 
 impl Storable for Config {
     fn path(&self) -> impl AsRef<std::path::Path> {
         CONFIG_PATH.as_path()
     }
+
+    /// Written atomically, after a daily backup of the previous file.
+    fn store(&self) -> config_file2::Result<()> {
+        self.write_to(self.path().as_ref(), Some(&backup::BACKUP_DIR))?;
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -67,11 +98,16 @@ impl Storable for Config {
 #[serde(default)]
 pub struct Config {
     pub db_version: u32,
-    /// The last time the config was updated from frontend
+    /// The last time the config was updated from frontend. Only for display,
+    /// sync compares [`revision`](Self::revision) instead.
     pub last_updated: DateTime<Utc>,
     /// The last time the config was uploaded to remote or downloaded from
-    /// remote
+    /// remote. Only for display.
     pub last_sync: Option<DateTime<Utc>>,
+    /// Writes to the config, counted per device.
+    pub revision: VersionVector,
+    /// [`revision`](Self::revision) of the config at the last sync.
+    pub synced_revision: VersionVector,
     #[deprecated(note = "use last_sync instead")]
     pub last_uploaded: Option<DateTime<Utc>>,
     pub games: Vec<Game>,
@@ -89,7 +125,8 @@ pub struct Game {
     pub id: u32,
     pub name: String,
     pub excutable_path: Option<String>,
-    pub save_paths: Vec<String>,
+    #[serde(deserialize_with = "deserialize_save_paths_compat")]
+    pub save_paths: Vec<SavePathConfig>,
     pub image_url: Option<String>,
     pub image_sha256: Option<String>,
     pub added_time: DateTime<Utc>,
@@ -105,12 +142,46 @@ pub struct Game {
     pub plugins: Vec<PluginInstance>,
 }
 
+/// A save path of a game and which files in it to archive.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub struct SavePathConfig {
+    /// Path template, may contain vars.
+    pub path: String,
+    /// Glob patterns relative to the save path. If any, only the files
+    /// matching one of them are archived.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// Glob patterns relative to the save path of files and dirs that are
+    /// never archived. Takes precedence over `include`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+}
+
+impl From<String> for SavePathConfig {
+    fn from(path: String) -> Self {
+        Self {
+            path,
+            ..Default::default()
+        }
+    }
+}
+
 impl Config {
     #[inline]
     pub fn get_device(&self) -> Option<&Device> {
         self.devices.iter().find(|d| d.uid == *DEVICE_UID)
     }
 
+    /// Name of this device, for the archive filenames.
+    #[inline]
+    pub fn device_name(&self) -> String {
+        self.get_device()
+            .map(|d| d.name.clone())
+            .unwrap_or_else(|| format!("Unknown{}", self.devices.len()))
+    }
+
     #[inline]
     pub fn get_device_mut(&mut self) -> Option<&mut Device> {
         self.devices.iter_mut().find(|d| d.uid == *DEVICE_UID)
@@ -209,13 +280,38 @@ impl Config {
         Ok(())
     }
 
+    /// Write the config to `path` atomically, after a daily backup of the
+    /// previous file to `backup_dir` if given.
+    fn write_to(&self, path: &Path, backup_dir: Option<&Path>) -> io::Result<()> {
+        let content = toml::to_string(self).map_err(io::Error::other)?;
+        if let Some(dir) = backup_dir
+            && let Err(e) = backup::backup_if_due(dir, path)
+        {
+            warn!("failed to back up config: {e}");
+        }
+        backup::write_atomic(path, content.as_bytes())
+    }
+
+    /// Count a write of this device.
     #[inline]
-    pub fn save_and_emit(&mut self, app_handle: &AppHandle) -> Result<()> {
+    pub fn touch(&mut self) {
         self.last_updated = Utc::now();
+        self.revision.bump();
+    }
+
+    /// Whether the config has writes that were not synced.
+    #[inline]
+    pub fn is_dirty(&self) -> bool {
+        !self.revision.seen_by(&self.synced_revision)
+    }
+
+    #[inline]
+    pub fn save_and_emit(&mut self, app_handle: &AppHandle) -> Result<()> {
+        self.touch();
         self.save_and_emit_no_update(app_handle)
     }
 
-    /// Save config without updating last_updated. This is useful in some cases.
+    /// Save config without counting a write. This is useful in some cases.
     #[inline]
     pub fn save_and_emit_no_update(&mut self, app_handle: &AppHandle) -> Result<()> {
         self.store()?;
@@ -261,6 +357,7 @@ mod tests {
     use chrono::DateTime;
 
     use super::*;
+    use crate::db::secrets::SECRET_REF_PREFIX;
 
     /// Helper: build a [`Config`] with the given (id, use_time_secs,
     /// last_played_time) tuples. `last_played_time` is given as epoch
@@ -357,4 +454,35 @@ mod tests {
             Error::GameTimeCheckFailed(_)
         ));
     }
+
+    #[test]
+    fn backups_never_have_migrated_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILENAME);
+        let backups = dir.path().join("backups");
+        let mut config = Config::default();
+        config.settings.storage.webdav.password = Some("hunter2".to_string());
+        config.settings.storage.s3.secret_key = "hunter3".to_string();
+        fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+
+        // what `migrate_secrets` leaves behind
+        config.settings.storage.webdav.password = Some(format!("{SECRET_REF_PREFIX}webdav"));
+        config.settings.storage.s3.secret_key = format!("{SECRET_REF_PREFIX}s3");
+        config.write_to(&path, None).unwrap();
+        config.write_to(&path, Some(&backups)).unwrap();
+
+        let entries = fs::read_dir(&backups).unwrap().collect::<Vec<_>>();
+        assert_eq!(entries.len(), 1);
+        for entry in entries {
+            let path = entry.unwrap().path();
+            let content = fs::read_to_string(&path).unwrap();
+            assert!(!content.contains("hunter"), "{content}");
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mode = fs::metadata(&path).unwrap().permissions().mode();
+                assert_eq!(mode & 0o777, 0o600);
+            }
+        }
+    }
 }
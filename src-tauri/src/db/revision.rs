@@ -0,0 +1,166 @@
+//! Revisions of the config, to tell which side of a sync is newer without
+//! trusting the clocks of the devices.
+//!
+//! Every device counts its own writes in a [`VersionVector`] keyed by
+//! [`DEVICE_UID`]. A config is newer than another only if it has seen all of
+//! its writes and more; if both have writes the other has not seen, they are
+//! concurrent and need a merge.
+
+use std::{cmp::Ordering, collections::BTreeMap};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::device::DEVICE_UID;
+
+/// Key of the writes made before configs had revisions. They are counted in
+/// seconds of `last_updated`, so that configs migrated on different devices
+/// compare like they did by timestamp.
+pub const LEGACY_KEY: &str = "legacy";
+
+/// Number of writes per device. Devices without writes are left out.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(transparent)]
+pub struct VersionVector(BTreeMap<String, u32>);
+
+impl VersionVector {
+    /// The revision of a config written at `time` before revisions existed.
+    pub fn legacy(time: DateTime<Utc>) -> Self {
+        let mut vv = Self::default();
+        vv.set(
+            LEGACY_KEY,
+            time.timestamp().clamp(0, u32::MAX.into()) as u32,
+        );
+        vv
+    }
+
+    #[inline]
+    pub fn get(&self, uid: &str) -> u32 {
+        self.0.get(uid).copied().unwrap_or_default()
+    }
+
+    fn set(&mut self, uid: &str, count: u32) {
+        if count == 0 {
+            self.0.remove(uid);
+        } else {
+            self.0.insert(uid.to_string(), count);
+        }
+    }
+
+    /// Count a write of this device.
+    #[inline]
+    pub fn bump(&mut self) {
+        self.bump_device(*DEVICE_UID);
+    }
+
+    pub fn bump_device(&mut self, uid: &str) {
+        self.set(uid, self.get(uid).saturating_add(1));
+    }
+
+    /// The revision that has seen the writes of both `self` and `other`.
+    pub fn join(&self, other: &Self) -> Self {
+        let mut joined = self.clone();
+        for (uid, &count) in &other.0 {
+            joined.set(uid, count.max(self.get(uid)));
+        }
+        joined
+    }
+
+    /// Whether `other` has seen every write of `self`.
+    #[inline]
+    pub fn seen_by(&self, other: &Self) -> bool {
+        self <= other
+    }
+
+    /// Whether neither revision has seen all writes of the other.
+    #[inline]
+    pub fn concurrent(&self, other: &Self) -> bool {
+        self.partial_cmp(other).is_none()
+    }
+}
+
+impl PartialEq for VersionVector {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl Eq for VersionVector {}
+
+/// Ordered by dominance: `a <= b` if `b` has seen every write of `a`.
+/// Concurrent revisions are not ordered.
+impl PartialOrd for VersionVector {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        let (mut less, mut greater) = (false, false);
+        for uid in self.0.keys().chain(other.0.keys()) {
+            match self.get(uid).cmp(&other.get(uid)) {
+                Ordering::Less => less = true,
+                Ordering::Greater => greater = true,
+                Ordering::Equal => {}
+            }
+        }
+        match (less, greater) {
+            (false, false) => Some(Ordering::Equal),
+            (true, false) => Some(Ordering::Less),
+            (false, true) => Some(Ordering::Greater),
+            (true, true) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vv(counts: &[(&str, u32)]) -> VersionVector {
+        let mut vv = VersionVector::default();
+        for &(uid, count) in counts {
+            vv.set(uid, count);
+        }
+        vv
+    }
+
+    #[test]
+    fn orders_by_dominance() {
+        let base = vv(&[("a", 1), ("b", 2)]);
+        let ahead = vv(&[("a", 2), ("b", 2)]);
+        let other = vv(&[("a", 1), ("b", 3)]);
+
+        assert!(base < ahead);
+        assert!(base <= vv(&[("a", 1), ("b", 2), ("c", 0)]));
+        assert!(VersionVector::default() < base);
+        assert!(ahead.concurrent(&other));
+        assert_eq!(ahead.partial_cmp(&other), None);
+
+        let joined = ahead.join(&other);
+        assert_eq!(joined, vv(&[("a", 2), ("b", 3)]));
+        assert!(ahead < joined && other < joined);
+    }
+
+    #[test]
+    fn bumps_the_counter_of_a_device() {
+        let mut revision = VersionVector::default();
+        revision.bump_device("a");
+        revision.bump_device("a");
+        revision.bump_device("b");
+        assert_eq!(revision.get("a"), 2);
+        assert_eq!(revision.get("b"), 1);
+        assert_eq!(revision.get("c"), 0);
+
+        let json = serde_json::to_value(&revision).unwrap();
+        assert_eq!(json, serde_json::json!({ "a": 2, "b": 1 }));
+    }
+
+    #[test]
+    fn legacy_revisions_compare_by_time() {
+        let older = VersionVector::legacy(DateTime::from_timestamp(100, 0).unwrap());
+        let newer = VersionVector::legacy(DateTime::from_timestamp(200, 0).unwrap());
+        assert!(older < newer);
+        assert_eq!(
+            VersionVector::legacy(DateTime::<Utc>::UNIX_EPOCH),
+            VersionVector::default()
+        );
+    }
+}
@@ -0,0 +1,237 @@
+//! Per-device store for storage credentials.
+//!
+//! Credentials must not end up in `config.toml`, since that file is uploaded
+//! to the very remote they protect. The config only holds references like
+//! `secret:webdav.password`; the values live in `secrets.toml` under
+//! [`CONFIG_DIR`], which is never synced, is only readable by the current
+//! user and can optionally be encrypted with a random key kept in the OS
+//! keyring.
+
+use std::{collections::BTreeMap, fs, path::PathBuf, sync::LazyLock as Lazy};
+
+use keyring::Entry;
+use log::{error, info, warn};
+use parking_lot::Mutex;
+
+use super::{CONFIG_DIR, backup::write_atomic_private, settings::StorageConfig};
+use crate::{
+    error::{Error, Result},
+    sync::crypto,
+};
+
+pub static SECRETS_PATH: Lazy<PathBuf> = Lazy::new(|| CONFIG_DIR.join("secrets.toml"));
+
+/// Prefix of a value in config that refers to the secrets store.
+pub const SECRET_REF_PREFIX: &str = "secret:";
+
+const WEBDAV_PASSWORD: &str = "webdav.password";
+const S3_ACCESS_KEY: &str = "s3.accessKey";
+const S3_SECRET_KEY: &str = "s3.secretKey";
+const ENCRYPTION_PASSPHRASE: &str = "encryption.passphrase";
+
+/// Keyring entry holding the key of an encrypted secrets file.
+const KEYRING_SERVICE: &str = "com.absx.galgamemanager";
+const KEYRING_USER: &str = "secrets";
+
+#[derive(Default)]
+struct SecretStore {
+    values: BTreeMap<String, String>,
+    encrypted: bool,
+}
+
+static SECRETS: Lazy<Mutex<SecretStore>> = Lazy::new(|| {
+    let store = SecretStore::load().unwrap_or_else(|e| {
+        error!("failed to load secrets, starting empty: {e}");
+        SecretStore::default()
+    });
+    Mutex::new(store)
+});
+
+#[inline]
+fn keyring_entry() -> Result<Entry> {
+    Ok(Entry::new(KEYRING_SERVICE, KEYRING_USER)?)
+}
+
+/// Key the secrets file is encrypted with. A new one is put in the keyring if
+/// there is none and `create` is set.
+fn keyring_key(create: bool) -> Result<String> {
+    let entry = keyring_entry()?;
+    match entry.get_password() {
+        Err(keyring::Error::NoEntry) if create => {
+            let key = crypto::random_passphrase();
+            entry.set_password(&key)?;
+            Ok(key)
+        }
+        key => Ok(key?),
+    }
+}
+
+/// Make an existing secrets file only readable by the current user.
+#[cfg(unix)]
+fn restrict_permissions(metadata: &fs::Metadata) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    if metadata.permissions().mode() & 0o077 != 0 {
+        fs::set_permissions(SECRETS_PATH.as_path(), fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
+
+impl SecretStore {
+    fn load() -> Result<Self> {
+        let data = match fs::read(SECRETS_PATH.as_path()) {
+            Ok(d) => d,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+        #[cfg(unix)]
+        restrict_permissions(&fs::metadata(SECRETS_PATH.as_path())?)?;
+        let encrypted = crypto::is_encrypted(&data);
+        let data = if encrypted {
+            crypto::decrypt_bytes(&keyring_key(false)?, &data)?
+        } else {
+            data
+        };
+        Ok(Self {
+            values: toml::from_slice(&data)?,
+            encrypted,
+        })
+    }
+
+    fn store(&self) -> Result<()> {
+        let content = toml::to_string(&self.values)?;
+        let data = if self.encrypted {
+            crypto::encrypt_bytes(&keyring_key(false)?, content.as_bytes())?
+        } else {
+            content.into_bytes()
+        };
+        write_atomic_private(&SECRETS_PATH, &data)?;
+        Ok(())
+    }
+
+    /// Value of the secret `key`.
+    fn get(&self, key: &str) -> Result<String> {
+        self.values
+            .get(key)
+            .cloned()
+            .ok_or_else(|| Error::MissingSecret(key.to_string()))
+    }
+}
+
+#[inline]
+fn secret_ref(key: &str) -> String {
+    format!("{SECRET_REF_PREFIX}{key}")
+}
+
+/// Resolve a credential from config: references are looked up in the store,
+/// anything else is returned as is. Fails if a referenced secret is missing,
+/// e.g. because the secrets file was lost.
+pub fn resolve(value: &str) -> Result<String> {
+    match value.strip_prefix(SECRET_REF_PREFIX) {
+        Some(key) => SECRETS.lock().get(key),
+        None => Ok(value.to_string()),
+    }
+}
+
+/// Move plaintext credentials of `storage` into the secrets store, leaving
+/// references behind. Returns whether anything was moved.
+pub fn extract_secrets(storage: &mut StorageConfig) -> Result<bool> {
+    let mut store = SECRETS.lock();
+    let mut moved = false;
+    let mut take = |key: &str, value: &mut String| {
+        if !value.is_empty() && !value.starts_with(SECRET_REF_PREFIX) {
+            let secret = std::mem::replace(value, secret_ref(key));
+            store.values.insert(key.to_string(), secret);
+            moved = true;
+        }
+    };
+    if let Some(password) = &mut storage.webdav.password {
+        take(WEBDAV_PASSWORD, password);
+    }
+    take(S3_ACCESS_KEY, &mut storage.s3.access_key);
+    take(S3_SECRET_KEY, &mut storage.s3.secret_key);
+    if let Some(passphrase) = &mut storage.encryption.passphrase {
+        take(ENCRYPTION_PASSPHRASE, passphrase);
+    }
+    if moved {
+        store.store()?;
+        info!("moved storage credentials into the secrets store");
+    }
+    Ok(moved)
+}
+
+/// Replace the credentials of `storage` (e.g. from a remote config) with the
+/// local ones, so that applying a config never touches local secrets.
+pub fn keep_local_secrets(storage: &mut StorageConfig, local: &StorageConfig) {
+    storage.webdav.password.clone_from(&local.webdav.password);
+    storage.s3.access_key.clone_from(&local.s3.access_key);
+    storage.s3.secret_key.clone_from(&local.s3.secret_key);
+    storage
+        .encryption
+        .passphrase
+        .clone_from(&local.encryption.passphrase);
+}
+
+/// Whether the secrets file is encrypted with the key in the keyring.
+pub fn is_encrypted() -> bool {
+    SECRETS.lock().encrypted
+}
+
+/// Encrypt the secrets file with a key in the OS keyring, or decrypt it and
+/// drop the key.
+pub fn set_encrypted(encrypted: bool) -> Result<()> {
+    let mut store = SECRETS.lock();
+    if encrypted {
+        keyring_key(true)?;
+    }
+    store.encrypted = encrypted;
+    if let Err(e) = store.store() {
+        store.encrypted = !encrypted;
+        return Err(e);
+    }
+    if !encrypted {
+        match keyring_entry()?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => warn!("failed to delete the secrets key from the keyring: {e}"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_values_are_resolved_as_is() {
+        assert_eq!(resolve("hunter2").unwrap(), "hunter2");
+        assert_eq!(resolve("").unwrap(), "");
+    }
+
+    #[test]
+    fn missing_secrets_are_errors() {
+        let mut store = SecretStore::default();
+        store
+            .values
+            .insert(WEBDAV_PASSWORD.to_string(), "hunter2".to_string());
+        assert_eq!(store.get(WEBDAV_PASSWORD).unwrap(), "hunter2");
+        assert!(matches!(
+            store.get(S3_SECRET_KEY),
+            Err(Error::MissingSecret(key)) if key == S3_SECRET_KEY
+        ));
+    }
+
+    #[test]
+    fn keep_local_secrets_ignores_remote_values() {
+        let mut local = StorageConfig::default();
+        local.webdav.password = Some(secret_ref(WEBDAV_PASSWORD));
+        local.s3.secret_key = secret_ref(S3_SECRET_KEY);
+        let mut remote = StorageConfig::default();
+        remote.webdav.password = Some("leaked".into());
+        remote.s3.access_key = "leaked".into();
+
+        keep_local_secrets(&mut remote, &local);
+        assert_eq!(remote.webdav.password, Some(secret_ref(WEBDAV_PASSWORD)));
+        assert_eq!(remote.s3.access_key, "");
+        assert_eq!(remote.s3.secret_key, secret_ref(S3_SECRET_KEY));
+    }
+}
@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 use tauri::AppHandle;
 use ts_rs::TS;
 
-use super::migration::deserialize_local_config_compat;
+use super::{migration::deserialize_local_config_compat, secrets};
 use crate::{
     archive::ArchiveConfig,
     db::device::VarMap,
@@ -26,11 +26,14 @@ pub struct Settings {
     pub launch: LaunchConfig,
     /// in secs
     pub auto_sync_interval: u32,
+    pub auto_sync: AutoSyncConfig,
     /// IO timeout for remote sync operations (upload/download), in seconds.
     pub sync_io_timeout_secs: u32,
     /// Non-IO timeout for remote sync operations (connection/listing), in
     /// seconds.
     pub sync_non_io_timeout_secs: u32,
+    /// Number of daily remote config snapshots to keep, 0 keeps all.
+    pub config_snapshot_retention: u32,
 }
 
 impl Default for Settings {
@@ -41,8 +44,10 @@ impl Default for Settings {
             appearance: Default::default(),
             launch: Default::default(),
             auto_sync_interval: 1200,
+            auto_sync: Default::default(),
             sync_io_timeout_secs: DEFAULT_IO_TIMEOUT.as_secs() as u32,
             sync_non_io_timeout_secs: DEFAULT_NON_IO_TIMEOUT.as_secs() as u32,
+            config_snapshot_retention: 30,
         }
     }
 }
@@ -69,6 +74,22 @@ pub struct StorageConfig {
     pub local: LocalConfig, // Local 配置 (路径)
     pub webdav: WebDavConfig,      // WebDAV 配置
     pub s3: S3Config,              // S3 配置
+    pub encryption: EncryptionConfig,
+    pub layout: RemoteLayout,
+}
+
+/// How the config is stored on the remote. The remote is moved to this
+/// layout on the next upload.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub enum RemoteLayout {
+    /// A single `config.toml`, as read by older versions.
+    #[default]
+    Single,
+    /// Every game in its own object, see [`crate::sync::layout`]. Opt-in,
+    /// since older versions do not see the config in this layout.
+    Split,
 }
 
 impl StorageConfig {
@@ -118,6 +139,40 @@ impl StorageConfig {
     }
 }
 
+/// Client-side encryption of archives and config uploaded to remote.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct EncryptionConfig {
+    /// Encrypt archives and config before uploading. Encrypted objects are
+    /// always decrypted on download, regardless of this switch.
+    pub enabled: bool,
+    pub passphrase: Option<String>,
+}
+
+impl EncryptionConfig {
+    /// Passphrase to encrypt uploads with, `None` if encryption is disabled.
+    pub fn upload_passphrase(&self) -> Result<Option<String>> {
+        if !self.enabled {
+            return Ok(None);
+        }
+        self.download_passphrase().map(Some)
+    }
+
+    /// Passphrase to decrypt encrypted downloads with.
+    pub fn download_passphrase(&self) -> Result<String> {
+        let passphrase = match self.passphrase.as_deref() {
+            Some(passphrase) => secrets::resolve(passphrase)?,
+            None => String::new(),
+        };
+        if passphrase.is_empty() {
+            return Err(Error::Crypto("passphrase not set".into()));
+        }
+        Ok(passphrase)
+    }
+}
+
 #[derive(Debug, Default, Serialize, Deserialize, Clone, TS)]
 #[ts(export)]
 #[serde(rename_all = "camelCase")]
@@ -200,6 +255,34 @@ impl Default for LaunchConfig {
     }
 }
 
+/// When the config is synced with remote in the background, besides every
+/// [`Settings::auto_sync_interval`].
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct AutoSyncConfig {
+    /// Apply the remote config on startup, if it is newer.
+    pub on_startup: bool,
+    /// Upload the config when the window is closed to the tray.
+    pub on_minimize: bool,
+    /// Upload the config after a game exits.
+    pub on_game_exit: bool,
+    /// Upload the config when quitting from the tray.
+    pub on_quit: bool,
+}
+
+impl Default for AutoSyncConfig {
+    fn default() -> Self {
+        Self {
+            on_startup: true,
+            on_minimize: true,
+            on_game_exit: false,
+            on_quit: true,
+        }
+    }
+}
+
 #[derive(Debug, Default, Serialize, Deserialize, Clone, TS)]
 #[ts(export)]
 #[serde(rename_all = "camelCase")]
@@ -48,12 +48,24 @@ pub enum Error {
     #[error("Broken config content: {0}")]
     BrokenConfig(#[from] toml::de::Error),
 
+    #[error("Serialize config error: {0}")]
+    SerializeConfig(#[from] toml::ser::Error),
+
+    #[error("Encryption error: {0}")]
+    Crypto(String),
+
     #[error("Storage provider not set")]
     ProviderNotSet,
 
     #[error("Invalid path")]
     InvalidPath,
 
+    #[error("Checksum mismatch: {0}")]
+    ChecksumMismatch(String),
+
+    #[error("Invalid glob pattern: {0}")]
+    Glob(#[from] globset::Error),
+
     #[error("Game time check failed: {0}")]
     GameTimeCheckFailed(String),
 
@@ -72,6 +84,27 @@ pub enum Error {
 
     #[error("PE parse error: {0}")]
     PeParse(#[from] goblin::error::Error),
+
+    #[error("Save conflict: {0}")]
+    SaveConflict(Box<crate::sync::history::SaveConflict>),
+
+    #[error("Watch error: {0}")]
+    Watch(#[from] notify::Error),
+
+    #[error("Remote config changed while syncing")]
+    RemoteConfigChanged,
+
+    #[error("Remote config is being synced by {0}")]
+    RemoteConfigLocked(String),
+
+    #[error("Local config changed since it was read")]
+    ConfigOutdated,
+
+    #[error("Secret '{0}' is missing from the secrets store")]
+    MissingSecret(String),
+
+    #[error("Keyring error: {0}")]
+    Keyring(#[from] keyring::Error),
 }
 
 impl Clone for Error {
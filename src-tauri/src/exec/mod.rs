@@ -14,9 +14,10 @@ use tokio::sync::oneshot;
 use ts_rs::TS;
 
 use crate::{
-    db::CONFIG,
+    db::{CONFIG, edit::save_owned_write},
     error::{Error, Result},
     plugin::{LaunchCtx, PluginConfig, Transaction, enabled_plugin_contexts, instance_config},
+    sync::scheduler::{self, SyncTrigger},
 };
 
 #[cfg(all(unix, not(target_os = "linux")))]
@@ -326,11 +327,13 @@ pub async fn launch_game_with_plugins(app: AppHandle, game_id: u32) -> Result<()
     }
 
     GAME_LOOP_HANDLES.remove(&game_id);
+    scheduler::spawn_sync(&launch.app, SyncTrigger::GameExit);
     Ok(())
 }
 
 fn update_game_time(app: &tauri::AppHandle, game_id: u32, dur: chrono::TimeDelta) -> Result<()> {
     let mut lock = CONFIG.lock();
+    let from = lock.revision.clone();
     let game = lock.get_game_by_id_mut(game_id)?;
     game.use_time += dur;
     game.last_played_time = Some(chrono::Utc::now());
@@ -339,7 +342,7 @@ fn update_game_time(app: &tauri::AppHandle, game_id: u32, dur: chrono::TimeDelta
         game_id,
         game.use_time
     );
-    lock.save_and_emit(app)
+    save_owned_write(&mut lock, app, from)
 }
 
 #[cfg(test)]
@@ -10,8 +10,7 @@ pub mod sync;
 pub mod utils;
 
 use bindings::*;
-use log::{error, info, warn};
-use sync::UploadConfigStatus;
+use log::info;
 use tauri::{
     Manager, generate_context,
     menu::{Menu, MenuItem},
@@ -23,6 +22,7 @@ use tauri_plugin_window_state::{AppHandleExt, StateFlags, WindowExt};
 use crate::{
     db::CONFIG_DIR,
     logging::{LOG_HANDLE, init_logger},
+    sync::scheduler::{self, SyncTrigger},
 };
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -41,6 +41,17 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             get_config,
             save_config,
+            add_game,
+            update_game,
+            delete_game,
+            reorder_games,
+            set_game_plugins,
+            update_settings,
+            update_plugin_metadatas,
+            restore_previous_config,
+            update_device,
+            secrets_encrypted,
+            set_secrets_encrypted,
             device_id,
             resolve_var,
             log,
@@ -48,19 +59,45 @@ pub fn run() {
             delete_local_archive,
             delete_local_archive_all,
             rename_local_archive,
+            set_local_archive_note,
+            set_local_archive_pinned,
             archive,
             extract,
+            extract_files,
+            extract_files_to,
+            list_archive_entries,
+            read_archive_file,
+            diff_archives,
+            diff_archive_with_saves,
             prepare_image,
             list_archive,
             upload_archive,
             delete_archive,
             delete_archive_all,
             pull_archive,
+            check_restore_conflict,
+            resolve_save_conflict,
+            verify_archives,
+            sync_status,
+            reconcile,
+            last_config_sync,
+            set_remote_archive_note,
+            set_remote_archive_pinned,
+            preview_retention,
             rename_remote_archive,
             clean_current_operator,
             upload_config,
             get_remote_config,
             apply_remote_config,
+            list_config_snapshots,
+            get_config_snapshot,
+            diff_config_snapshot,
+            restore_config_snapshot,
+            prune_config_snapshots,
+            list_config_backups,
+            diff_config_backup,
+            restore_config_backup,
+            merge_remote_config,
             exec,
             is_game_running,
             running_game_ids,
@@ -115,12 +152,8 @@ pub fn run() {
                 .icon(app.default_window_icon().unwrap().clone())
                 .menu(&menu)
                 .on_menu_event(|app, event| match event.id.as_ref() {
-                    "quit_sync" => {
-                        app.exit(114514);
-                    }
-                    "quit_nosync" => {
-                        app.exit(0);
-                    }
+                    "quit_sync" => scheduler::quit(app, true),
+                    "quit_nosync" => scheduler::quit(app, false),
                     "open_config" => _ = opener::open(CONFIG_DIR.as_os_str()),
                     "open_save" => {
                         _ = opener::open(
@@ -153,6 +186,8 @@ pub fn run() {
                     _ => {}
                 })
                 .build(app)?;
+
+            scheduler::start(app.handle());
             Ok(())
         })
         .build(generate_context!())
@@ -178,99 +213,13 @@ pub fn run() {
                     _ = std::fs::File::create(seen_path);
                 }
 
-                // upload config
-                let app = app.clone();
-                tauri::async_runtime::spawn(async move {
-                    info!("[minimize] uploading config...");
-                    let app_clone = app.clone();
-                    let notify = move |title: &str, body: &str| {
-                        _ = app_clone
-                            .notification()
-                            .builder()
-                            .title(title)
-                            .body(body)
-                            .show();
-                    };
-                    match bindings::upload_config(app, true).await {
-                        Ok(UploadConfigStatus::Uploaded) => {
-                            info!("[minimize] upload config success");
-                            notify("\u{2705} Synced", "Configuration uploaded successfully");
-                        }
-                        Ok(UploadConfigStatus::LocalClean) => {
-                            warn!("[minimize] local clean, skip upload");
-                            notify("\u{23ed} Sync Skipped", "Local configuration is up to date");
-                        }
-                        Ok(UploadConfigStatus::Conflict) => {
-                            warn!("[minimize] conflict detected");
-                            notify(
-                                "\u{26a0}\u{fe0f} Sync Conflict",
-                                "Remote configuration is newer \u{2014} please pull first",
-                            );
-                        }
-                        Err(e) => {
-                            error!("[minimize] failed to upload config: {e}");
-                            notify(
-                                "\u{274c} Sync Failed",
-                                &format!("Failed to upload configuration: {e}"),
-                            );
-                        }
-                    }
-                });
+                scheduler::spawn_sync(app, SyncTrigger::Minimize);
             }
-            tauri::RunEvent::ExitRequested { api, code, .. } => {
+            tauri::RunEvent::ExitRequested { code, .. } => {
                 _ = app.save_window_state(StateFlags::all());
-                if code == Some(114514) {
-                    if let Some(window) = app.get_webview_window("main") {
-                        let _ = window.minimize();
-                    }
-                    api.prevent_exit();
-                    info!("[exit] uploading config...");
-                    let res = tauri::async_runtime::block_on(async move {
-                        bindings::upload_config(app.clone(), true).await
-                    });
-                    let notify = |title: &str, body: &str| {
-                        _ = app.notification().builder().title(title).body(body).show();
-                    };
-                    match res {
-                        Ok(UploadConfigStatus::Uploaded) => {
-                            info!("[exit] upload config success");
-                            notify("\u{2705} Synced", "Configuration uploaded successfully");
-                            before_exit();
-                            std::thread::sleep(std::time::Duration::from_secs(1));
-                            std::process::exit(0);
-                        }
-                        Ok(UploadConfigStatus::LocalClean) => {
-                            warn!("[exit] local clean, skip upload");
-                            notify("\u{23ed} Sync Skipped", "Local configuration is up to date");
-                            before_exit();
-                            std::thread::sleep(std::time::Duration::from_secs(1));
-                            std::process::exit(0);
-                        }
-                        Ok(UploadConfigStatus::Conflict) => {
-                            warn!("[exit] conflict detected");
-                            notify(
-                                "\u{26a0}\u{fe0f} Sync Conflict",
-                                "Remote configuration is newer \u{2014} please pull first",
-                            );
-                            before_exit();
-                            std::thread::sleep(std::time::Duration::from_secs(1));
-                            std::process::exit(0);
-                        }
-                        Err(e) => {
-                            error!("[exit] failed to upload config: {e}");
-                            notify(
-                                "\u{274c} Sync Failed",
-                                &format!("Failed to upload configuration: {e}"),
-                            );
-                            before_exit();
-                            std::thread::sleep(std::time::Duration::from_secs(1));
-                            std::process::exit(1);
-                        }
-                    }
-                } else {
-                    info!("exit code: {:?}", code);
-                }
+                info!("exit code: {:?}", code);
             }
+            tauri::RunEvent::Exit => before_exit(),
             _ => (),
         });
 }
@@ -0,0 +1,752 @@
+//! Auto-upload plugin: automatically archive and upload saves when a game
+//! exits.
+//!
+//! This module is self-contained — it defines all config types **and** the
+//! handler in one place. The snapshots taken while the game is running live
+//! in [`session`], the pull before it starts in [`pull`].
+
+mod pull;
+mod session;
+
+use std::{collections::HashMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+use tauri::Manager as _;
+use ts_rs::TS;
+
+use self::{
+    pull::{LocalSaves, PullDecision},
+    session::{Session, SessionTarget},
+};
+use super::{PluginConfig, PluginContext, SaveUploadDispatcher, Transaction};
+use crate::{
+    archive::{
+        ArchiveInfo, ArchiveTrigger, RetentionPolicy, fingerprint_saves, read_meta, sort_by_age,
+    },
+    db::SavePathConfig,
+    error::{Error, Result},
+    sync::{
+        MyOperation,
+        history::{self, SaveConflict, SyncRecord},
+    },
+    utils::{
+        list_dir_all,
+        toast::{ToastVariant, dismiss_toast, emit_loading_toast, emit_toast},
+    },
+};
+
+/// Plugin identifier used in the registry and config.
+pub const PLUGIN_ID: &str = "autoUpload";
+
+/// i18n hint keys used in toast messages.
+const HINT_ARCHIVE_FAILED: &str = "<hint.archiveFailed>";
+const HINT_PULL_FAILED: &str = "<hint.pullFailed>";
+const HINT_PULL_SUCCESS: &str = "<hint.pullSuccess>";
+const HINT_PULLING: &str = "<hint.pulling>";
+const HINT_SAVE_CONFLICT: &str = "<hint.saveConflict>";
+const HINT_UPLOAD_FAILED: &str = "<hint.uploadFailed>";
+const HINT_UPLOAD_SKIPPED: &str = "<hint.uploadSkipped>";
+const HINT_UPLOAD_SUCCESS: &str = "<hint.uploadSuccess>";
+const HINT_UPLOADING: &str = "<hint.uploading>";
+
+// ── Config types ──
+
+/// Where the retention (末位淘汰) policy is enforced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+pub enum RetentionScope {
+    Local,
+    Remote,
+    #[default]
+    Both,
+}
+
+/// Per-game config for the AutoUpload plugin.
+///
+/// An archive is kept if any of the rules below keeps it, and pinned archives
+/// are always kept. Nothing is pruned if all the numbers are `0`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AutoUploadGameConfig {
+    /// Number of the newest archives to keep for this game; the older ones
+    /// are evicted (末位淘汰) unless kept by the rules below.
+    pub max_kept: u32,
+    /// Also keep the newest archive of each of the last `keep_daily` days
+    /// that have archives.
+    pub keep_daily: u32,
+    /// Also keep the newest archive of each of the last `keep_weekly` weeks
+    /// that have archives.
+    pub keep_weekly: u32,
+    /// Also keep the newest archive of each of the last `keep_monthly` months
+    /// that have archives.
+    pub keep_monthly: u32,
+    /// Where the retention policy is enforced.
+    pub retention_scope: RetentionScope,
+    /// Tell whether the saves changed since the last archive by their content
+    /// instead of their mtime. Slower, but not fooled by games that rewrite
+    /// their saves without changing them.
+    pub hash_contents: bool,
+    /// Before the game starts, pull and restore the newest remote archive if
+    /// it is newer than the local saves. If the local saves changed since the
+    /// last sync too, the game is not started.
+    pub pull_before_launch: bool,
+    /// Take local snapshots of the saves while the game is running, once the
+    /// writes to them go quiet.
+    pub session_snapshots: bool,
+    /// Seconds without writes to the saves before a snapshot is taken.
+    pub session_quiet_secs: u32,
+    /// Also take a snapshot every `session_interval_mins` minutes if the saves
+    /// changed, `0` for never.
+    pub session_interval_mins: u32,
+    /// Most snapshots to take while the game is running once, `0` for no
+    /// limit.
+    pub session_max_snapshots: u32,
+    /// Number of the newest in-session snapshots to keep locally. They are
+    /// pruned apart from the archives taken on exit; `0` keeps all of them.
+    pub session_max_kept: u32,
+}
+
+impl Default for AutoUploadGameConfig {
+    fn default() -> Self {
+        Self {
+            max_kept: 20,
+            keep_daily: 0,
+            keep_weekly: 0,
+            keep_monthly: 0,
+            retention_scope: RetentionScope::Both,
+            hash_contents: false,
+            pull_before_launch: false,
+            session_snapshots: false,
+            session_quiet_secs: 30,
+            session_interval_mins: 0,
+            session_max_snapshots: 10,
+            session_max_kept: 10,
+        }
+    }
+}
+
+impl AutoUploadGameConfig {
+    #[inline]
+    pub fn policy(&self) -> RetentionPolicy {
+        RetentionPolicy {
+            keep_last: self.max_kept,
+            keep_daily: self.keep_daily,
+            keep_weekly: self.keep_weekly,
+            keep_monthly: self.keep_monthly,
+        }
+    }
+
+    #[inline]
+    fn prunes_local(&self) -> bool {
+        matches!(
+            self.retention_scope,
+            RetentionScope::Local | RetentionScope::Both
+        )
+    }
+
+    #[inline]
+    fn prunes_remote(&self) -> bool {
+        matches!(
+            self.retention_scope,
+            RetentionScope::Remote | RetentionScope::Both
+        )
+    }
+}
+
+/// Archives the retention policy of a game would delete, from the oldest to
+/// the newest.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionPreview {
+    pub local: Vec<String>,
+    pub remote: Vec<String>,
+}
+
+/// Global metadata for the AutoUpload plugin (stored in `PluginMetadatas`).
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AutoUploadPluginMeta {
+    pub enabled: bool,
+    pub auto_add: bool,
+    /// Default per-game config applied when the plugin is added to a new game.
+    pub config_defaults: AutoUploadGameConfig,
+}
+
+impl Default for AutoUploadPluginMeta {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            auto_add: false,
+            config_defaults: AutoUploadGameConfig::default(),
+        }
+    }
+}
+
+// ── Handler ───────
+
+pub struct AutoUploadPlugin {
+    /// In-session snapshots of the running games, by game id.
+    sessions: parking_lot::Mutex<HashMap<u32, Session>>,
+}
+
+impl AutoUploadPlugin {
+    pub fn new() -> Self {
+        Self {
+            sessions: Default::default(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl super::PluginHandler for AutoUploadPlugin {
+    async fn before_game_start(&self, ctx: PluginContext) -> Result<()> {
+        let PluginConfig::AutoUpload(config) = &*ctx.config else {
+            return Ok(());
+        };
+        if !config.pull_before_launch {
+            return Ok(());
+        }
+
+        let game = {
+            let lock = crate::db::CONFIG.lock();
+            lock.get_game_by_id(ctx.launch.game_id)?.clone()
+        };
+        if game.save_paths.is_empty() {
+            return Ok(());
+        }
+        let (archive_conf, device_name, storage, varmap, io_timeout, non_io_timeout) = {
+            let lock = crate::db::CONFIG.lock();
+            (
+                lock.settings.archive.clone(),
+                lock.device_name(),
+                lock.settings.storage.clone(),
+                lock.varmap().clone(),
+                std::time::Duration::from_secs(lock.settings.sync_io_timeout_secs.max(1) as u64),
+                std::time::Duration::from_secs(lock.settings.sync_non_io_timeout_secs.max(1) as u64),
+            )
+        };
+        if storage.is_not_set() {
+            log::warn!("AutoUploadPlugin: storage not configured, skipping pull");
+            return Ok(());
+        }
+        let backup_dir = ctx.launch.app.path().app_local_data_dir()?.join("backup");
+        let game_backup_dir = backup_dir.join(ctx.launch.game_id.to_string());
+
+        // the game can still be played offline, until there is something to
+        // pull
+        let op = match storage.build_operator_with_timeouts(
+            &ctx.launch.app,
+            &varmap,
+            io_timeout,
+            non_io_timeout,
+        ) {
+            Ok(op) => op,
+            Err(e) => {
+                log::warn!("AutoUpload: build operator failed, skipping pull: {e}");
+                return Ok(());
+            }
+        };
+        let mut archives = match op.list_archive(ctx.launch.game_id).await {
+            Ok(a) => a,
+            Err(e) => {
+                log::warn!(
+                    "AutoUpload: list remote archives failed (game {}), skipping pull: {e}",
+                    ctx.launch.game_id
+                );
+                return Ok(());
+            }
+        };
+        sort_by_age(&mut archives);
+        let Some(newest) = archives.pop() else {
+            return Ok(());
+        };
+        let Some(meta) = &newest.meta else {
+            return Ok(());
+        };
+
+        match LocalSaves::read(&game_backup_dir, &game.save_paths)?.decide(&newest) {
+            PullDecision::UpToDate => return Ok(()),
+            PullDecision::Conflict => {
+                let conflict = SaveConflict {
+                    game_id: ctx.launch.game_id,
+                    local: SyncRecord::of_saves(&device_name, &game_backup_dir, &game.save_paths)?,
+                    remote: SyncRecord::of_creator(&newest.name, meta),
+                };
+                let msg = format!("{HINT_SAVE_CONFLICT}{}: {conflict}", game.name);
+                log::warn!("AutoUpload: {msg}");
+                emit_toast(&ctx.launch.app, ToastVariant::Error, msg);
+                return Err(Error::SaveConflict(Box::new(conflict)));
+            }
+            PullDecision::Pull => {}
+        }
+
+        log::info!(
+            "AutoUpload: pulling {} for game {} before launch",
+            newest.name,
+            ctx.launch.game_id
+        );
+        let loading_toast_id = format!("auto_upload_pull_{}", ctx.launch.game_id);
+        emit_loading_toast(
+            &ctx.launch.app,
+            format!("{HINT_PULLING}{}", game.name),
+            &loading_toast_id,
+        );
+        let res = match op
+            .pull_archive(ctx.launch.game_id, &newest.name, &backup_dir)
+            .await
+        {
+            Ok(()) => crate::archive::restore_impl(
+                &device_name,
+                &archive_conf,
+                game_backup_dir.clone(),
+                newest.name.clone(),
+                game.save_paths,
+                None,
+                game.use_time,
+            ),
+            Err(e) => Err(e),
+        };
+        if res.is_ok() {
+            let res = match SyncRecord::of_local(&device_name, &game_backup_dir.join(&newest.name))
+            {
+                Ok(record) => history::record_restore(&*op, ctx.launch.game_id, record).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = res {
+                log::warn!("AutoUpload: record restore in save history failed: {e}");
+            }
+        }
+        dismiss_toast(&ctx.launch.app, &loading_toast_id);
+        if let Err(e) = res {
+            let msg = format!("{HINT_PULL_FAILED}{}: {e}", game.name);
+            log::error!("AutoUpload: {msg}");
+            emit_toast(&ctx.launch.app, ToastVariant::Error, msg);
+            return Err(e);
+        }
+        let msg = format!("{HINT_PULL_SUCCESS}{}: {}", game.name, newest.name);
+        log::info!("AutoUpload: {msg}");
+        emit_toast(&ctx.launch.app, ToastVariant::Success, msg);
+        Ok(())
+    }
+
+    async fn after_game_start(&self, ctx: PluginContext) -> Result<()> {
+        let PluginConfig::AutoUpload(config) = &*ctx.config else {
+            return Ok(());
+        };
+        if !config.session_snapshots {
+            return Ok(());
+        }
+
+        let game = {
+            let lock = crate::db::CONFIG.lock();
+            lock.get_game_by_id(ctx.launch.game_id)?.clone()
+        };
+        if game.save_paths.is_empty() {
+            return Ok(());
+        }
+        let (archive_conf, device_name) = {
+            let lock = crate::db::CONFIG.lock();
+            (lock.settings.archive.clone(), lock.device_name())
+        };
+        let data_dir = ctx.launch.app.path().app_local_data_dir()?;
+        let target = SessionTarget {
+            game_id: ctx.launch.game_id,
+            device_name,
+            archive_conf,
+            game_backup_dir: data_dir.join("backup").join(ctx.launch.game_id.to_string()),
+            save_paths: game.save_paths,
+            use_time: game.use_time,
+        };
+
+        let Some(session) = Session::start(target, *config)? else {
+            return Ok(());
+        };
+        let previous = self.sessions.lock().insert(ctx.launch.game_id, session);
+        if let Some(previous) = previous {
+            previous.stop().await;
+        }
+        Ok(())
+    }
+
+    async fn after_game_exit(&self, ctx: PluginContext) -> Result<()> {
+        let PluginConfig::AutoUpload(config) = &*ctx.config else {
+            return Ok(());
+        };
+        let config = *config;
+
+        // the exit archive is taken after the last in-session snapshot
+        let session = self.sessions.lock().remove(&ctx.launch.game_id);
+        if let Some(session) = session {
+            session.stop().await;
+        }
+
+        let game = {
+            let lock = crate::db::CONFIG.lock();
+            lock.get_game_by_id(ctx.launch.game_id)?.clone()
+        };
+        let game_name = game.name.clone();
+
+        if game.save_paths.is_empty() {
+            log::info!(
+                "AutoUploadPlugin: game {} has no save paths, skipping",
+                ctx.launch.game_id
+            );
+            return Ok(());
+        }
+
+        let data_dir = ctx.launch.app.path().app_local_data_dir()?;
+        let game_backup_dir = data_dir.join("backup").join(ctx.launch.game_id.to_string());
+
+        let (archive_conf, device_name, storage, varmap, io_timeout, non_io_timeout) = {
+            let lock = crate::db::CONFIG.lock();
+            (
+                lock.settings.archive.clone(),
+                lock.device_name(),
+                lock.settings.storage.clone(),
+                lock.varmap().clone(),
+                std::time::Duration::from_secs(lock.settings.sync_io_timeout_secs.max(1) as u64),
+                std::time::Duration::from_secs(lock.settings.sync_non_io_timeout_secs.max(1) as u64),
+            )
+        };
+
+        let unchanged = unchanged_since_latest(
+            &game_backup_dir,
+            &game.save_paths,
+            config.hash_contents,
+            |a| !is_in_session(a),
+        )
+        .unwrap_or_else(|e| {
+            log::warn!("AutoUploadPlugin: fingerprint saves failed: {e}");
+            None
+        });
+        let archive_filename = if let Some(latest) = unchanged {
+            log::info!(
+                "AutoUploadPlugin: saves of game {} unchanged since {latest}, skip archiving",
+                ctx.launch.game_id
+            );
+            latest
+        } else {
+            log::info!(
+                "AutoUploadPlugin: archiving saves for game {}",
+                ctx.launch.game_id
+            );
+            match crate::archive::archive_impl(
+                &device_name,
+                &archive_conf,
+                game_backup_dir.clone(),
+                game.save_paths,
+                ArchiveTrigger::AutoUpload,
+                game.use_time,
+            ) {
+                Ok(filename) => filename,
+                Err(e) => {
+                    let msg = format!("{HINT_ARCHIVE_FAILED}{game_name}: {e}");
+                    log::error!("AutoUpload: {msg}");
+                    emit_toast(&ctx.launch.app, ToastVariant::Error, msg);
+                    return Err(e);
+                }
+            }
+        };
+
+        if storage.is_not_set() {
+            log::warn!("AutoUploadPlugin: storage not configured, skipping upload");
+            return Ok(());
+        }
+
+        let loading_toast_id = format!("auto_upload_{}", ctx.launch.game_id);
+        emit_loading_toast(
+            &ctx.launch.app,
+            format!("{HINT_UPLOADING}{game_name}"),
+            &loading_toast_id,
+        );
+
+        let op = match storage.build_operator_with_timeouts(
+            &ctx.launch.app,
+            &varmap,
+            io_timeout,
+            non_io_timeout,
+        ) {
+            Ok(op) => op,
+            Err(e) => {
+                dismiss_toast(&ctx.launch.app, &loading_toast_id);
+                let msg = format!("{HINT_UPLOAD_FAILED}{game_name}: {e}");
+                log::error!("AutoUpload: {msg}");
+                emit_toast(&ctx.launch.app, ToastVariant::Error, msg);
+                return Err(e);
+            }
+        };
+
+        if let Some(duplicate) = remote_duplicate(
+            &*op,
+            ctx.launch.game_id,
+            &game_backup_dir.join(&archive_filename),
+        )
+        .await
+        {
+            log::info!(
+                "AutoUpload: remote already holds {duplicate} with the same saves as \
+                 {archive_filename}, skip upload"
+            );
+            dismiss_toast(&ctx.launch.app, &loading_toast_id);
+            emit_toast(
+                &ctx.launch.app,
+                ToastVariant::Success,
+                format!("{HINT_UPLOAD_SKIPPED}{game_name}"),
+            );
+            return Ok(());
+        }
+
+        let record = SyncRecord::of_local(&device_name, &game_backup_dir.join(&archive_filename))?;
+        match history::check_upload(&*op, ctx.launch.game_id, &record).await {
+            Ok(None) => {}
+            Ok(Some(conflict)) => {
+                dismiss_toast(&ctx.launch.app, &loading_toast_id);
+                let msg = format!("{HINT_SAVE_CONFLICT}{game_name}: {conflict}");
+                log::warn!("AutoUpload: {msg}");
+                emit_toast(&ctx.launch.app, ToastVariant::Error, msg);
+                return Err(Error::SaveConflict(Box::new(conflict)));
+            }
+            Err(e) => {
+                dismiss_toast(&ctx.launch.app, &loading_toast_id);
+                let msg = format!("{HINT_UPLOAD_FAILED}{game_name}: {e}");
+                log::error!("AutoUpload: {msg}");
+                emit_toast(&ctx.launch.app, ToastVariant::Error, msg);
+                return Err(e);
+            }
+        }
+
+        let tx = Transaction::new();
+        let save_dispatcher =
+            SaveUploadDispatcher::new(&ctx.launch.app, ctx.launch.game_id, tx.clone())?;
+
+        if let Err(e) = save_dispatcher.dispatch_before(&archive_filename).await {
+            tx.rollback();
+            dismiss_toast(&ctx.launch.app, &loading_toast_id);
+            let msg = format!("{HINT_UPLOAD_FAILED}{game_name}: {e}");
+            log::error!("AutoUpload: {msg}");
+            emit_toast(&ctx.launch.app, ToastVariant::Error, msg);
+            return Err(e);
+        }
+
+        if let Err(e) = op
+            .upload_archive(
+                ctx.launch.game_id,
+                &archive_filename,
+                &data_dir.join("backup"),
+            )
+            .await
+        {
+            tx.rollback();
+            dismiss_toast(&ctx.launch.app, &loading_toast_id);
+            let msg = format!("{HINT_UPLOAD_FAILED}{game_name}: {e}");
+            log::error!("AutoUpload: {msg}");
+            emit_toast(&ctx.launch.app, ToastVariant::Error, msg);
+            return Err(e);
+        }
+
+        save_dispatcher.dispatch_after(&archive_filename).await;
+        tx.execute_after_exit();
+        if let Err(e) = history::record_upload(&*op, ctx.launch.game_id, record).await {
+            log::warn!("AutoUpload: record upload in save history failed: {e}");
+        }
+
+        // Retention (末位淘汰)
+        let policy = config.policy();
+        if !policy.is_unlimited() {
+            if config.prunes_remote() {
+                prune_remote(&*op, ctx.launch.game_id, &policy).await;
+            }
+            if config.prunes_local() {
+                let local_game_dir = data_dir.join("backup").join(ctx.launch.game_id.to_string());
+                prune_local(&local_game_dir, &policy, false);
+            }
+        }
+
+        dismiss_toast(&ctx.launch.app, &loading_toast_id);
+        let msg = format!("{HINT_UPLOAD_SUCCESS}{game_name}");
+        log::info!("AutoUpload: {msg}");
+        emit_toast(&ctx.launch.app, ToastVariant::Success, msg);
+        Ok(())
+    }
+}
+
+/// Whether an archive is a snapshot taken while the game was running. These
+/// are pruned apart from the other archives.
+fn is_in_session(archive: &ArchiveInfo) -> bool {
+    archive
+        .meta
+        .as_ref()
+        .is_some_and(|m| m.trigger == ArchiveTrigger::InSession)
+}
+
+// ── Change detection helpers ──
+
+/// The newest local archive of a game that `filter` accepts, if the saves did
+/// not change since it was created.
+fn unchanged_since_latest(
+    local_game_dir: &Path,
+    save_paths: &[SavePathConfig],
+    hash_contents: bool,
+    filter: impl Fn(&ArchiveInfo) -> bool,
+) -> Result<Option<String>> {
+    if !local_game_dir.exists() {
+        return Ok(None);
+    }
+    let mut archives = list_dir_all(local_game_dir)?;
+    archives.retain(filter);
+    sort_by_age(&mut archives);
+    let Some(ArchiveInfo {
+        name,
+        meta: Some(meta),
+        ..
+    }) = archives.pop()
+    else {
+        return Ok(None);
+    };
+    let recorded = if hash_contents {
+        meta.content_hash
+    } else {
+        meta.fingerprint
+    };
+    let Some(recorded) = recorded else {
+        return Ok(None);
+    };
+    Ok((fingerprint_saves(save_paths, hash_contents)? == recorded).then_some(name))
+}
+
+/// A remote archive with the same saves as the local archive at
+/// `archive_path`, if any. Best-effort: errors are logged and the archive is
+/// uploaded anyway.
+async fn remote_duplicate(
+    op: &(dyn MyOperation + Send + Sync),
+    game_id: u32,
+    archive_path: &Path,
+) -> Option<String> {
+    let content_hash = match read_meta(archive_path) {
+        Ok(meta) => meta?.content_hash?,
+        Err(e) => {
+            log::warn!(
+                "AutoUpload: read metadata of {} failed: {e}",
+                archive_path.display()
+            );
+            return None;
+        }
+    };
+    match op.list_archive(game_id).await {
+        Ok(archives) => archives
+            .into_iter()
+            .find(|a| {
+                a.meta.as_ref().and_then(|m| m.content_hash.as_deref())
+                    == Some(content_hash.as_str())
+            })
+            .map(|a| a.name),
+        Err(e) => {
+            log::warn!("AutoUpload: list remote archives failed (game {game_id}): {e}");
+            None
+        }
+    }
+}
+
+// ── Retention (末位淘汰) helpers ──
+
+/// Preview what the retention policy in `config` would delete for a game.
+/// Remote archives are only listed if `op` is given.
+pub async fn preview_retention(
+    config: &AutoUploadGameConfig,
+    local_game_dir: &Path,
+    op: Option<&(dyn MyOperation + Send + Sync)>,
+    game_id: u32,
+) -> Result<RetentionPreview> {
+    let policy = config.policy();
+    let evicted_names = |mut archives: Vec<ArchiveInfo>| -> Vec<String> {
+        archives.retain(|a| !is_in_session(a));
+        policy
+            .evicted(&archives)
+            .into_iter()
+            .map(|a| a.name.clone())
+            .collect()
+    };
+    let mut preview = RetentionPreview::default();
+    if config.prunes_local() && local_game_dir.exists() {
+        preview.local = evicted_names(list_dir_all(local_game_dir)?);
+    }
+    if let Some(op) = op
+        && config.prunes_remote()
+    {
+        preview.remote = evicted_names(op.list_archive(game_id).await?);
+    }
+    Ok(preview)
+}
+
+/// Delete the remote archives `policy` evicts. In-session snapshots are left
+/// alone.
+///
+/// Best-effort: errors are logged and never abort the (already successful)
+/// upload.
+async fn prune_remote(
+    op: &(dyn MyOperation + Send + Sync),
+    game_id: u32,
+    policy: &RetentionPolicy,
+) {
+    let mut archives = match op.list_archive(game_id).await {
+        Ok(a) => a,
+        Err(e) => {
+            log::warn!("AutoUpload: list remote archives failed (game {game_id}): {e}");
+            return;
+        }
+    };
+    archives.retain(|a| !is_in_session(a));
+    let evicted = policy.evicted(&archives);
+    if evicted.is_empty() {
+        return;
+    }
+    for name in evicted.iter().map(|a| &a.name) {
+        if let Err(e) = op.delete_archive(game_id, name.as_str()).await {
+            log::warn!("AutoUpload: delete remote archive {name} failed (game {game_id}): {e}");
+        }
+    }
+    log::info!(
+        "AutoUpload: pruned {} remote archive(s) for game {game_id}",
+        evicted.len()
+    );
+}
+
+/// Delete the local archives `policy` evicts, among the in-session snapshots
+/// if `in_session` and among the other archives otherwise.
+fn prune_local(local_game_dir: &Path, policy: &RetentionPolicy, in_session: bool) {
+    let mut archives = match list_dir_all(local_game_dir) {
+        Ok(a) => a,
+        Err(e) => {
+            log::warn!(
+                "AutoUpload: list local archives failed ({}): {e}",
+                local_game_dir.display()
+            );
+            return;
+        }
+    };
+    archives.retain(|a| is_in_session(a) == in_session);
+    let evicted = policy.evicted(&archives);
+    if evicted.is_empty() {
+        return;
+    }
+    for name in evicted.iter().map(|a| &a.name) {
+        let path = local_game_dir.join(name);
+        if let Err(e) = std::fs::remove_file(&path)
+            .and_then(|()| crate::archive::remove_checksum(&path))
+            .and_then(|()| crate::archive::remove_meta(&path))
+        {
+            log::warn!(
+                "AutoUpload: delete local archive {} failed: {e}",
+                path.display()
+            );
+        }
+    }
+    log::info!("AutoUpload: pruned {} local archive(s)", evicted.len());
+    if let Err(e) = crate::archive::gc_chunks(local_game_dir) {
+        log::warn!(
+            "AutoUpload: collect unreferenced chunks failed ({}): {e}",
+            local_game_dir.display()
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! Pull of the newest remote archive before a game starts, so that the saves
+//! of another device are not played over.
+
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    archive::{ArchiveInfo, ArchiveTrigger, fingerprint_saves, saves_modified, sort_by_age},
+    db::SavePathConfig,
+    error::Result,
+    utils::list_dir_all,
+};
+
+/// What to do with the newest remote archive before a game starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PullDecision {
+    /// The local saves are as new as the remote archive, or newer.
+    UpToDate,
+    Pull,
+    /// Both the local saves and the remote archive changed since the last
+    /// sync.
+    Conflict,
+}
+
+/// The local saves of a game, as far as the pull is concerned.
+#[derive(Debug, Clone, Default)]
+pub struct LocalSaves {
+    /// Content hash of the current saves.
+    pub content_hash: String,
+    /// When the saves were last modified, `None` if there are no saves.
+    pub modified: Option<DateTime<Utc>>,
+    /// When the newest local archive was created. Pre-restore archives are
+    /// left out, since they are created when pulling.
+    pub latest_archive: Option<DateTime<Utc>>,
+    /// Whether the saves changed since the last archive that was synced.
+    pub changed_since_sync: bool,
+}
+
+impl LocalSaves {
+    pub fn read(local_game_dir: &Path, save_paths: &[SavePathConfig]) -> Result<Self> {
+        let content_hash = fingerprint_saves(save_paths, true)?;
+        let modified = saves_modified(save_paths)?;
+
+        let mut archives = if local_game_dir.exists() {
+            list_dir_all(local_game_dir)?
+        } else {
+            vec![]
+        };
+        archives.retain(|a| {
+            a.meta
+                .as_ref()
+                .is_some_and(|m| m.trigger != ArchiveTrigger::PreRestore)
+        });
+        sort_by_age(&mut archives);
+        let latest_archive = archives.last().and_then(ArchiveInfo::created_at);
+
+        // snapshots taken while playing were never uploaded
+        let last_sync = archives
+            .iter()
+            .rev()
+            .filter_map(|a| a.meta.as_ref())
+            .find(|m| m.trigger != ArchiveTrigger::InSession);
+        let changed_since_sync = match last_sync {
+            Some(meta) => match &meta.content_hash {
+                Some(hash) => *hash != content_hash,
+                None => modified.is_some_and(|m| m > meta.created_at),
+            },
+            None => modified.is_some(),
+        };
+
+        Ok(Self {
+            content_hash,
+            modified,
+            latest_archive,
+            changed_since_sync,
+        })
+    }
+
+    /// What to do with `remote`, the newest remote archive.
+    pub fn decide(&self, remote: &ArchiveInfo) -> PullDecision {
+        let Some(meta) = &remote.meta else {
+            return PullDecision::UpToDate;
+        };
+        if meta.content_hash.as_ref() == Some(&self.content_hash)
+            || self.modified.is_some_and(|m| m >= meta.created_at)
+            || self.latest_archive.is_some_and(|a| a >= meta.created_at)
+        {
+            PullDecision::UpToDate
+        } else if self.changed_since_sync {
+            PullDecision::Conflict
+        } else {
+            PullDecision::Pull
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone as _};
+
+    use super::*;
+    use crate::archive::{ArchiveAlgo, ArchiveMeta};
+
+    fn remote(created_at: DateTime<Utc>, content_hash: &str) -> ArchiveInfo {
+        ArchiveInfo {
+            name: "remote.tar".to_string(),
+            size: 0,
+            format: None,
+            meta: Some(ArchiveMeta {
+                created_at,
+                device_uid: "other".to_string(),
+                device_name: String::new(),
+                algorithm: ArchiveAlgo::Tar,
+                use_time: Duration::zero(),
+                trigger: ArchiveTrigger::AutoUpload,
+                note: None,
+                pinned: false,
+                fingerprint: None,
+                content_hash: Some(content_hash.to_string()),
+            }),
+        }
+    }
+
+    #[test]
+    fn pulls_only_newer_archives() {
+        let at = |h: u32| Utc.with_ymd_and_hms(2024, 1, 1, h, 0, 0).unwrap();
+        let local = LocalSaves {
+            content_hash: "local".to_string(),
+            modified: Some(at(8)),
+            latest_archive: Some(at(9)),
+            changed_since_sync: false,
+        };
+
+        assert_eq!(local.decide(&remote(at(10), "remote")), PullDecision::Pull);
+        assert_eq!(
+            local.decide(&remote(at(10), "local")),
+            PullDecision::UpToDate
+        );
+        assert_eq!(
+            local.decide(&remote(at(9), "remote")),
+            PullDecision::UpToDate
+        );
+        let mut unknown = remote(at(10), "remote");
+        unknown.meta = None;
+        assert_eq!(local.decide(&unknown), PullDecision::UpToDate);
+
+        let changed = LocalSaves {
+            changed_since_sync: true,
+            ..local
+        };
+        assert_eq!(
+            changed.decide(&remote(at(10), "remote")),
+            PullDecision::Conflict
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Snapshots of the saves while a game is running.
+//!
+//! A session watches the save paths of a running game and takes a local
+//! snapshot once the writes to them go quiet, and optionally at a fixed
+//! interval. Snapshots are archives with the [`ArchiveTrigger::InSession`]
+//! trigger, so that they are pruned apart from the archives taken on exit.
+
+use std::path::{Path, PathBuf};
+
+use notify::{
+    Event, EventKind, RecursiveMode, Watcher as _,
+    event::{AccessKind, AccessMode},
+};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::{self, Duration, Instant, Interval},
+};
+
+use super::{AutoUploadGameConfig, prune_local, unchanged_since_latest};
+use crate::{
+    archive::{ArchiveConfig, ArchiveTrigger, RetentionPolicy, SavePath},
+    db::SavePathConfig,
+    error::Result,
+};
+
+/// What a session snapshots, and where to.
+pub struct SessionTarget {
+    pub game_id: u32,
+    pub device_name: String,
+    pub archive_conf: ArchiveConfig,
+    pub game_backup_dir: PathBuf,
+    pub save_paths: Vec<SavePathConfig>,
+    pub use_time: chrono::Duration,
+}
+
+/// A running session, stopped by [`Session::stop`].
+pub struct Session {
+    stop_tx: oneshot::Sender<()>,
+    task: tauri::async_runtime::JoinHandle<()>,
+}
+
+impl Session {
+    /// Start watching the save paths of `target`. Save paths that do not
+    /// exist yet are not watched; `None` if there is nothing to watch and no
+    /// interval either.
+    pub fn start(target: SessionTarget, config: AutoUploadGameConfig) -> Result<Option<Self>> {
+        let mut watched = vec![];
+        for save_path in &target.save_paths {
+            match SavePath::resolve(save_path) {
+                Ok(save_path) if save_path.path.exists() => watched.push(save_path),
+                Ok(save_path) => log::info!(
+                    "AutoUpload: save path {} does not exist, not watching it",
+                    save_path.path.display()
+                ),
+                Err(e) => log::warn!("AutoUpload: resolve save path failed: {e}"),
+            }
+        }
+
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+                Ok(event) if is_write(&event.kind) => _ = event_tx.send(event.paths),
+                Ok(_) => {}
+                Err(e) => log::warn!("AutoUpload: watch save paths failed: {e}"),
+            })?;
+        watched.retain(|save_path| {
+            // the parent of a file, so that the file can be replaced
+            let (path, mode) = if save_path.path.is_dir() {
+                (save_path.path.as_path(), RecursiveMode::Recursive)
+            } else {
+                let parent = save_path.path.parent().unwrap_or(&save_path.path);
+                (parent, RecursiveMode::NonRecursive)
+            };
+            match watcher.watch(path, mode) {
+                Ok(()) => true,
+                Err(e) => {
+                    log::warn!("AutoUpload: watch {} failed: {e}", path.display());
+                    false
+                }
+            }
+        });
+        if watched.is_empty() && config.session_interval_mins == 0 {
+            return Ok(None);
+        }
+
+        log::info!(
+            "AutoUpload: watching {} save path(s) of game {}",
+            watched.len(),
+            target.game_id
+        );
+        let (stop_tx, stop_rx) = oneshot::channel();
+        let task = tauri::async_runtime::spawn(async move {
+            // keep the watcher alive until the session stops
+            let _watcher = watcher;
+            run(target, config, watched, event_rx, stop_rx).await;
+        });
+        Ok(Some(Self { stop_tx, task }))
+    }
+
+    /// Stop the session, after the snapshot being taken if any.
+    pub async fn stop(self) {
+        _ = self.stop_tx.send(());
+        if let Err(e) = self.task.await {
+            log::warn!("AutoUpload: session task failed: {e}");
+        }
+    }
+}
+
+async fn run(
+    target: SessionTarget,
+    config: AutoUploadGameConfig,
+    watched: Vec<SavePath>,
+    mut event_rx: mpsc::UnboundedReceiver<Vec<PathBuf>>,
+    mut stop_rx: oneshot::Receiver<()>,
+) {
+    let quiet = Duration::from_secs(config.session_quiet_secs.max(1) as u64);
+    let mut interval = (config.session_interval_mins > 0).then(|| {
+        let period = Duration::from_secs(config.session_interval_mins as u64 * 60);
+        time::interval_at(Instant::now() + period, period)
+    });
+    let mut deadline: Option<Instant> = None;
+    let mut taken = 0;
+
+    let target = std::sync::Arc::new(target);
+    while config.session_max_snapshots == 0 || taken < config.session_max_snapshots {
+        let due = tokio::select! {
+            _ = &mut stop_rx => break,
+            Some(paths) = event_rx.recv() => {
+                if paths.iter().any(|p| is_watched(&watched, p)) {
+                    deadline = Some(Instant::now() + quiet);
+                }
+                false
+            }
+            () = time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                deadline = None;
+                true
+            }
+            // not while the game is still writing, the quiet snapshot follows
+            () = tick(&mut interval), if interval.is_some() => deadline.is_none(),
+        };
+        if !due {
+            continue;
+        }
+
+        let target = target.clone();
+        let snapshot =
+            tauri::async_runtime::spawn_blocking(move || snapshot(&target, config)).await;
+        match snapshot {
+            Ok(Ok(Some(filename))) => {
+                taken += 1;
+                log::info!("AutoUpload: took in-session snapshot {filename}");
+            }
+            Ok(Ok(None)) => {}
+            Ok(Err(e)) => log::warn!("AutoUpload: in-session snapshot failed: {e}"),
+            Err(e) => log::warn!("AutoUpload: in-session snapshot task failed: {e}"),
+        }
+    }
+    if taken > 0 {
+        log::info!(
+            "AutoUpload: took {taken} in-session snapshot(s) of game {}",
+            target.game_id
+        );
+    }
+}
+
+async fn tick(interval: &mut Option<Interval>) {
+    if let Some(interval) = interval {
+        interval.tick().await;
+    }
+}
+
+/// Whether an event kind may change the content of a file.
+fn is_write(kind: &EventKind) -> bool {
+    match kind {
+        EventKind::Access(AccessKind::Close(AccessMode::Write)) => true,
+        EventKind::Access(_) | EventKind::Other => false,
+        _ => true,
+    }
+}
+
+/// Whether `path` is one of the archived files and dirs of `save_paths`.
+fn is_watched(save_paths: &[SavePath], path: &Path) -> bool {
+    save_paths.iter().any(|save_path| {
+        path == save_path.path || (path.starts_with(&save_path.path) && save_path.is_archived(path))
+    })
+}
+
+/// Take a snapshot if the saves changed since the newest archive, and prune
+/// the old snapshots.
+fn snapshot(target: &SessionTarget, config: AutoUploadGameConfig) -> Result<Option<String>> {
+    let unchanged = unchanged_since_latest(
+        &target.game_backup_dir,
+        &target.save_paths,
+        config.hash_contents,
+        |_| true,
+    )?;
+    if unchanged.is_some() {
+        return Ok(None);
+    }
+    let filename = crate::archive::archive_impl(
+        &target.device_name,
+        &target.archive_conf,
+        target.game_backup_dir.clone(),
+        target.save_paths.clone(),
+        ArchiveTrigger::InSession,
+        target.use_time,
+    )?;
+    let policy = RetentionPolicy {
+        keep_last: config.session_max_kept,
+        ..Default::default()
+    };
+    if !policy.is_unlimited() {
+        prune_local(&target.game_backup_dir, &policy, true);
+    }
+    Ok(Some(filename))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watches_save_paths_only() {
+        let dir = Path::new("/games/foo");
+        let watched = [
+            SavePath::new("{game}/save", dir.join("save")),
+            SavePath::new("{game}/system.dat", dir.join("system.dat")),
+        ];
+
+        assert!(is_watched(&watched, &dir.join("save")));
+        assert!(is_watched(&watched, &dir.join("save/slot1.dat")));
+        assert!(is_watched(&watched, &dir.join("system.dat")));
+        // siblings of a file save path are in the watched dir too
+        assert!(!is_watched(&watched, &dir.join("system.dat.tmp")));
+        assert!(!is_watched(&watched, &dir.join("game.exe")));
+
+        assert!(is_write(&EventKind::Access(AccessKind::Close(
+            AccessMode::Write
+        ))));
+        assert!(!is_write(&EventKind::Access(AccessKind::Read)));
+    }
+}
@@ -11,8 +11,9 @@
 //! 3. Add a new variant to `PluginInstance` below.
 //! 4. Add a new field to `PluginMetadatas` below.
 //! 5. Add a new variant to `PluginConfig` below.
-//! 6. Update `handler_key()` and `is_enabled()` in their respective modules,
-//!    and add a match arm to `instance_config()` in `mod.rs`.
+//! 6. Update `handler_key()`, `is_enabled()` and `auto_add_instances()` in
+//!    their respective modules, and add a match arm to `instance_config()` in
+//!    `mod.rs`.
 //! 7. Register the handler in the `PluginRegistry` constructor in `mod.rs`.
 
 use serde::{Deserialize, Serialize};
@@ -142,6 +143,52 @@ impl PluginMetadatas {
             PluginInstance::Wine { .. } => self.wine.enabled,
         }
     }
+
+    /// New instances of the plugins marked `auto_add`, with their config
+    /// defaults, in the order the frontend lists the plugins.
+    pub fn auto_add_instances(&self) -> Vec<PluginInstance> {
+        [
+            self.execute.auto_add.then(|| PluginInstance::Execute {
+                config: self.execute.config_defaults.clone(),
+            }),
+            self.auto_upload
+                .auto_add
+                .then_some(PluginInstance::AutoUpload {
+                    config: self.auto_upload.config_defaults,
+                }),
+            self.game_wrapper
+                .auto_add
+                .then(|| PluginInstance::GameWrapper {
+                    config: self.game_wrapper.config_defaults.clone(),
+                }),
+            self.locale_emulator
+                .auto_add
+                .then(|| PluginInstance::LocaleEmulator {
+                    config: self.locale_emulator.config_defaults.clone(),
+                }),
+            self.translator
+                .auto_add
+                .then(|| PluginInstance::Translator {
+                    config: self.translator.config_defaults.clone(),
+                }),
+            self.voice_speedup
+                .auto_add
+                .then(|| PluginInstance::VoiceSpeedup {
+                    config: self.voice_speedup.config_defaults.clone(),
+                }),
+            self.voice_zerointerrupt
+                .auto_add
+                .then(|| PluginInstance::VoiceZerointerrupt {
+                    config: self.voice_zerointerrupt.config_defaults.clone(),
+                }),
+            self.wine.auto_add.then(|| PluginInstance::Wine {
+                config: self.wine.config_defaults.clone(),
+            }),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
 }
 
 // ── Runtime context enum ─────────────────────────────────────────────────────
@@ -180,7 +227,17 @@ mod tests {
             PluginInstance::AutoUpload {
                 config: AutoUploadGameConfig {
                     max_kept: 7,
+                    keep_daily: 7,
+                    keep_weekly: 4,
+                    keep_monthly: 6,
                     retention_scope: RetentionScope::Local,
+                    hash_contents: true,
+                    pull_before_launch: true,
+                    session_snapshots: true,
+                    session_quiet_secs: 60,
+                    session_interval_mins: 15,
+                    session_max_snapshots: 0,
+                    session_max_kept: 5,
                 },
             },
             PluginInstance::VoiceSpeedup {
@@ -301,4 +358,21 @@ mod tests {
         };
         assert!(!metas.is_enabled(&wine));
     }
+
+    #[test]
+    fn auto_add_instances_use_the_config_defaults() {
+        let mut metas = PluginMetadatas::default();
+        assert!(metas.auto_add_instances().is_empty());
+
+        metas.wine.auto_add = true;
+        metas.wine.config_defaults.prefix = "/prefix".into();
+        metas.execute.auto_add = true;
+        let instances = metas.auto_add_instances();
+        let keys: Vec<&str> = instances.iter().map(|i| i.handler_key()).collect();
+        assert_eq!(keys, ["execute", "wine"]);
+        assert!(matches!(
+            &instances[1],
+            PluginInstance::Wine { config } if config.prefix == "/prefix"
+        ));
+    }
 }
@@ -15,10 +15,11 @@ use std::{
     sync::{Arc, LazyLock as Lazy},
 };
 
+pub use auto_upload::{RetentionPreview, preview_retention};
 // Re-export all public config types for downstream convenience.
 pub use config::{
-    ArchPreference, AutoUploadPluginMeta, DllOverride, ExecuteGameConfig, ExecutePhase,
-    ExecutePluginMeta, ExitSignal, GameWrapperGameConfig, GameWrapperPluginMeta,
+    ArchPreference, AutoUploadGameConfig, AutoUploadPluginMeta, DllOverride, ExecuteGameConfig,
+    ExecutePhase, ExecutePluginMeta, ExitSignal, GameWrapperGameConfig, GameWrapperPluginMeta,
     LocaleEmulatorGameConfig, LocaleEmulatorPluginMeta, PluginConfig, PluginInstance,
     PluginMetadatas, SpeedupProvider, TranslatorGameConfig, TranslatorPluginMeta,
     VoiceSpeedupGameConfig, VoiceSpeedupPluginMeta, VoiceZerointerruptGameConfig,
@@ -0,0 +1,331 @@
+//! Client-side encryption of objects stored on remote.
+//!
+//! An encrypted object starts with [`MAGIC`], followed by the KDF salt and the
+//! base nonce. The payload is split into chunks that are sealed one by one with
+//! XChaCha20-Poly1305, so large archives never have to be held in memory. Each
+//! chunk authenticates its index and whether it is the last one, which makes
+//! reordered or truncated objects fail to decrypt.
+//!
+//! Objects without the header are plaintext, so remotes written before
+//! encryption was enabled stay readable.
+//!
+//! Deriving a key is slow on purpose, so keys are cached for the session by
+//! passphrase and salt, and all objects encrypted in a session share a salt.
+//! Each object still has its own random nonce.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::LazyLock,
+};
+
+use argon2::Argon2;
+use chacha20poly1305::{
+    AeadCore, Key, KeyInit, XChaCha20Poly1305, XNonce,
+    aead::{Aead, OsRng, Payload, rand_core::RngCore},
+};
+
+use parking_lot::Mutex;
+
+use crate::error::{Error, Result};
+
+/// Header identifying an encrypted object.
+pub const MAGIC: &[u8; 8] = b"GALMENC1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = MAGIC.len() + SALT_LEN + NONCE_LEN;
+const CHUNK_SIZE: usize = 1024 * 1024;
+const TAG_LEN: usize = 16;
+
+/// Plain content of the key check object, see [`key_check`].
+const KEY_CHECK: &[u8] = b"galgame-manager key check";
+
+type Salt = [u8; SALT_LEN];
+
+/// Derived keys, by passphrase and salt.
+static KEYS: LazyLock<Mutex<HashMap<(String, Salt), Key>>> = LazyLock::new(Default::default);
+/// Salt of the objects encrypted in this session, by passphrase.
+static SESSION_SALTS: LazyLock<Mutex<HashMap<String, Salt>>> = LazyLock::new(Default::default);
+
+/// Whether `data` (or its first bytes) belongs to an encrypted object.
+#[inline]
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| Error::Crypto(format!("key derivation failed: {e}")))?;
+    Ok(key)
+}
+
+/// [`derive_key`], once per passphrase and salt.
+fn cached_key(passphrase: &str, salt: &Salt) -> Result<Key> {
+    let id = (passphrase.to_string(), *salt);
+    if let Some(key) = KEYS.lock().get(&id) {
+        return Ok(*key);
+    }
+    // not under the lock, derivation takes a while
+    let key = derive_key(passphrase, salt)?;
+    KEYS.lock().insert(id, key);
+    Ok(key)
+}
+
+fn session_salt(passphrase: &str) -> Salt {
+    *SESSION_SALTS
+        .lock()
+        .entry(passphrase.to_string())
+        .or_insert_with(|| {
+            let mut salt = [0; SALT_LEN];
+            OsRng.fill_bytes(&mut salt);
+            salt
+        })
+}
+
+fn chunk_nonce(base: &XNonce, index: u64) -> XNonce {
+    let mut nonce = *base;
+    for (b, i) in nonce[NONCE_LEN - 8..].iter_mut().zip(index.to_le_bytes()) {
+        *b ^= i;
+    }
+    nonce
+}
+
+fn chunk_aad(index: u64, last: bool) -> [u8; 9] {
+    let mut aad = [0; 9];
+    aad[..8].copy_from_slice(&index.to_le_bytes());
+    aad[8] = last as u8;
+    aad
+}
+
+fn read_chunk(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(CHUNK_SIZE);
+    reader.take(CHUNK_SIZE as u64).read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// Read a sealed chunk, `None` at the end of the object.
+fn read_sealed_chunk(reader: &mut impl Read) -> Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let len = u32::from_le_bytes(len) as usize;
+    if len > CHUNK_SIZE + TAG_LEN {
+        return Err(Error::Crypto("corrupted encrypted object".into()));
+    }
+    let mut buf = vec![0; len];
+    reader.read_exact(&mut buf)?;
+    Ok(Some(buf))
+}
+
+/// Encrypt everything from `reader` into `writer` with a key derived from
+/// `passphrase`.
+pub fn encrypt(passphrase: &str, mut reader: impl Read, mut writer: impl Write) -> Result<()> {
+    let salt = session_salt(passphrase);
+    let base = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let cipher = XChaCha20Poly1305::new(&cached_key(passphrase, &salt)?);
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&salt)?;
+    writer.write_all(&base)?;
+
+    // read one chunk ahead so that the last one can be flagged
+    let mut chunk = read_chunk(&mut reader)?;
+    let mut index = 0;
+    loop {
+        let next = if chunk.len() == CHUNK_SIZE {
+            read_chunk(&mut reader)?
+        } else {
+            vec![]
+        };
+        let last = next.is_empty();
+        let sealed = cipher
+            .encrypt(
+                &chunk_nonce(&base, index),
+                Payload {
+                    msg: &chunk,
+                    aad: &chunk_aad(index, last),
+                },
+            )
+            .map_err(|_| Error::Crypto("encryption failed".into()))?;
+        writer.write_all(&(sealed.len() as u32).to_le_bytes())?;
+        writer.write_all(&sealed)?;
+        if last {
+            break;
+        }
+        chunk = next;
+        index += 1;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Decrypt an object written by [`encrypt`] from `reader` into `writer`.
+pub fn decrypt(passphrase: &str, mut reader: impl Read, mut writer: impl Write) -> Result<()> {
+    let mut header = [0; HEADER_LEN];
+    reader.read_exact(&mut header)?;
+    if !is_encrypted(&header) {
+        return Err(Error::Crypto("not an encrypted object".into()));
+    }
+    let (salt, base) = header[MAGIC.len()..].split_at(SALT_LEN);
+    let base = XNonce::from_slice(base);
+    let salt = salt.try_into().expect("header holds a salt");
+    let cipher = XChaCha20Poly1305::new(&cached_key(passphrase, salt)?);
+
+    let mut sealed = read_sealed_chunk(&mut reader)?
+        .ok_or_else(|| Error::Crypto("truncated encrypted object".into()))?;
+    let mut index = 0;
+    loop {
+        let next = read_sealed_chunk(&mut reader)?;
+        let chunk = cipher
+            .decrypt(
+                &chunk_nonce(base, index),
+                Payload {
+                    msg: &sealed,
+                    aad: &chunk_aad(index, next.is_none()),
+                },
+            )
+            .map_err(|_| Error::Crypto("wrong passphrase or corrupted data".into()))?;
+        writer.write_all(&chunk)?;
+        match next {
+            Some(next) => sealed = next,
+            None => break,
+        }
+        index += 1;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// In-memory version of [`encrypt`].
+pub fn encrypt_bytes(passphrase: &str, data: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(HEADER_LEN + data.len());
+    encrypt(passphrase, data, &mut out)?;
+    Ok(out)
+}
+
+/// In-memory version of [`decrypt`].
+pub fn decrypt_bytes(passphrase: &str, data: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len());
+    decrypt(passphrase, data, &mut out)?;
+    Ok(out)
+}
+
+/// A random passphrase with a full key worth of entropy.
+pub fn random_passphrase() -> String {
+    let mut key = Key::default();
+    OsRng.fill_bytes(&mut key);
+    hex::encode(key)
+}
+
+/// Content of the key check object, which tells whether a passphrase is the
+/// one the remote was encrypted with before anything else is read or written.
+#[inline]
+pub fn key_check(passphrase: &str) -> Result<Vec<u8>> {
+    encrypt_bytes(passphrase, KEY_CHECK)
+}
+
+/// Fails if `data`, a [`key_check`], was not made with `passphrase`.
+pub fn verify_key_check(passphrase: &str, data: &[u8]) -> Result<()> {
+    match decrypt_bytes(passphrase, data) {
+        Ok(plain) if plain == KEY_CHECK => Ok(()),
+        _ => Err(Error::Crypto(
+            "passphrase does not match the one the remote is encrypted with".into(),
+        )),
+    }
+}
+
+/// Whether the file at `path` holds an encrypted object.
+pub fn is_encrypted_file(path: &Path) -> io::Result<bool> {
+    let mut head = [0; MAGIC.len()];
+    match File::open(path)?.read_exact(&mut head) {
+        Ok(()) => Ok(is_encrypted(&head)),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Encrypt the file `src` into `dst`.
+pub fn encrypt_file(passphrase: &str, src: &Path, dst: &Path) -> Result<()> {
+    encrypt(
+        passphrase,
+        BufReader::new(File::open(src)?),
+        BufWriter::new(File::create(dst)?),
+    )
+}
+
+/// Decrypt the file `src` into `dst`.
+pub fn decrypt_file(passphrase: &str, src: &Path, dst: &Path) -> Result<()> {
+    decrypt(
+        passphrase,
+        BufReader::new(File::open(src)?),
+        BufWriter::new(File::create(dst)?),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_multiple_chunks() {
+        let data: Vec<u8> = (0..CHUNK_SIZE * 2 + 123).map(|i| i as u8).collect();
+        let sealed = encrypt_bytes("pass", &data).unwrap();
+        assert!(is_encrypted(&sealed));
+        assert_eq!(decrypt_bytes("pass", &sealed).unwrap(), data);
+    }
+
+    #[test]
+    fn roundtrip_empty() {
+        let sealed = encrypt_bytes("pass", &[]).unwrap();
+        assert!(decrypt_bytes("pass", &sealed).unwrap().is_empty());
+    }
+
+    #[test]
+    fn wrong_passphrase_fails() {
+        let sealed = encrypt_bytes("pass", b"save data").unwrap();
+        assert!(matches!(
+            decrypt_bytes("other", &sealed),
+            Err(Error::Crypto(_))
+        ));
+    }
+
+    #[test]
+    fn keys_are_derived_once_per_salt() {
+        let a = encrypt_bytes("session", b"a").unwrap();
+        let b = encrypt_bytes("session", b"b").unwrap();
+        // same salt, different nonces
+        assert_eq!(a[..MAGIC.len() + SALT_LEN], b[..MAGIC.len() + SALT_LEN]);
+        assert_ne!(
+            a[MAGIC.len() + SALT_LEN..HEADER_LEN],
+            b[MAGIC.len() + SALT_LEN..HEADER_LEN]
+        );
+        let salt: Salt = a[MAGIC.len()..MAGIC.len() + SALT_LEN].try_into().unwrap();
+        assert!(KEYS.lock().contains_key(&("session".to_string(), salt)));
+        assert_eq!(decrypt_bytes("session", &b).unwrap(), b"b");
+    }
+
+    #[test]
+    fn key_check_detects_other_passphrases() {
+        let check = key_check("pass").unwrap();
+        assert!(verify_key_check("pass", &check).is_ok());
+        assert!(verify_key_check("other", &check).is_err());
+        let other = encrypt_bytes("pass", b"not a check").unwrap();
+        assert!(verify_key_check("pass", &other).is_err());
+    }
+
+    #[test]
+    fn truncation_is_detected() {
+        let data = vec![7; CHUNK_SIZE * 2];
+        let sealed = encrypt_bytes("pass", &data).unwrap();
+        // drop the last chunk entirely
+        let truncated = &sealed[..HEADER_LEN + 4 + CHUNK_SIZE + TAG_LEN];
+        assert!(decrypt_bytes("pass", truncated).is_err());
+    }
+}
@@ -0,0 +1,306 @@
+//! Save history of a game across devices, to detect save conflicts.
+//!
+//! The history is stored next to the archives of a game on remote. It records
+//! the archive each device last uploaded or restored, which is what the
+//! device last agreed on with the others. Saves diverged if a device uploads
+//! an archive while another device uploaded one since it last agreed, or if
+//! it restores the archive of another device over saves it never uploaded.
+
+use std::{collections::BTreeMap, path::Path};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::MyOperation;
+use crate::{
+    archive::{ArchiveMeta, ArchiveTrigger, fingerprint_saves, read_meta, sort_by_age},
+    db::{SavePathConfig, device::DEVICE_UID},
+    error::Result,
+    utils::list_dir_all,
+};
+
+/// Name of the history file in the remote dir of a game.
+pub const HISTORY_FILENAME: &str = "history.toml";
+
+/// An archive a device uploaded or restored.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncRecord {
+    pub device_uid: String,
+    pub device_name: String,
+    pub archive: String,
+    /// See [`ArchiveMeta::content_hash`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub content_hash: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+impl SyncRecord {
+    /// Record of this device for the local archive at `archive_path`.
+    pub fn of_local(device_name: &str, archive_path: &Path) -> Result<Self> {
+        let meta = read_meta(archive_path)?;
+        Ok(Self {
+            device_uid: DEVICE_UID.to_string(),
+            device_name: device_name.to_string(),
+            archive: archive_path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            content_hash: meta.and_then(|m| m.content_hash),
+            at: Utc::now(),
+        })
+    }
+
+    /// Record of this device for its current saves. The archive is the newest
+    /// local one, empty if there is none.
+    pub fn of_saves(
+        device_name: &str,
+        local_game_dir: &Path,
+        save_paths: &[SavePathConfig],
+    ) -> Result<Self> {
+        let mut archives = if local_game_dir.exists() {
+            list_dir_all(local_game_dir)?
+        } else {
+            vec![]
+        };
+        archives.retain(|a| {
+            a.meta
+                .as_ref()
+                .is_none_or(|m| m.trigger != ArchiveTrigger::PreRestore)
+        });
+        sort_by_age(&mut archives);
+        Ok(Self {
+            device_uid: DEVICE_UID.to_string(),
+            device_name: device_name.to_string(),
+            archive: archives.pop().map(|a| a.name).unwrap_or_default(),
+            content_hash: Some(fingerprint_saves(save_paths, true)?),
+            at: Utc::now(),
+        })
+    }
+
+    /// Record of the device that created an archive, at its creation.
+    pub fn of_creator(archive: &str, meta: &ArchiveMeta) -> Self {
+        Self {
+            device_uid: meta.device_uid.clone(),
+            device_name: meta.device_name.clone(),
+            archive: archive.to_string(),
+            content_hash: meta.content_hash.clone(),
+            at: meta.created_at,
+        }
+    }
+
+    /// Whether both records are known to hold the same saves.
+    #[inline]
+    fn same_saves(&self, other: &Self) -> bool {
+        self.archive == other.archive
+            || self.content_hash.is_some() && self.content_hash == other.content_hash
+    }
+}
+
+/// Saves of a game that diverged between two devices.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveConflict {
+    pub game_id: u32,
+    /// The saves of this device.
+    pub local: SyncRecord,
+    /// The saves of the other device.
+    pub remote: SyncRecord,
+}
+
+impl std::fmt::Display for SaveConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} has {} and {} has {}",
+            self.local.device_name,
+            self.local.archive,
+            self.remote.device_name,
+            self.remote.archive
+        )
+    }
+}
+
+/// How to resolve a [`SaveConflict`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub enum ConflictResolution {
+    /// Upload the local archive over the other one.
+    KeepLocal,
+    /// Restore the archive of the other device.
+    KeepRemote,
+    /// Upload the local archive and pin both of them.
+    KeepBoth,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SaveHistory {
+    /// The last uploaded archive.
+    pub head: Option<SyncRecord>,
+    /// The archive each device last uploaded or restored, by device uid.
+    pub devices: BTreeMap<String, SyncRecord>,
+}
+
+impl SaveHistory {
+    #[inline]
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("save history is always serializable")
+    }
+
+    #[inline]
+    pub fn from_toml(content: &[u8]) -> std::result::Result<Self, toml::de::Error> {
+        toml::from_slice(content)
+    }
+
+    /// The archive uploaded by another device that uploading `local` would
+    /// silently replace as the newest one.
+    pub fn upload_conflict(&self, local: &SyncRecord) -> Option<&SyncRecord> {
+        let head = self.head.as_ref()?;
+        if head.device_uid == local.device_uid || head.same_saves(local) {
+            return None;
+        }
+        match self.devices.get(&local.device_uid) {
+            // it has the saves of the head already
+            Some(base) if base.same_saves(head) => None,
+            _ => Some(head),
+        }
+    }
+
+    /// Whether restoring `archive` of another device would overwrite
+    /// `current`, saves of this device that were never uploaded.
+    pub fn restore_conflict(&self, current: &SyncRecord, archive: &SyncRecord) -> bool {
+        let Some(base) = self.devices.get(&current.device_uid) else {
+            return false;
+        };
+        archive.device_uid != current.device_uid
+            && !base.same_saves(current)
+            && !archive.same_saves(current)
+    }
+
+    pub fn record_upload(&mut self, record: SyncRecord) {
+        self.head = Some(record.clone());
+        self.devices.insert(record.device_uid.clone(), record);
+    }
+
+    pub fn record_restore(&mut self, record: SyncRecord) {
+        self.devices.insert(record.device_uid.clone(), record);
+    }
+}
+
+/// The conflict uploading `local` to remote would cause, if any.
+pub async fn check_upload(
+    op: &(dyn MyOperation + Send + Sync),
+    game_id: u32,
+    local: &SyncRecord,
+) -> Result<Option<SaveConflict>> {
+    let history = op.save_history(game_id).await?;
+    Ok(history.upload_conflict(local).map(|remote| SaveConflict {
+        game_id,
+        local: local.clone(),
+        remote: remote.clone(),
+    }))
+}
+
+/// The conflict restoring `archive` over the `current` saves would cause, if
+/// any.
+pub async fn check_restore(
+    op: &(dyn MyOperation + Send + Sync),
+    game_id: u32,
+    current: &SyncRecord,
+    archive: &SyncRecord,
+) -> Result<Option<SaveConflict>> {
+    let history = op.save_history(game_id).await?;
+    Ok(history
+        .restore_conflict(current, archive)
+        .then(|| SaveConflict {
+            game_id,
+            local: current.clone(),
+            remote: archive.clone(),
+        }))
+}
+
+/// Record that this device uploaded an archive.
+pub async fn record_upload(
+    op: &(dyn MyOperation + Send + Sync),
+    game_id: u32,
+    record: SyncRecord,
+) -> Result<()> {
+    let mut history = op.save_history(game_id).await?;
+    history.record_upload(record);
+    op.write_save_history(game_id, &history).await
+}
+
+/// Record that this device restored an archive.
+pub async fn record_restore(
+    op: &(dyn MyOperation + Send + Sync),
+    game_id: u32,
+    record: SyncRecord,
+) -> Result<()> {
+    let mut history = op.save_history(game_id).await?;
+    history.record_restore(record);
+    op.write_save_history(game_id, &history).await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone as _;
+
+    use super::*;
+
+    fn record(device: &str, archive: &str, hash: &str) -> SyncRecord {
+        SyncRecord {
+            device_uid: device.to_string(),
+            device_name: device.to_string(),
+            archive: archive.to_string(),
+            content_hash: Some(hash.to_string()),
+            at: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn detects_diverged_uploads() {
+        let mut history = SaveHistory::default();
+        assert_eq!(history.upload_conflict(&record("a", "a1", "1")), None);
+        history.record_upload(record("a", "a1", "1"));
+
+        // b never agreed on a1
+        assert!(history.upload_conflict(&record("b", "b1", "2")).is_some());
+        // unless it has the same saves
+        assert_eq!(history.upload_conflict(&record("b", "b1", "1")), None);
+        // a builds on its own upload
+        assert_eq!(history.upload_conflict(&record("a", "a2", "3")), None);
+
+        history.record_restore(record("b", "a1", "1"));
+        assert_eq!(history.upload_conflict(&record("b", "b1", "2")), None);
+        history.record_upload(record("b", "b1", "2"));
+
+        // a played on a1 while b uploaded b1
+        let conflict = history.upload_conflict(&record("a", "a2", "3"));
+        assert_eq!(conflict.map(|r| r.archive.as_str()), Some("b1"));
+    }
+
+    #[test]
+    fn detects_overwritten_saves() {
+        let mut history = SaveHistory::default();
+        history.record_upload(record("a", "a1", "1"));
+        history.record_restore(record("b", "a1", "1"));
+        history.record_upload(record("b", "b1", "2"));
+
+        // a did not change its saves since a1
+        assert!(!history.restore_conflict(&record("a", "", "1"), &record("b", "b1", "2")));
+        // a did
+        assert!(history.restore_conflict(&record("a", "", "3"), &record("b", "b1", "2")));
+        // its own archives are fine
+        assert!(!history.restore_conflict(&record("a", "", "3"), &record("a", "a1", "1")));
+        // c never synced
+        assert!(!history.restore_conflict(&record("c", "", "4"), &record("b", "b1", "2")));
+        let toml = history.to_toml();
+        assert_eq!(SaveHistory::from_toml(toml.as_bytes()).unwrap(), history);
+    }
+}
@@ -0,0 +1,262 @@
+//! The [split](crate::db::settings::RemoteLayout::Split) layout of the remote config.
+//!
+//! Every game is its own object under [`LIBRARY_DIR`], next to objects for the
+//! settings, the devices and the plugin metadata. The [`LibraryIndex`] lists
+//! the hash of every object, so that only changed objects are written or
+//! read. It is written last: readers never see half of a write, they see
+//! objects that do not match the index and try again.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    archive::sha256_of,
+    db::{Config, Game, device::Device, revision::VersionVector},
+    error::{Error, Result},
+};
+
+pub const LIBRARY_DIR: &str = "library/";
+pub const INDEX_PATH: &str = "library/index.toml";
+const SETTINGS_PATH: &str = "library/settings.toml";
+const DEVICES_PATH: &str = "library/devices.toml";
+const PLUGINS_PATH: &str = "library/plugins.toml";
+
+#[inline]
+fn game_path(id: u32) -> String {
+    format!("{LIBRARY_DIR}games/{id}.toml")
+}
+
+/// The config fields that are not in an object of their own, and the hashes
+/// of all objects.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryIndex {
+    pub db_version: u32,
+    pub last_updated: DateTime<Utc>,
+    pub revision: VersionVector,
+    /// Whether the objects are encrypted.
+    #[serde(default)]
+    pub encrypted: bool,
+    /// SHA-256 of the plain content of every object, by path.
+    pub objects: BTreeMap<String, String>,
+    /// Ids of the games in the order of the library.
+    #[serde(default)]
+    pub games: Vec<u32>,
+}
+
+impl LibraryIndex {
+    /// Paths of the objects of `self` that `new` does not have anymore.
+    pub fn removed<'a>(&'a self, new: &'a Self) -> impl Iterator<Item = &'a str> {
+        self.objects
+            .keys()
+            .filter(|path| !new.objects.contains_key(*path))
+            .map(String::as_str)
+    }
+}
+
+/// TOML documents cannot be arrays.
+#[derive(Serialize, Deserialize)]
+struct Devices {
+    devices: Vec<Device>,
+}
+
+/// A config in the split layout.
+#[derive(Debug, Clone, Default)]
+pub struct SplitConfig {
+    pub index: LibraryIndex,
+    /// Plain content of every object, by path.
+    pub objects: BTreeMap<String, String>,
+}
+
+impl SplitConfig {
+    pub fn new(config: &Config) -> Result<Self> {
+        let mut split = Self {
+            index: LibraryIndex {
+                db_version: config.db_version,
+                last_updated: config.last_updated,
+                revision: config.revision.clone(),
+                encrypted: false,
+                objects: BTreeMap::new(),
+                games: config.games.iter().map(|g| g.id).collect(),
+            },
+            objects: BTreeMap::new(),
+        };
+        split.insert(SETTINGS_PATH.to_string(), &config.settings)?;
+        split.insert(
+            DEVICES_PATH.to_string(),
+            &Devices {
+                devices: config.devices.clone(),
+            },
+        )?;
+        split.insert(PLUGINS_PATH.to_string(), &config.plugin_metadatas)?;
+        for game in &config.games {
+            split.insert(game_path(game.id), game)?;
+        }
+        Ok(split)
+    }
+
+    fn insert(&mut self, path: String, value: &impl Serialize) -> Result<()> {
+        let content = toml::to_string(value)?;
+        self.index
+            .objects
+            .insert(path.clone(), sha256_of(content.as_bytes())?);
+        self.objects.insert(path, content);
+        Ok(())
+    }
+
+    /// Objects that `index` does not have with the same content.
+    pub fn changed<'a>(
+        &'a self,
+        index: Option<&'a LibraryIndex>,
+    ) -> impl Iterator<Item = (&'a str, &'a str)> {
+        self.objects
+            .iter()
+            .filter(move |(path, _)| {
+                index.and_then(|i| i.objects.get(*path)) != self.index.objects.get(*path)
+            })
+            .map(|(path, content)| (path.as_str(), content.as_str()))
+    }
+
+    /// Content of the object at `path` if it has `hash`.
+    pub fn get_if(&self, path: &str, hash: &str) -> Option<&str> {
+        (self.index.objects.get(path)? == hash)
+            .then(|| self.objects.get(path).map(String::as_str))
+            .flatten()
+    }
+
+    /// Put the config back together. Fails with
+    /// [`Error::RemoteConfigChanged`] if an object is missing or does not
+    /// match the index.
+    pub fn assemble(self) -> Result<Config> {
+        for (path, hash) in &self.index.objects {
+            match self.objects.get(path) {
+                Some(content) if sha256_of(content.as_bytes())? == *hash => {}
+                _ => return Err(Error::RemoteConfigChanged),
+            }
+        }
+
+        let mut config = Config {
+            db_version: self.index.db_version,
+            last_updated: self.index.last_updated,
+            revision: self.index.revision,
+            ..Default::default()
+        };
+        let mut games = vec![];
+        for (path, content) in &self.objects {
+            match path.as_str() {
+                SETTINGS_PATH => config.settings = parse(path, content)?,
+                DEVICES_PATH => config.devices = parse::<Devices>(path, content)?.devices,
+                PLUGINS_PATH => config.plugin_metadatas = parse(path, content)?,
+                _ => games.push(parse::<Game>(path, content)?),
+            }
+        }
+        // games missing from the order go last
+        games.sort_by_key(|g| {
+            let position = self.index.games.iter().position(|&id| id == g.id);
+            (position.unwrap_or(usize::MAX), g.id)
+        });
+        config.games = games;
+        Ok(config)
+    }
+}
+
+fn parse<T: DeserializeOwned>(path: &str, content: &str) -> Result<T> {
+    toml::from_str(content).map_err(|e| {
+        log::warn!("invalid remote config object {path}: {e}");
+        e.into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::SavePathConfig;
+
+    fn config() -> Config {
+        let mut config = Config::default();
+        config.revision.bump_device("a");
+        config.devices.push(Device {
+            name: "desktop".to_string(),
+            uid: "a".to_string(),
+            ..Default::default()
+        });
+        for id in [2, 1] {
+            config.games.push(Game {
+                id,
+                name: format!("game {id}"),
+                save_paths: vec![SavePathConfig::from(format!("/saves/{id}"))],
+                ..Default::default()
+            });
+        }
+        config
+    }
+
+    #[test]
+    fn splits_and_assembles() {
+        let config = config();
+        let split = SplitConfig::new(&config).unwrap();
+        assert_eq!(split.objects.len(), 5);
+        assert!(split.objects.contains_key("library/games/1.toml"));
+
+        let assembled = split.assemble().unwrap();
+        assert_eq!(assembled.revision, config.revision);
+        assert_eq!(assembled.devices[0].name, "desktop");
+        assert_eq!(
+            assembled.games.iter().map(|g| g.id).collect::<Vec<_>>(),
+            [2, 1]
+        );
+        assert_eq!(assembled.games[0].save_paths, config.games[0].save_paths);
+    }
+
+    #[test]
+    fn games_missing_from_the_order_go_last() {
+        let mut config = config();
+        config.games.push(Game {
+            id: 3,
+            ..Default::default()
+        });
+        let mut split = SplitConfig::new(&config).unwrap();
+        split.index.games = vec![1];
+        let assembled = split.assemble().unwrap();
+        assert_eq!(
+            assembled.games.iter().map(|g| g.id).collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn only_changed_objects_are_written() {
+        let old = SplitConfig::new(&config()).unwrap();
+        let mut config = config();
+        config.games[0].name = "renamed".to_string();
+        config.games.pop();
+        let new = SplitConfig::new(&config).unwrap();
+
+        assert_eq!(
+            new.changed(Some(&old.index))
+                .map(|(path, _)| path)
+                .collect::<Vec<_>>(),
+            ["library/games/2.toml"]
+        );
+        assert_eq!(
+            old.index.removed(&new.index).collect::<Vec<_>>(),
+            ["library/games/1.toml"]
+        );
+        assert_eq!(new.changed(None).count(), 4);
+    }
+
+    #[test]
+    fn objects_must_match_the_index() {
+        let mut split = SplitConfig::new(&config()).unwrap();
+        split
+            .objects
+            .insert("library/games/1.toml".to_string(), "id = 3".to_string());
+        assert!(matches!(split.assemble(), Err(Error::RemoteConfigChanged)));
+
+        let mut split = SplitConfig::new(&config()).unwrap();
+        split.objects.remove("library/settings.toml");
+        assert!(matches!(split.assemble(), Err(Error::RemoteConfigChanged)));
+    }
+}
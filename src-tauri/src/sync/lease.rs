@@ -0,0 +1,154 @@
+//! Lease on the remote config, for backends that cannot write it
+//! conditionally.
+//!
+//! A device holds the lease while it checks and writes the remote config. The
+//! lease expires so that a crashed device does not block the others forever.
+//! It is only as atomic as the backend allows: created with `if_not_exists`
+//! where supported, otherwise written and read back after a short delay.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use log::warn;
+use opendal::Operator;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{CONFIG, device::DEVICE_UID},
+    error::{Error, Result},
+};
+
+/// Name of the lease object in the remote root.
+pub const LEASE_FILENAME: &str = "config.lock";
+
+/// How long a lease is valid, long enough to check and write the config.
+const LEASE_TTL: Duration = Duration::from_secs(60);
+
+/// How long to wait before reading back a written lease, so that a device
+/// writing at the same time is noticed.
+const LEASE_SETTLE: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigLease {
+    pub device_uid: String,
+    pub device_name: String,
+    /// Tells apart leases of the same device.
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl ConfigLease {
+    fn new(device_name: String, now: DateTime<Utc>) -> Self {
+        Self {
+            device_uid: DEVICE_UID.to_string(),
+            device_name,
+            token: format!(
+                "{}-{}",
+                *DEVICE_UID,
+                now.timestamp_nanos_opt().unwrap_or_default()
+            ),
+            expires_at: now + LEASE_TTL,
+        }
+    }
+
+    /// Whether this lease keeps `other` from being taken at `now`.
+    #[inline]
+    fn blocks(&self, other: &Self, now: DateTime<Utc>) -> bool {
+        self.token != other.token && self.expires_at > now
+    }
+
+    fn locked(&self) -> Error {
+        Error::RemoteConfigLocked(self.device_name.clone())
+    }
+}
+
+async fn read_lease(op: &Operator) -> Result<Option<ConfigLease>> {
+    match op.read(LEASE_FILENAME).await {
+        Ok(buf) => Ok(toml::from_slice(&buf.to_vec()).ok()),
+        Err(e) if e.kind() == opendal::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Take the lease, failing with [`Error::RemoteConfigLocked`] if another
+/// device holds it.
+pub async fn acquire(op: &Operator) -> Result<ConfigLease> {
+    let now = Utc::now();
+    let lease = ConfigLease::new(CONFIG.lock().device_name(), now);
+    if let Some(held) = read_lease(op).await? {
+        if held.blocks(&lease, now) {
+            return Err(held.locked());
+        }
+        warn!(
+            "remote config lease of {} expired, taking over",
+            held.device_name
+        );
+        op.delete(LEASE_FILENAME).await?;
+    }
+
+    let content = toml::to_string(&lease).expect("lease is always serializable");
+    if op.info().full_capability().write_with_if_not_exists {
+        return match op
+            .write_with(LEASE_FILENAME, content)
+            .if_not_exists(true)
+            .await
+        {
+            Ok(_) => Ok(lease),
+            Err(e) if e.kind() == opendal::ErrorKind::ConditionNotMatch => {
+                Err(match read_lease(op).await? {
+                    Some(held) => held.locked(),
+                    None => Error::RemoteConfigChanged,
+                })
+            }
+            Err(e) => Err(e.into()),
+        };
+    }
+
+    op.write(LEASE_FILENAME, content).await?;
+    tokio::time::sleep(LEASE_SETTLE).await;
+    match read_lease(op).await? {
+        Some(held) if held.token == lease.token => Ok(lease),
+        Some(held) => Err(held.locked()),
+        None => Err(Error::RemoteConfigChanged),
+    }
+}
+
+/// Give the lease back, unless it was taken over meanwhile. Errors are only
+/// logged, the lease expires anyway.
+pub async fn release(op: &Operator, lease: &ConfigLease) {
+    let res = match read_lease(op).await {
+        Ok(Some(held)) if held.token == lease.token => {
+            op.delete(LEASE_FILENAME).await.map_err(Error::from)
+        }
+        Ok(_) => Ok(()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = res {
+        warn!("failed to release remote config lease: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone as _;
+
+    use super::*;
+
+    #[test]
+    fn only_live_leases_of_others_block() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let ours = ConfigLease::new("a".to_string(), now);
+        let theirs = ConfigLease {
+            token: "other".to_string(),
+            ..ours.clone()
+        };
+
+        assert!(theirs.blocks(&ours, now));
+        assert!(!ours.blocks(&ours, now));
+        assert!(!theirs.blocks(&ours, now + LEASE_TTL));
+
+        let toml = toml::to_string(&ours).unwrap();
+        assert_eq!(toml::from_str::<ConfigLease>(&toml).unwrap(), ours);
+    }
+}
@@ -1,4 +1,11 @@
+pub(crate) mod crypto;
+pub mod history;
+pub mod layout;
+mod lease;
 mod opendal;
+pub mod scheduler;
+pub mod snapshot;
+pub mod status;
 use std::{
     path::{Path, PathBuf},
     time::Duration,
@@ -15,12 +22,19 @@ use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Emitter as _};
 use ts_rs::TS;
 
+use self::{history::SaveHistory, snapshot::ConfigSnapshot};
 use crate::{
-    archive::ArchiveInfo,
+    archive::{ArchiveInfo, ChecksumStatus},
     db::{
-        CONFIG, CONFIG_FILENAME, Config, TimeCmp,
+        CONFIG, Config, TimeCmp,
+        backup::{BackupReason, backup_config},
         device::{ResolveVar, VarMap},
-        settings::{LocalConfig, S3Config, WebDavConfig},
+        merge::{
+            ConfigConflict, ConflictResolutions, MergeResult, merge_base, merge_config,
+            store_base_config,
+        },
+        secrets::{self, keep_local_secrets},
+        settings::{LocalConfig, RemoteLayout, S3Config, WebDavConfig},
     },
     error::{Error, Result},
     utils,
@@ -29,6 +43,11 @@ use crate::{
 const MAX_RETRY_DELAY: Duration = Duration::from_secs(5);
 const RETRY_TIMES: usize = 3;
 
+/// How often a safe config upload is retried when the remote config changed or
+/// is locked by another device meanwhile.
+const CONFIG_WRITE_RETRIES: usize = 3;
+const CONFIG_WRITE_RETRY_DELAY: Duration = Duration::from_secs(2);
+
 /// Tauri event key emitted when a sync operation fails.
 const EVENT_SYNC_FAILED: &str = "sync://failed";
 
@@ -43,12 +62,40 @@ pub const DEFAULT_NON_IO_TIMEOUT: Duration = Duration::from_secs(15);
 pub enum UploadConfigStatus {
     /// Config was successfully uploaded to remote.
     Uploaded,
+    /// Both sides changed since last sync and were merged without conflicts;
+    /// the merged config was applied locally and uploaded.
+    Merged,
     /// Local config has not changed since last sync — upload skipped.
     LocalClean,
-    /// Remote config is newer than local base — conflict detected.
+    /// Both sides changed since last sync and some fields conflict. Resolve
+    /// them through `merge_remote_config`.
     Conflict,
 }
 
+/// The version of the remote config that was read, which a conditional write
+/// expects to replace.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigVersion {
+    /// ETag reported by the backend, if any.
+    pub etag: Option<String>,
+    /// SHA-256 of the raw remote content, `None` if there is no remote config.
+    /// In the split layout, the content of the index.
+    pub hash: Option<String>,
+    /// Layout the remote config was read from.
+    pub layout: RemoteLayout,
+}
+
+/// Outcome of a three-way merge with the remote config.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct MergeOutcome {
+    /// Whether the merged config was applied locally and uploaded.
+    pub applied: bool,
+    /// Conflicts left unresolved; not empty if and only if not applied.
+    pub conflicts: Vec<ConfigConflict>,
+}
+
 #[async_trait::async_trait]
 pub trait MyOperation {
     fn inner(&self) -> &Operator;
@@ -90,6 +137,47 @@ pub trait MyOperation {
             .pull_archive(game_id, archive_filename, backup_dir)
             .await
     }
+    /// Check a remote archive against its checksum. The archive is downloaded
+    /// for that.
+    #[inline]
+    async fn check_archive(&self, game_id: u32, archive_filename: &str) -> Result<ChecksumStatus> {
+        self.inner().check_archive(game_id, archive_filename).await
+    }
+    /// The checksum in the sidecar of a remote archive, `None` if it has none.
+    #[inline]
+    async fn archive_checksum(
+        &self,
+        game_id: u32,
+        archive_filename: &str,
+    ) -> Result<Option<String>> {
+        self.inner()
+            .archive_checksum(game_id, archive_filename)
+            .await
+    }
+    /// Set the note in the metadata of a remote archive, or clear it.
+    #[inline]
+    async fn set_archive_note(
+        &self,
+        game_id: u32,
+        archive_filename: &str,
+        note: Option<String>,
+    ) -> Result<()> {
+        self.inner()
+            .set_archive_note(game_id, archive_filename, note)
+            .await
+    }
+    /// Pin a remote archive so that it is never pruned, or unpin it.
+    #[inline]
+    async fn set_archive_pinned(
+        &self,
+        game_id: u32,
+        archive_filename: &str,
+        pinned: bool,
+    ) -> Result<()> {
+        self.inner()
+            .set_archive_pinned(game_id, archive_filename, pinned)
+            .await
+    }
     #[inline]
     async fn rename_archive(
         &self,
@@ -101,9 +189,23 @@ pub trait MyOperation {
             .rename_archive(game_id, archive_filename, new_archive_filename)
             .await
     }
+    /// The save history of a game, empty if it has none yet.
+    #[inline]
+    async fn save_history(&self, game_id: u32) -> Result<SaveHistory> {
+        self.inner().save_history(game_id).await
+    }
+    #[inline]
+    async fn write_save_history(&self, game_id: u32, history: &SaveHistory) -> Result<()> {
+        self.inner().write_save_history(game_id, history).await
+    }
     #[inline]
     async fn get_remote_config(&self) -> Result<Option<Config>> {
-        self.inner().get_remote_config().await
+        Ok(self.get_remote_config_versioned().await?.0)
+    }
+    /// The remote config and its version, to write it conditionally later.
+    #[inline]
+    async fn get_remote_config_versioned(&self) -> Result<(Option<Config>, ConfigVersion)> {
+        self.inner().get_remote_config_versioned().await
     }
 
     #[cfg(feature = "config-daily-backup")]
@@ -112,40 +214,102 @@ pub trait MyOperation {
         self.inner().replicate_config().await
     }
 
-    /// upload config to remote, do not check anything or print log. should not
-    /// be used outside this mod.
+    /// Daily snapshots of the config on remote, newest first.
+    #[inline]
+    async fn list_config_snapshots(&self) -> Result<Vec<ConfigSnapshot>> {
+        self.inner().list_config_snapshots().await
+    }
+    /// The config of the snapshot called `name`.
+    #[inline]
+    async fn get_config_snapshot(&self, name: &str) -> Result<Config> {
+        self.inner().get_config_snapshot(name).await
+    }
+    /// Delete the snapshots beyond the `keep` newest ones and return their
+    /// names. `0` keeps all.
+    #[inline]
+    async fn prune_config_snapshots(&self, keep: u32) -> Result<Vec<String>> {
+        self.inner().prune_config_snapshots(keep).await
+    }
+
+    /// upload `config` to remote in the configured
+    /// [layout](crate::db::settings::RemoteLayout), do not check anything or
+    /// print log. should not be used outside this mod.
+    ///
+    /// If `expected` is given, the write fails with
+    /// [`Error::RemoteConfigChanged`] unless the remote config is still of
+    /// that version.
     #[inline]
-    async fn upload_config_inner(&self, filename: &str) -> Result<()> {
-        self.inner().upload_config_inner(filename).await
+    async fn upload_config_inner(
+        &self,
+        config: Config,
+        expected: Option<&ConfigVersion>,
+    ) -> Result<()> {
+        self.inner().upload_config_inner(config, expected).await
     }
 
     /// upload config to remote
     ///
     /// # Parameters
     ///
-    /// - safe=true: will not upload config if local is clean or remote config
-    ///   is newer
+    /// - safe=true: will not upload config if local is clean, and merges with
+    ///   remote config if it is newer. The remote config is only replaced if
+    ///   it did not change since it was checked; otherwise the upload is
+    ///   checked again, a few times.
     async fn upload_config(&self, app: &AppHandle, safe: bool) -> Result<UploadConfigStatus> {
+        let mut attempt = 0;
+        loop {
+            match self.upload_config_once(app, safe).await {
+                Err(e @ (Error::RemoteConfigChanged | Error::RemoteConfigLocked(_)))
+                    if attempt < CONFIG_WRITE_RETRIES =>
+                {
+                    attempt += 1;
+                    warn!("{e}, checking again ({attempt}/{CONFIG_WRITE_RETRIES})");
+                    tokio::time::sleep(CONFIG_WRITE_RETRY_DELAY).await;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// One attempt of [`upload_config`](Self::upload_config). should not be
+    /// used outside this mod.
+    async fn upload_config_once(&self, app: &AppHandle, safe: bool) -> Result<UploadConfigStatus> {
         // Local Clean Check
         let local_config = CONFIG.lock().clone();
-        let local_last_sync = local_config.last_sync.unwrap_or_default();
-        if safe && local_config.last_updated <= local_last_sync {
+        if safe && !local_config.is_dirty() {
             warn!(
-                "Local clean (updated {} <= sync {}), skip upload",
-                local_config.last_updated, local_last_sync
+                "Local clean (revision {:?} seen by sync {:?}), skip upload",
+                local_config.revision, local_config.synced_revision
             );
             return Ok(UploadConfigStatus::LocalClean);
         }
 
-        let remote_config = self.get_remote_config().await?;
+        let (remote_config, version) = self.get_remote_config_versioned().await?;
         if let Some(remote_config) = remote_config {
-            // Remote Newer Check
-            if safe && remote_config.last_updated > local_last_sync {
-                warn!(
-                    "Conflict detected! Remote ({}) > Local Base Sync ({}). Please pull first.",
-                    remote_config.last_updated, local_last_sync
+            // Remote Newer Check: both sides changed, merge them
+            if safe
+                && !remote_config
+                    .revision
+                    .seen_by(&local_config.synced_revision)
+            {
+                info!(
+                    "Remote ({:?}) not seen by Local Base Sync ({:?}), merging.",
+                    remote_config.revision, local_config.synced_revision
                 );
-                return Ok(UploadConfigStatus::Conflict);
+                let outcome = self
+                    .merge_with(
+                        app,
+                        local_config,
+                        remote_config,
+                        &version,
+                        &Default::default(),
+                    )
+                    .await?;
+                return Ok(if outcome.applied {
+                    UploadConfigStatus::Merged
+                } else {
+                    UploadConfigStatus::Conflict
+                });
             }
 
             // Games times check
@@ -158,23 +322,105 @@ pub trait MyOperation {
             info!("remote config is null, uploading");
         }
 
-        self.upload_config_inner(CONFIG_FILENAME).await?;
+        // the snapshot that was checked, not the live config
+        self.upload_config_inner(local_config.clone(), safe.then_some(&version))
+            .await?;
         info!("upload config success");
         // Update local last_sync
         {
             let mut locked_config = CONFIG.lock();
             locked_config.last_sync = Some(local_config.last_updated);
+            locked_config.synced_revision = local_config.revision.clone();
             locked_config.save_and_emit_no_update(app)?;
         }
+        if let Err(e) = store_base_config(&local_config) {
+            warn!("failed to store base config: {e}");
+        }
         Ok(UploadConfigStatus::Uploaded)
     }
 
+    /// Three-way merge the remote config into the local one, using the config
+    /// of the last sync as base. If nothing conflicts (after applying
+    /// `resolutions`), the merged config is applied locally and uploaded.
+    async fn merge_remote_config(
+        &self,
+        app: &AppHandle,
+        resolutions: &ConflictResolutions,
+    ) -> Result<MergeOutcome> {
+        let local_config = CONFIG.lock().clone();
+        let (Some(remote_config), version) = self.get_remote_config_versioned().await? else {
+            info!("remote config is null, nothing to merge");
+            self.upload_config(app, false).await?;
+            return Ok(MergeOutcome {
+                applied: true,
+                conflicts: vec![],
+            });
+        };
+        self.merge_with(app, local_config, remote_config, &version, resolutions)
+            .await
+    }
+
+    /// Merge `remote_config` into `local_config` and upload the result, if
+    /// the remote config is still of `version`. Only then is the result
+    /// stored locally, merged again with what was written to the local config
+    /// in the meantime. Should not be used outside this mod.
+    async fn merge_with(
+        &self,
+        app: &AppHandle,
+        local_config: Config,
+        remote_config: Config,
+        version: &ConfigVersion,
+        resolutions: &ConflictResolutions,
+    ) -> Result<MergeOutcome> {
+        let base = merge_base(&local_config, &remote_config);
+        let MergeResult {
+            mut merged,
+            conflicts,
+        } = merge_config(&base, &local_config, &remote_config, resolutions);
+        if !conflicts.is_empty() {
+            warn!(
+                "{} conflict(s) between local and remote config, please resolve them first",
+                conflicts.len()
+            );
+            return Ok(MergeOutcome {
+                applied: false,
+                conflicts,
+            });
+        }
+
+        keep_local_secrets(&mut merged.settings.storage, &local_config.settings.storage);
+        info!("merge diff:\n{}", utils::diff(&local_config, &merged));
+        merged.touch();
+        self.upload_config_inner(merged.clone(), Some(version))
+            .await?;
+        info!("upload merged config success");
+        {
+            let mut locked_config = CONFIG.lock();
+            // Local writes since `local_config` (e.g. play time) are changes
+            // on top of the uploaded config. They win if they touch the same
+            // fields as the merge, and are uploaded with the next sync.
+            let mut current =
+                merge_config(&local_config, &locked_config, &merged, &Default::default()).merged;
+            current.last_sync = Some(merged.last_updated);
+            current.synced_revision = merged.revision.clone();
+            *locked_config = current;
+            locked_config.save_and_emit_no_update(app)?;
+        }
+        if let Err(e) = store_base_config(&merged) {
+            warn!("failed to store base config: {e}");
+        }
+        Ok(MergeOutcome {
+            applied: true,
+            conflicts: vec![],
+        })
+    }
+
     /// Apply remote config to local config
     ///
     /// # Parameters
     ///
-    /// - safe=true: will not apply config if local is dirty or remote config is
-    ///   older
+    /// - safe=true: will not apply config if remote config is older, and merges
+    ///   with it if local is dirty
     ///
     /// # Returns
     ///
@@ -193,22 +439,53 @@ pub trait MyOperation {
         };
 
         let mut local_config = CONFIG.lock();
-        let local_last_sync = local_config.last_sync.unwrap_or_default();
+        let remote_seen = remote_config
+            .revision
+            .seen_by(&local_config.synced_revision);
 
-        // Local Clean Check
-        if safe && local_config.last_updated > local_last_sync {
-            warn!(
-                "Local dirty (updated {} > sync {}), cannot overwrite. Please upload or revert first.",
-                local_config.last_updated, local_last_sync
-            );
-            return Ok((None, false));
+        // Local Clean Check: if both sides changed, merge them instead of
+        // overwriting
+        if safe && local_config.is_dirty() {
+            if remote_seen {
+                warn!(
+                    "Local dirty (revision {:?} not seen by sync {:?}) and remote not newer, skip download.",
+                    local_config.revision, local_config.synced_revision
+                );
+                return Ok((None, false));
+            }
+            let base = merge_base(&local_config, &remote_config);
+            let MergeResult {
+                mut merged,
+                conflicts,
+            } = merge_config(&base, &local_config, &remote_config, &Default::default());
+            if !conflicts.is_empty() {
+                warn!(
+                    "Local dirty and {} conflict(s) with remote, cannot apply. Please resolve them first.",
+                    conflicts.len()
+                );
+                return Ok((None, false));
+            }
+
+            info!("Applying merged remote config...");
+            backup_before_apply(&local_config);
+            keep_local_secrets(&mut merged.settings.storage, &local_config.settings.storage);
+            // Local changes in `merged` are not uploaded yet, so keep it dirty.
+            merged.touch();
+            merged.last_sync = Some(remote_config.last_updated);
+            merged.synced_revision = remote_config.revision.clone();
+            let old = std::mem::replace(&mut *local_config, merged);
+            local_config.save_and_emit_no_update(app)?;
+            if let Err(e) = store_base_config(&remote_config) {
+                warn!("failed to store base config: {e}");
+            }
+            return Ok((Some(old), false));
         }
 
         // Remote Newer Check
-        if safe && remote_config.last_updated <= local_last_sync {
+        if safe && remote_seen {
             warn!(
-                "Remote not newer (remote {} <= sync {}), skip download.",
-                remote_config.last_updated, local_last_sync
+                "Remote not newer (remote {:?} seen by sync {:?}), skip download.",
+                remote_config.revision, local_config.synced_revision
             );
             return Ok((None, false));
         }
@@ -219,15 +496,31 @@ pub trait MyOperation {
         }
 
         info!("Applying remote config...");
+        backup_before_apply(&local_config);
 
         let mut new_config = remote_config.clone();
         new_config.last_sync = Some(remote_config.last_updated);
+        new_config.synced_revision = remote_config.revision.clone();
+        keep_local_secrets(
+            &mut new_config.settings.storage,
+            &local_config.settings.storage,
+        );
         let old = std::mem::replace(&mut *local_config, new_config);
         local_config.save_and_emit_no_update(app)?;
+        if let Err(e) = store_base_config(&remote_config) {
+            warn!("failed to store base config: {e}");
+        }
         Ok((Some(old), false))
     }
 }
 
+/// Applying a remote config must not fail because of a backup.
+fn backup_before_apply(config: &Config) {
+    if let Err(e) = backup_config(config, BackupReason::Remote) {
+        warn!("failed to back up config before applying remote config: {e}");
+    }
+}
+
 pub trait BuildOperator {
     type CTX;
     fn get_operator(&self) -> Option<Box<dyn MyOperation + Send + Sync>>;
@@ -319,7 +612,9 @@ impl BuildOperator for WebDavConfig {
             services::Webdav::default()
                 .endpoint(&self.endpoint)
                 .username(&self.username)
-                .password(self.password.as_deref().unwrap_or_default())
+                .password(&secrets::resolve(
+                    self.password.as_deref().unwrap_or_default(),
+                )?)
                 .root(&self.root_path),
         )?
         .layer(
@@ -372,8 +667,8 @@ impl BuildOperator for S3Config {
         let operator = Operator::new(
             services::S3::default()
                 .bucket(&self.bucket)
-                .access_key_id(&self.access_key)
-                .secret_access_key(&self.secret_key),
+                .access_key_id(&secrets::resolve(&self.access_key)?)
+                .secret_access_key(&secrets::resolve(&self.secret_key)?),
         )?
         .layer(
             TimeoutLayer::new()
@@ -402,9 +697,13 @@ impl BuildOperator for S3Config {
 mod tests {
     use std::fs;
 
+    use chrono::Utc;
     use tempfile::tempdir;
 
     use super::*;
+    use crate::archive::{
+        ArchiveAlgo, ArchiveMeta, ArchiveTrigger, read_meta, remove_meta, write_meta,
+    };
 
     #[tokio::test]
     async fn test_local_operator_basics() -> Result<()> {
@@ -421,6 +720,19 @@ mod tests {
         let src_archive = src_path.join(game_id.to_string()).join(archive_filename);
         fs::create_dir(src_archive.parent().unwrap())?;
         fs::write(&src_archive, "test")?;
+        let meta = ArchiveMeta {
+            created_at: Utc::now(),
+            device_uid: "uid".to_string(),
+            device_name: "pc".to_string(),
+            algorithm: ArchiveAlgo::Tar,
+            use_time: chrono::Duration::seconds(60),
+            trigger: ArchiveTrigger::Manual,
+            note: None,
+            pinned: false,
+            fingerprint: None,
+            content_hash: None,
+        };
+        write_meta(&src_archive, &meta)?;
 
         let local_conf = LocalConfig {
             path: remote_path.to_string_lossy().to_string(),
@@ -445,16 +757,50 @@ mod tests {
             ls,
             vec![ArchiveInfo {
                 name: archive_filename.to_string(),
-                size: 4
+                size: 4,
+                format: None,
+                meta: Some(meta.clone()),
             }]
         );
 
+        // note
+        op.set_archive_note(game_id, archive_filename, Some("boss".to_string()))
+            .await?;
+        let ls = op.list_archive(game_id).await?;
+        assert_eq!(ls[0].meta.as_ref().unwrap().note.as_deref(), Some("boss"));
+
+        // save history, which is not an archive
+        assert_eq!(op.save_history(game_id).await?, SaveHistory::default());
+        let record = history::SyncRecord::of_local("pc", &src_archive)?;
+        history::record_upload(&*op, game_id, record.clone()).await?;
+        assert_eq!(op.save_history(game_id).await?.head, Some(record));
+        assert_eq!(op.list_archive(game_id).await?, ls);
+
         // pull
         fs::remove_file(&src_archive)?;
+        remove_meta(&src_archive)?;
         op.pull_archive(game_id, archive_filename, src_path)
             .await
             .unwrap();
         assert_eq!(fs::read_to_string(&src_archive)?, "test");
+        assert_eq!(read_meta(&src_archive)?, ls[0].meta);
+        assert_eq!(
+            op.check_archive(game_id, archive_filename).await?,
+            ChecksumStatus::Ok
+        );
+
+        // truncated on remote
+        let remote_archive = remote_path.join("1").join(archive_filename);
+        fs::write(&remote_archive, "tes")?;
+        assert_eq!(
+            op.check_archive(game_id, archive_filename).await?,
+            ChecksumStatus::Mismatch
+        );
+        assert!(matches!(
+            op.pull_archive(game_id, archive_filename, src_path).await,
+            Err(Error::ChecksumMismatch(_))
+        ));
+        assert!(!src_archive.exists());
 
         op.delete_archive(game_id, archive_filename).await.unwrap();
         assert!(!remote_path.join("test").join("1").exists());
@@ -462,6 +808,209 @@ mod tests {
         Ok(())
     }
 
+    fn config_in(layout: RemoteLayout) -> Config {
+        let mut config = Config::default();
+        config.settings.storage.layout = layout;
+        config.games.push(crate::db::Game {
+            id: 1,
+            name: "game".to_string(),
+            ..Default::default()
+        });
+        config
+    }
+
+    #[tokio::test]
+    async fn test_conditional_config_write() -> Result<()> {
+        for layout in [RemoteLayout::Single, RemoteLayout::Split] {
+            let remote_dir = tempdir()?;
+            let op = LocalConfig {
+                path: remote_dir.path().to_string_lossy().to_string(),
+                ..Default::default()
+            }
+            .get_operator_or_init(
+                &Default::default(),
+                DEFAULT_IO_TIMEOUT,
+                DEFAULT_NON_IO_TIMEOUT,
+            )?;
+            let config = config_in(layout);
+
+            // missing config is created once
+            let (remote, missing) = op.get_remote_config_versioned().await?;
+            assert!(remote.is_none());
+            op.upload_config_inner(config.clone(), Some(&missing))
+                .await?;
+            assert!(matches!(
+                op.upload_config_inner(config.clone(), Some(&missing)).await,
+                Err(Error::RemoteConfigChanged)
+            ));
+
+            // a checked version can be written over once
+            let (remote, version) = op.get_remote_config_versioned().await?;
+            assert_eq!(version.layout, layout);
+            assert_eq!(remote.unwrap().games[0].name, "game");
+            let mut changed = config.clone();
+            changed.games[0].name = "changed".to_string();
+            op.upload_config_inner(changed, Some(&version)).await?;
+            assert!(matches!(
+                op.upload_config_inner(config.clone(), Some(&version)).await,
+                Err(Error::RemoteConfigChanged)
+            ));
+            assert!(!remote_dir.path().join(lease::LEASE_FILENAME).exists());
+            let (remote, _) = op.get_remote_config_versioned().await?;
+            assert_eq!(remote.unwrap().games[0].name, "changed");
+
+            // unconditional writes always go through
+            op.upload_config_inner(config, None).await?;
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_single_write_keeps_concurrent_split_write() -> Result<()> {
+        let remote_dir = tempdir()?;
+        let op = LocalConfig {
+            path: remote_dir.path().to_string_lossy().to_string(),
+            ..Default::default()
+        }
+        .get_operator_or_init(
+            &Default::default(),
+            DEFAULT_IO_TIMEOUT,
+            DEFAULT_NON_IO_TIMEOUT,
+        )?;
+        let (_, missing) = op.get_remote_config_versioned().await?;
+        // another device moves the remote to the split layout meanwhile
+        let mut split = config_in(RemoteLayout::Split);
+        split.games[0].name = "split".to_string();
+        op.upload_config_inner(split, Some(&missing)).await?;
+
+        assert!(matches!(
+            op.upload_config_inner(config_in(RemoteLayout::Single), Some(&missing))
+                .await,
+            Err(Error::RemoteConfigChanged)
+        ));
+        let (remote, version) = op.get_remote_config_versioned().await?;
+        assert_eq!(version.layout, RemoteLayout::Split);
+        assert_eq!(remote.unwrap().games[0].name, "split");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_config_layout_migration() -> Result<()> {
+        let remote_dir = tempdir()?;
+        let op = LocalConfig {
+            path: remote_dir.path().to_string_lossy().to_string(),
+            ..Default::default()
+        }
+        .get_operator_or_init(
+            &Default::default(),
+            DEFAULT_IO_TIMEOUT,
+            DEFAULT_NON_IO_TIMEOUT,
+        )?;
+        let single = remote_dir.path().join(crate::db::CONFIG_FILENAME);
+        let index = remote_dir.path().join(layout::INDEX_PATH);
+
+        op.upload_config_inner(config_in(RemoteLayout::Single), None)
+            .await?;
+        assert!(single.exists());
+
+        // to the split layout
+        let (remote, version) = op.get_remote_config_versioned().await?;
+        let mut config = remote.unwrap();
+        config.settings.storage.layout = RemoteLayout::Split;
+        op.upload_config_inner(config, Some(&version)).await?;
+        assert!(!single.exists());
+        assert!(index.exists());
+        assert!(remote_dir.path().join("library/games/1.toml").exists());
+
+        // and back
+        let (remote, version) = op.get_remote_config_versioned().await?;
+        assert_eq!(version.layout, RemoteLayout::Split);
+        let mut config = remote.unwrap();
+        assert_eq!(config.games[0].name, "game");
+        config.settings.storage.layout = RemoteLayout::Single;
+        op.upload_config_inner(config, Some(&version)).await?;
+        assert!(single.exists());
+        assert!(!remote_dir.path().join("library").exists());
+        let (remote, version) = op.get_remote_config_versioned().await?;
+        assert_eq!(version.layout, RemoteLayout::Single);
+        assert_eq!(remote.unwrap().games[0].name, "game");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_config_snapshots() -> Result<()> {
+        let remote_dir = tempdir()?;
+        let op = LocalConfig {
+            path: remote_dir.path().to_string_lossy().to_string(),
+            ..Default::default()
+        }
+        .get_operator_or_init(
+            &Default::default(),
+            DEFAULT_IO_TIMEOUT,
+            DEFAULT_NON_IO_TIMEOUT,
+        )?;
+        for (name, game) in [
+            ("config_20240101.toml", "old"),
+            ("config_20240102.toml", "new"),
+        ] {
+            let mut config = config_in(RemoteLayout::Single);
+            config.games[0].name = game.to_string();
+            fs::write(remote_dir.path().join(name), toml::to_string(&config)?)?;
+        }
+        fs::write(remote_dir.path().join("config.toml"), "")?;
+
+        let snapshots = op.list_config_snapshots().await?;
+        assert_eq!(
+            snapshots
+                .iter()
+                .map(|s| s.name.as_str())
+                .collect::<Vec<_>>(),
+            ["config_20240102.toml", "config_20240101.toml"]
+        );
+        let config = op.get_config_snapshot("config_20240101.toml").await?;
+        assert_eq!(config.games[0].name, "old");
+        assert!(matches!(
+            op.get_config_snapshot("config.toml").await,
+            Err(Error::InvalidPath)
+        ));
+
+        assert!(op.prune_config_snapshots(0).await?.is_empty());
+        assert_eq!(
+            op.prune_config_snapshots(1).await?,
+            ["config_20240101.toml"]
+        );
+        assert_eq!(op.list_config_snapshots().await?.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_recent_chunks_survive_gc() -> Result<()> {
+        let remote_dir = tempdir()?;
+        let op = LocalConfig {
+            path: remote_dir.path().to_string_lossy().to_string(),
+            ..Default::default()
+        }
+        .get_operator_or_init(
+            &Default::default(),
+            DEFAULT_IO_TIMEOUT,
+            DEFAULT_NON_IO_TIMEOUT,
+        )?;
+        let chunk_dir = remote_dir.path().join("1").join(crate::archive::CHUNKS_DIR);
+        fs::create_dir_all(&chunk_dir)?;
+        fs::write(chunk_dir.join("old"), "old")?;
+        fs::File::options()
+            .write(true)
+            .open(chunk_dir.join("old"))?
+            .set_modified(std::time::SystemTime::now() - Duration::from_secs(2 * 60 * 60))?;
+        // e.g. uploaded by another device whose manifest is not written yet
+        fs::write(chunk_dir.join("new"), "new")?;
+
+        op.delete_archive(1, "1.snapshot").await?;
+        assert!(!chunk_dir.join("old").exists());
+        assert!(chunk_dir.join("new").exists());
+        Ok(())
+    }
+
     async fn test_big_file(op: &(impl MyOperation + Send + Sync + ?Sized)) -> Result<()> {
         let game_id = 1;
         let archive_filename = "big_file.tar";
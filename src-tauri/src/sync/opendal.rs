@@ -1,16 +1,32 @@
-use std::path::Path;
+use std::{collections::HashSet, io, path::Path, sync::LazyLock, time::SystemTime};
 
 use chrono::Utc;
 use futures::{AsyncWriteExt, TryStreamExt as _};
-use log::info;
-use opendal::Operator;
+use log::{info, warn};
+use opendal::{Entry, Operator};
+use parking_lot::Mutex;
+use tempfile::NamedTempFile;
 use tokio::fs;
 use tokio_util::compat::TokioAsyncReadCompatExt;
 
+use super::{
+    ConfigVersion, crypto,
+    history::{HISTORY_FILENAME, SaveHistory},
+    layout::{INDEX_PATH, LIBRARY_DIR, LibraryIndex, SplitConfig},
+    lease,
+    snapshot::{self, ConfigSnapshot, parse_snapshot_name, snapshot_name},
+};
 use crate::{
-    archive::ArchiveInfo,
-    db::{CONFIG, CONFIG_FILENAME, Config},
-    error::Result,
+    archive::{
+        ArchiveInfo, ArchiveMeta, CHUNK_GC_GRACE, CHUNKS_DIR, ChecksumStatus, SnapshotManifest,
+        checksum_filename, chunk_hash, format_checksum, is_sidecar, is_snapshot, meta_filename,
+        parse_checksum, read_checksum, read_meta, remove_checksum, remove_meta, sha256_of,
+        write_checksum, write_chunk, write_meta,
+    },
+    db::{
+        CONFIG, CONFIG_FILENAME, Config, merge::load_base_config, migrate, settings::RemoteLayout,
+    },
+    error::{Error, Result},
 };
 
 // https://t.me/withabsolutex/2598
@@ -18,6 +34,667 @@ const WRITER_MAX_BUFFER_SIZE: usize = 1024 * 1024 * 1024 * 1024;
 
 const WRITER_NORMAL_CHUNK_SIZE: usize = 4 * 1024 * 1024;
 
+/// Encrypted [`crypto::key_check`] in the remote root.
+const KEY_CHECK_FILENAME: &str = "encryption.check";
+
+/// Remotes whose key check passed in this session, by remote and passphrase.
+static KEY_CHECKED: LazyLock<Mutex<HashSet<(String, String)>>> = LazyLock::new(Default::default);
+
+/// Passphrase to encrypt uploads to `op` with, `None` if encryption is
+/// disabled. Fails if the remote is encrypted with another passphrase.
+async fn upload_passphrase(op: &Operator) -> Result<Option<String>> {
+    let passphrase = CONFIG
+        .lock()
+        .settings
+        .storage
+        .encryption
+        .upload_passphrase()?;
+    if let Some(passphrase) = &passphrase {
+        check_passphrase(op, passphrase).await?;
+    }
+    Ok(passphrase)
+}
+
+/// Verify `passphrase` against the key check of the remote, once per session.
+/// A remote without one gets it.
+async fn check_passphrase(op: &Operator, passphrase: &str) -> Result<()> {
+    let info = op.info();
+    let id = (
+        format!("{}://{}{}", info.scheme(), info.name(), info.root()),
+        passphrase.to_string(),
+    );
+    if KEY_CHECKED.lock().contains(&id) {
+        return Ok(());
+    }
+    match op.read(KEY_CHECK_FILENAME).await {
+        Ok(data) => crypto::verify_key_check(passphrase, &data.to_vec())?,
+        Err(e) if e.kind() == opendal::ErrorKind::NotFound => {
+            op.write(KEY_CHECK_FILENAME, crypto::key_check(passphrase)?)
+                .await?;
+        }
+        Err(e) => return Err(e.into()),
+    }
+    KEY_CHECKED.lock().insert(id);
+    Ok(())
+}
+
+/// Passphrase to decrypt encrypted downloads with.
+#[inline]
+fn download_passphrase() -> Result<String> {
+    CONFIG
+        .lock()
+        .settings
+        .storage
+        .encryption
+        .download_passphrase()
+}
+
+/// Stream the local file at `path` to `remote_path`.
+async fn write_file(op: &Operator, remote_path: &str, path: &Path) -> Result<()> {
+    let uploader = op
+        .writer_with(remote_path)
+        .chunk(if super::MyOperation::chunkable(op) {
+            WRITER_NORMAL_CHUNK_SIZE
+        } else {
+            WRITER_MAX_BUFFER_SIZE
+        })
+        .await?;
+    let mut writer = uploader.into_futures_async_write();
+    let file = fs::File::open(path).await?;
+    futures::io::copy(file.compat(), &mut writer).await?;
+    writer.close().await?;
+    Ok(())
+}
+
+/// Decrypt the archive at `path` in place if it was uploaded encrypted. The
+/// archive is removed if it cannot be decrypted.
+fn decrypt_archive_in_place(path: &Path) -> Result<()> {
+    if !crypto::is_encrypted_file(path)? {
+        return Ok(());
+    }
+    let part = path.with_file_name(format!(
+        ".{}.part",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let res = download_passphrase()
+        .and_then(|passphrase| crypto::decrypt_file(&passphrase, path, &part))
+        .and_then(|()| std::fs::rename(&part, path).map_err(Into::into));
+    if res.is_err() {
+        _ = std::fs::remove_file(&part);
+        _ = std::fs::remove_file(path);
+    }
+    res
+}
+
+/// Stream `remote_path` to the local file at `path`, decrypting it in place
+/// if it was uploaded encrypted.
+async fn read_file(op: &Operator, remote_path: &str, path: &Path) -> Result<()> {
+    let downloader = op
+        .reader_with(remote_path)
+        .chunk(4 * 1024 * 1024)
+        .concurrent(8)
+        .await?;
+    let file = fs::File::create(path).await?;
+    futures::io::copy(
+        downloader.into_futures_async_read(..).await?,
+        &mut file.compat(),
+    )
+    .await?;
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || decrypt_archive_in_place(&path))
+        .await
+        .map_err(io::Error::other)??;
+    Ok(())
+}
+
+/// Upload the checksum sidecar of the local archive at `archive_path`,
+/// computing it first if there is none.
+async fn upload_checksum(
+    op: &Operator,
+    remote_path: &str,
+    archive_path: &Path,
+    passphrase: Option<&str>,
+) -> Result<()> {
+    let path = archive_path.to_path_buf();
+    let checksum = tokio::task::spawn_blocking(move || match read_checksum(&path)? {
+        Some(checksum) => Ok(checksum),
+        None => write_checksum(&path),
+    })
+    .await
+    .map_err(io::Error::other)??;
+    let name = archive_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let content = format_checksum(&checksum, &name).into_bytes();
+    let content = match passphrase {
+        Some(passphrase) => crypto::encrypt_bytes(passphrase, &content)?,
+        None => content,
+    };
+    op.write(&checksum_filename(remote_path), content).await?;
+    Ok(())
+}
+
+/// The checksum of the remote archive at `remote_path`, `None` if it has
+/// none.
+async fn remote_checksum(op: &Operator, remote_path: &str) -> Result<Option<String>> {
+    let mut data = match op.read(&checksum_filename(remote_path)).await {
+        Ok(b) => b.to_vec(),
+        Err(e) if e.kind() == opendal::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    if crypto::is_encrypted(&data) {
+        data = crypto::decrypt_bytes(&download_passphrase()?, &data)?;
+    }
+    Ok(parse_checksum(&String::from_utf8_lossy(&data)))
+}
+
+/// SHA-256 of the raw content at `remote_path`, `None` if it does not exist.
+async fn remote_hash(op: &Operator, remote_path: &str) -> Result<Option<String>> {
+    match op.read(remote_path).await {
+        Ok(buf) => Ok(Some(sha256_of(buf.to_vec().as_slice())?)),
+        Err(e) if e.kind() == opendal::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Hash of the object the remote config is read from, see
+/// [`ConfigVersion::hash`].
+async fn head_hash(op: &Operator) -> Result<Option<String>> {
+    match remote_hash(op, INDEX_PATH).await? {
+        Some(hash) => Ok(Some(hash)),
+        None => remote_hash(op, CONFIG_FILENAME).await,
+    }
+}
+
+/// Run `write` under the [lease](super::lease), for backends that cannot
+/// write the config conditionally and for writes of several objects. Fails
+/// with [`Error::RemoteConfigChanged`] unless the remote config is still of
+/// `expected`, if given.
+async fn write_leased(
+    op: &Operator,
+    expected: Option<&ConfigVersion>,
+    write: impl Future<Output = Result<()>>,
+) -> Result<()> {
+    let lease = lease::acquire(op).await?;
+    let res = async {
+        if let Some(expected) = expected
+            && head_hash(op).await? != expected.hash
+        {
+            return Err(Error::RemoteConfigChanged);
+        }
+        write.await
+    }
+    .await;
+    lease::release(op, &lease).await;
+    res
+}
+
+#[inline]
+fn encrypt_with(passphrase: Option<&str>, content: Vec<u8>) -> Result<Vec<u8>> {
+    match passphrase {
+        Some(passphrase) => crypto::encrypt_bytes(passphrase, &content),
+        None => Ok(content),
+    }
+}
+
+/// The raw content at `remote_path` and its version, `None` if it does not
+/// exist.
+async fn read_versioned(
+    op: &Operator,
+    remote_path: &str,
+    layout: RemoteLayout,
+) -> Result<Option<(Vec<u8>, ConfigVersion)>> {
+    let etag = match op.stat(remote_path).await {
+        Ok(meta) => meta.etag().map(str::to_string),
+        Err(e) if e.kind() == opendal::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut reader = op
+        .reader_with(remote_path)
+        .chunk(4 * 1024 * 1024)
+        .concurrent(8);
+    if let Some(etag) = &etag
+        && op.info().full_capability().read_with_if_match
+    {
+        reader = reader.if_match(etag);
+    }
+
+    let buf = match reader.await?.read(..).await {
+        Ok(b) => b,
+        Err(e) if e.kind() == opendal::ErrorKind::NotFound => return Ok(None),
+        // changed since the stat
+        Err(e) if e.kind() == opendal::ErrorKind::ConditionNotMatch => {
+            return Err(Error::RemoteConfigChanged);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let bytes = buf.to_vec();
+    let version = ConfigVersion {
+        etag,
+        hash: Some(sha256_of(bytes.as_slice())?),
+        layout,
+    };
+    Ok(Some((bytes, version)))
+}
+
+#[inline]
+fn decrypt_download(bytes: Vec<u8>) -> Result<Vec<u8>> {
+    if crypto::is_encrypted(&bytes) {
+        crypto::decrypt_bytes(&download_passphrase()?, &bytes)
+    } else {
+        Ok(bytes)
+    }
+}
+
+async fn read_index(op: &Operator) -> Result<Option<(LibraryIndex, ConfigVersion)>> {
+    let Some((bytes, version)) = read_versioned(op, INDEX_PATH, RemoteLayout::Split).await? else {
+        return Ok(None);
+    };
+    let index = toml::from_slice(&decrypt_download(bytes)?)?;
+    Ok(Some((index, version)))
+}
+
+/// Objects of the local config and of the config of the last sync, which
+/// need not be downloaded again.
+fn known_objects() -> Vec<SplitConfig> {
+    let local = CONFIG.lock().clone();
+    [Some(local), load_base_config()]
+        .into_iter()
+        .flatten()
+        .filter_map(|config| SplitConfig::new(&config).ok())
+        .collect()
+}
+
+async fn read_split(op: &Operator, index: LibraryIndex) -> Result<Config> {
+    let known = known_objects();
+    let mut split = SplitConfig {
+        index,
+        objects: Default::default(),
+    };
+    for (path, hash) in &split.index.objects {
+        let content = match known.iter().find_map(|k| k.get_if(path, hash)) {
+            Some(content) => content.to_string(),
+            None => match op.read(path).await {
+                Ok(buf) => String::from_utf8_lossy(&decrypt_download(buf.to_vec())?).into_owned(),
+                // removed by a write that is not finished yet
+                Err(e) if e.kind() == opendal::ErrorKind::NotFound => {
+                    return Err(Error::RemoteConfigChanged);
+                }
+                Err(e) => return Err(e.into()),
+            },
+        };
+        split.objects.insert(path.clone(), content);
+    }
+    split.assemble()
+}
+
+/// Write the config as a single file, then remove the split layout if the
+/// remote was in it.
+///
+/// The split layout is only removed under the lease, after checking that its
+/// index is the one `expected` was read from, or by unconditional writes. An
+/// index showing up after a conditional write of the single file belongs to
+/// a concurrent write in the split layout, which wins.
+async fn write_single(
+    op: &Operator,
+    content: Vec<u8>,
+    expected: Option<&ConfigVersion>,
+) -> Result<()> {
+    let capability = op.info().full_capability();
+    let res = match expected {
+        None => {
+            let mut uploader = op
+                .writer_with(CONFIG_FILENAME)
+                .chunk(if super::MyOperation::chunkable(op) {
+                    WRITER_NORMAL_CHUNK_SIZE
+                } else {
+                    WRITER_MAX_BUFFER_SIZE
+                })
+                .concurrent(8)
+                .await?;
+            uploader.write(content).await?;
+            uploader.close().await?;
+            return remove_split(op).await;
+        }
+        Some(expected) if expected.layout == RemoteLayout::Single => {
+            match (&expected.etag, &expected.hash) {
+                (Some(etag), _) if capability.write_with_if_match => op
+                    .write_with(CONFIG_FILENAME, content)
+                    .if_match(etag)
+                    .await
+                    .map(drop),
+                (_, None) if capability.write_with_if_not_exists => op
+                    .write_with(CONFIG_FILENAME, content)
+                    .if_not_exists(true)
+                    .await
+                    .map(drop),
+                _ => {
+                    return write_leased(op, Some(expected), async {
+                        op.write(CONFIG_FILENAME, content).await?;
+                        remove_split(op).await
+                    })
+                    .await;
+                }
+            }
+        }
+        Some(expected) => {
+            return write_leased(op, Some(expected), async {
+                op.write(CONFIG_FILENAME, content).await?;
+                remove_split(op).await
+            })
+            .await;
+        }
+    };
+    match res {
+        // there was no index when the config was read
+        Ok(()) if op.exists(INDEX_PATH).await? => Err(Error::RemoteConfigChanged),
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == opendal::ErrorKind::ConditionNotMatch => {
+            Err(Error::RemoteConfigChanged)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Remove the split layout, index first so that it is never read half
+/// removed.
+async fn remove_split(op: &Operator) -> Result<()> {
+    if op.exists(INDEX_PATH).await? {
+        info!("moving remote config to a single file");
+        op.delete(INDEX_PATH).await?;
+        op.delete_with(LIBRARY_DIR).recursive(true).await?;
+    }
+    Ok(())
+}
+
+/// Write the objects of `split` that changed, then the index, then remove
+/// the objects that are gone, and the single file if the remote was in that
+/// layout.
+async fn write_split(
+    op: &Operator,
+    mut split: SplitConfig,
+    expected: Option<&ConfigVersion>,
+) -> Result<()> {
+    let passphrase = upload_passphrase(op).await?;
+    split.index.encrypted = passphrase.is_some();
+    write_leased(op, expected, async {
+        let old = read_index(op)
+            .await?
+            .map(|(index, _)| index)
+            // objects are encrypted again if encryption was toggled
+            .filter(|old| old.encrypted == split.index.encrypted);
+        for (path, content) in split.changed(old.as_ref()) {
+            let content = encrypt_with(passphrase.as_deref(), content.as_bytes().to_vec())?;
+            op.write(path, content).await?;
+        }
+        let index = toml::to_string(&split.index)?.into_bytes();
+        op.write(INDEX_PATH, encrypt_with(passphrase.as_deref(), index)?)
+            .await?;
+        if let Some(old) = &old {
+            for path in old.removed(&split.index) {
+                op.delete(path).await?;
+            }
+        }
+        if op.exists(CONFIG_FILENAME).await? {
+            info!("moving remote config to one object per game");
+            op.delete(CONFIG_FILENAME).await?;
+        }
+        Ok(())
+    })
+    .await
+}
+
+/// Check the downloaded copy at `path` of the remote archive at
+/// `remote_path` against the remote checksum.
+async fn check_download(op: &Operator, remote_path: &str, path: &Path) -> Result<ChecksumStatus> {
+    let Some(expected) = remote_checksum(op, remote_path).await? else {
+        return Ok(ChecksumStatus::Missing);
+    };
+    let file = std::fs::File::open(path)?;
+    let actual = tokio::task::spawn_blocking(move || sha256_of(file))
+        .await
+        .map_err(io::Error::other)??;
+    Ok(if actual == expected {
+        ChecksumStatus::Ok
+    } else {
+        ChecksumStatus::Mismatch
+    })
+}
+
+/// Write the metadata sidecar of the remote archive at `remote_path`.
+async fn write_remote_meta(
+    op: &Operator,
+    remote_path: &str,
+    meta: &ArchiveMeta,
+    passphrase: Option<&str>,
+) -> Result<()> {
+    let content = meta.to_toml().into_bytes();
+    let content = match passphrase {
+        Some(passphrase) => crypto::encrypt_bytes(passphrase, &content)?,
+        None => content,
+    };
+    op.write(&meta_filename(remote_path), content).await?;
+    Ok(())
+}
+
+/// The metadata of the remote archive at `remote_path`, `None` if it has none
+/// or it cannot be parsed.
+async fn remote_meta(op: &Operator, remote_path: &str) -> Result<Option<ArchiveMeta>> {
+    let mut data = match op.read(&meta_filename(remote_path)).await {
+        Ok(b) => b.to_vec(),
+        Err(e) if e.kind() == opendal::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    if crypto::is_encrypted(&data) {
+        data = crypto::decrypt_bytes(&download_passphrase()?, &data)?;
+    }
+    match ArchiveMeta::from_toml(&data) {
+        Ok(meta) => Ok(Some(meta)),
+        Err(e) => {
+            warn!("invalid archive metadata of {remote_path} on remote: {e}");
+            Ok(None)
+        }
+    }
+}
+
+/// Update the metadata of a remote archive. Archives of older versions get
+/// their metadata from [`ArchiveMeta::legacy`] first.
+async fn update_remote_meta(
+    op: &Operator,
+    game_id: u32,
+    archive_filename: &str,
+    update: impl FnOnce(&mut ArchiveMeta),
+) -> Result<()> {
+    let remote_path = format!("{}/{}", game_id, archive_filename);
+    let mut meta = remote_meta(op, &remote_path)
+        .await?
+        .or_else(|| ArchiveMeta::legacy(archive_filename))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no metadata for archive {remote_path}"),
+            )
+        })?;
+    update(&mut meta);
+    write_remote_meta(
+        op,
+        &remote_path,
+        &meta,
+        upload_passphrase(op).await?.as_deref(),
+    )
+    .await
+}
+
+/// Fill in the metadata of the listed remote archives of a game, falling back
+/// to [`ArchiveMeta::legacy`].
+async fn fill_remote_meta(op: &Operator, game_id: u32, archives: &mut [ArchiveInfo]) -> Result<()> {
+    for archive in archives {
+        archive.meta = remote_meta(op, &format!("{game_id}/{}", archive.name))
+            .await?
+            .or_else(|| ArchiveMeta::legacy(&archive.name));
+    }
+    Ok(())
+}
+
+/// Remote dir holding the snapshot chunks of a game.
+#[inline]
+fn remote_chunk_dir(game_id: u32) -> String {
+    format!("{game_id}/{CHUNKS_DIR}/")
+}
+
+/// Names of the snapshot chunks of a game that are already on remote.
+async fn list_remote_chunks(op: &Operator, game_id: u32) -> Result<HashSet<String>> {
+    let entries = match op.list(&remote_chunk_dir(game_id)).await {
+        Ok(e) => e,
+        Err(e) if e.kind() == opendal::ErrorKind::NotFound => return Ok(HashSet::new()),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(entries
+        .into_iter()
+        .filter(|e| e.metadata().is_file())
+        .map(|e| e.name().to_string())
+        .collect())
+}
+
+/// Upload the chunks of the local snapshot at `manifest_path` that are not on
+/// remote yet.
+async fn upload_chunks(
+    op: &Operator,
+    game_id: u32,
+    manifest_path: &Path,
+    passphrase: Option<&str>,
+) -> Result<()> {
+    let manifest = SnapshotManifest::from_str(&fs::read_to_string(manifest_path).await?)?;
+    let chunk_dir = manifest_path.with_file_name(CHUNKS_DIR);
+    let existing = list_remote_chunks(op, game_id).await?;
+    let chunks = manifest.chunks();
+    let missing: Vec<&str> = chunks
+        .iter()
+        .copied()
+        .filter(|hash| !existing.contains(*hash))
+        .collect();
+    info!(
+        "uploading {} of {} chunk(s) for game {game_id}",
+        missing.len(),
+        chunks.len()
+    );
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    op.create_dir(&remote_chunk_dir(game_id)).await?;
+    for hash in missing {
+        let data = fs::read(chunk_dir.join(hash)).await?;
+        let data = match passphrase {
+            Some(passphrase) => {
+                let passphrase = passphrase.to_string();
+                tokio::task::spawn_blocking(move || crypto::encrypt_bytes(&passphrase, &data))
+                    .await
+                    .map_err(io::Error::other)??
+            }
+            None => data,
+        };
+        op.write(&format!("{}{hash}", remote_chunk_dir(game_id)), data)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Download the chunks of the local snapshot at `manifest_path` that are not
+/// present locally, verifying their hashes.
+async fn pull_chunks(op: &Operator, game_id: u32, manifest_path: &Path) -> Result<()> {
+    let manifest = SnapshotManifest::from_str(&fs::read_to_string(manifest_path).await?)?;
+    let chunk_dir = manifest_path.with_file_name(CHUNKS_DIR);
+    fs::create_dir_all(&chunk_dir).await?;
+    for hash in manifest.chunks() {
+        let path = chunk_dir.join(hash);
+        if fs::try_exists(&path).await? {
+            continue;
+        }
+        let mut data = op
+            .read(&format!("{}{hash}", remote_chunk_dir(game_id)))
+            .await?
+            .to_vec();
+        if crypto::is_encrypted(&data) {
+            let passphrase = download_passphrase()?;
+            data = tokio::task::spawn_blocking(move || crypto::decrypt_bytes(&passphrase, &data))
+                .await
+                .map_err(io::Error::other)??;
+        }
+        if chunk_hash(&data) != hash {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("corrupted chunk on remote: {hash}"),
+            )
+            .into());
+        }
+        write_chunk(&path, &data)?;
+    }
+    Ok(())
+}
+
+/// Whether the remote chunk `entry` was written within [`CHUNK_GC_GRACE`].
+/// Chunks of unknown age count as recent.
+async fn is_recent_chunk(op: &Operator, entry: &Entry) -> Result<bool> {
+    let modified = match entry.metadata().last_modified() {
+        Some(modified) => Some(modified),
+        None => op.stat(entry.path()).await?.last_modified(),
+    };
+    Ok(modified.is_none_or(|modified| {
+        SystemTime::from(modified)
+            .elapsed()
+            .is_ok_and(|age| age < CHUNK_GC_GRACE)
+    }))
+}
+
+/// Delete the chunks on remote that no snapshot of the game refers to
+/// anymore, except the ones younger than [`CHUNK_GC_GRACE`].
+async fn gc_remote_chunks(op: &Operator, game_id: u32) -> Result<()> {
+    let mut referenced = HashSet::new();
+    for entry in op.list(&format!("{game_id}/")).await? {
+        if !entry.metadata().is_file() || !is_snapshot(entry.name()) {
+            continue;
+        }
+        let mut data = op.read(entry.path()).await?.to_vec();
+        if crypto::is_encrypted(&data) {
+            data = crypto::decrypt_bytes(&download_passphrase()?, &data)?;
+        }
+        let manifest = SnapshotManifest::from_reader(data.as_slice())?;
+        referenced.extend(manifest.chunks().into_iter().map(str::to_string));
+    }
+
+    let entries = match op.list(&remote_chunk_dir(game_id)).await {
+        Ok(e) => e,
+        Err(e) if e.kind() == opendal::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut unreferenced = vec![];
+    for entry in entries {
+        if entry.metadata().is_file()
+            && !referenced.contains(entry.name())
+            && !is_recent_chunk(op, &entry).await?
+        {
+            unreferenced.push(entry.name().to_string());
+        }
+    }
+    if unreferenced.is_empty() {
+        return Ok(());
+    }
+    let mut deleter = op.deleter().await?;
+    for hash in &unreferenced {
+        deleter
+            .delete(format!("{}{hash}", remote_chunk_dir(game_id)))
+            .await?;
+    }
+    deleter.close().await?;
+    info!(
+        "removed {} unreferenced chunk(s) of game {game_id} from remote",
+        unreferenced.len()
+    );
+    Ok(())
+}
+
 #[derive(Debug, Clone)]
 pub struct LocalOperator(pub Operator);
 #[derive(Debug, Clone)]
@@ -48,11 +725,17 @@ impl super::MyOperation for LocalOperator {
         }
         debug_assert_eq!(d.unwrap().path(), path);
         while let Some(e) = lister.try_next().await? {
+            // e.g. the chunk dir of snapshots, sidecars of the archives, or the
+            // save history
+            if e.path().ends_with('/') || is_sidecar(e.path()) || e.name() == HISTORY_FILENAME {
+                continue;
+            }
             let size = self.inner().stat(e.path()).await?.content_length();
             let mut archive_info = ArchiveInfo::from(e).strip_prefix(&path);
             archive_info.size = size;
             archives.push(archive_info);
         }
+        fill_remote_meta(self.inner(), game_id, &mut archives).await?;
         Ok(archives)
     }
 }
@@ -92,9 +775,15 @@ impl super::MyOperation for Operator {
         }
         debug_assert_eq!(d.unwrap().path(), path);
         while let Some(e) = lister.try_next().await? {
+            // e.g. the chunk dir of snapshots, sidecars of the archives, or the
+            // save history
+            if e.path().ends_with('/') || is_sidecar(e.path()) || e.name() == HISTORY_FILENAME {
+                continue;
+            }
             let archive_info = ArchiveInfo::from(e).strip_prefix(&path);
             archives.push(archive_info);
         }
+        fill_remote_meta(self, game_id, &mut archives).await?;
         Ok(archives)
     }
 
@@ -108,27 +797,51 @@ impl super::MyOperation for Operator {
         self.create_dir(&format!("{}/", game_id)).await?;
 
         let remote_path = format!("{}/{}", game_id, archive_filename);
-        let uploader = self
-            .writer_with(&remote_path)
-            .chunk(if self.chunkable() {
-                WRITER_NORMAL_CHUNK_SIZE
-            } else {
-                WRITER_MAX_BUFFER_SIZE
-            })
-            .await?;
-        let mut writer = uploader.into_futures_async_write();
         let archive_path = backup_dir.join(game_id.to_string()).join(archive_filename);
-        let file = fs::File::open(archive_path).await?;
-        futures::io::copy(file.compat(), &mut writer).await?;
-        writer.close().await?;
+        let passphrase = upload_passphrase(self).await?;
+        // chunks go first, so that a manifest on remote is always complete
+        if is_snapshot(archive_filename) {
+            upload_chunks(self, game_id, &archive_path, passphrase.as_deref()).await?;
+        }
+        match &passphrase {
+            None => write_file(self, &remote_path, &archive_path).await?,
+            Some(passphrase) => {
+                // encrypt into a temp file first, archives can be too large to be
+                // held in memory. The temp file is unique, uploads of the same
+                // archive may overlap.
+                let sealed = NamedTempFile::new()?;
+                let (src, dst) = (archive_path.clone(), sealed.path().to_path_buf());
+                let passphrase = passphrase.clone();
+                tokio::task::spawn_blocking(move || crypto::encrypt_file(&passphrase, &src, &dst))
+                    .await
+                    .map_err(io::Error::other)??;
+                write_file(self, &remote_path, sealed.path()).await?;
+            }
+        }
+        if let Some(meta) = read_meta(&archive_path)? {
+            write_remote_meta(self, &remote_path, &meta, passphrase.as_deref()).await?;
+        }
+        // the checksum goes last, so that it is only on remote if the archive is
+        // complete
+        upload_checksum(self, &remote_path, &archive_path, passphrase.as_deref()).await?;
+        // chunks that were skipped above as already on remote may have been
+        // collected by a concurrent gc before the manifest referred to them
+        if is_snapshot(archive_filename) {
+            upload_chunks(self, game_id, &archive_path, passphrase.as_deref()).await?;
+        }
         Ok(())
     }
 
     async fn delete_archive(&self, game_id: u32, archive_filename: &str) -> Result<()> {
         let remote_path = format!("{}/{}", game_id, archive_filename);
         let mut deleter = self.deleter().await?;
+        deleter.delete(checksum_filename(&remote_path)).await?;
+        deleter.delete(meta_filename(&remote_path)).await?;
         deleter.delete(remote_path).await?;
         deleter.close().await?;
+        if is_snapshot(archive_filename) {
+            gc_remote_chunks(self, game_id).await?;
+        }
         Ok(())
     }
 
@@ -145,23 +858,52 @@ impl super::MyOperation for Operator {
         backup_dir: &Path,
     ) -> Result<()> {
         let remote_path = format!("{}/{}", game_id, archive_filename);
-        let downloader = self
-            .reader_with(&remote_path)
-            .chunk(4 * 1024 * 1024)
-            .concurrent(8)
-            .await?;
         let archive_dir = backup_dir.join(game_id.to_string());
         fs::create_dir_all(&archive_dir).await?;
         let archive_path = archive_dir.join(archive_filename);
-        let file = fs::File::create(archive_path).await?;
-        futures::io::copy(
-            downloader.into_futures_async_read(..).await?,
-            &mut file.compat(),
-        )
-        .await?;
+        read_file(self, &remote_path, &archive_path).await?;
+        match check_download(self, &remote_path, &archive_path).await? {
+            ChecksumStatus::Ok => {
+                write_checksum(&archive_path)?;
+            }
+            ChecksumStatus::Missing => {
+                warn!("no checksum for {remote_path} on remote, not verified");
+                remove_checksum(&archive_path)?;
+            }
+            ChecksumStatus::Mismatch => {
+                _ = fs::remove_file(&archive_path).await;
+                return Err(Error::ChecksumMismatch(remote_path));
+            }
+        }
+        match remote_meta(self, &remote_path).await? {
+            Some(meta) => write_meta(&archive_path, &meta)?,
+            None => remove_meta(&archive_path)?,
+        }
+        if is_snapshot(archive_filename) {
+            pull_chunks(self, game_id, &archive_path).await?;
+        }
         Ok(())
     }
 
+    async fn check_archive(&self, game_id: u32, archive_filename: &str) -> Result<ChecksumStatus> {
+        let remote_path = format!("{}/{}", game_id, archive_filename);
+        if remote_checksum(self, &remote_path).await?.is_none() {
+            return Ok(ChecksumStatus::Missing);
+        }
+        // unique, checks of the same archive may overlap
+        let file = NamedTempFile::new()?;
+        read_file(self, &remote_path, file.path()).await?;
+        check_download(self, &remote_path, file.path()).await
+    }
+
+    async fn archive_checksum(
+        &self,
+        game_id: u32,
+        archive_filename: &str,
+    ) -> Result<Option<String>> {
+        remote_checksum(self, &format!("{}/{}", game_id, archive_filename)).await
+    }
+
     async fn rename_archive(
         &self,
         game_id: u32,
@@ -171,47 +913,157 @@ impl super::MyOperation for Operator {
         let remote_path = format!("{}/{}", game_id, archive_filename);
         let new_remote_path = format!("{}/{}", game_id, new_archive_filename);
         self.rename(&remote_path, &new_remote_path).await?;
-        Ok(())
+        // the sidecar names the archive, so it is rewritten instead of renamed
+        if let Some(checksum) = remote_checksum(self, &remote_path).await? {
+            let content = format_checksum(&checksum, new_archive_filename).into_bytes();
+            let content = match upload_passphrase(self).await? {
+                Some(passphrase) => crypto::encrypt_bytes(&passphrase, &content)?,
+                None => content,
+            };
+            self.write(&checksum_filename(&new_remote_path), content)
+                .await?;
+            self.delete(&checksum_filename(&remote_path)).await?;
+        }
+        match self
+            .rename(
+                &meta_filename(&remote_path),
+                &meta_filename(&new_remote_path),
+            )
+            .await
+        {
+            Err(e) if e.kind() != opendal::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
     }
 
-    async fn upload_config_inner(&self, filename: &str) -> Result<()> {
-        let mut uploader = self
-            .writer_with(filename)
-            .chunk(if self.chunkable() {
-                WRITER_NORMAL_CHUNK_SIZE
-            } else {
-                WRITER_MAX_BUFFER_SIZE
-            })
-            .concurrent(8)
+    async fn set_archive_note(
+        &self,
+        game_id: u32,
+        archive_filename: &str,
+        note: Option<String>,
+    ) -> Result<()> {
+        update_remote_meta(self, game_id, archive_filename, |meta| meta.note = note).await
+    }
+
+    async fn set_archive_pinned(
+        &self,
+        game_id: u32,
+        archive_filename: &str,
+        pinned: bool,
+    ) -> Result<()> {
+        update_remote_meta(self, game_id, archive_filename, |meta| meta.pinned = pinned).await
+    }
+
+    async fn save_history(&self, game_id: u32) -> Result<SaveHistory> {
+        let remote_path = format!("{game_id}/{HISTORY_FILENAME}");
+        let mut data = match self.read(&remote_path).await {
+            Ok(b) => b.to_vec(),
+            Err(e) if e.kind() == opendal::ErrorKind::NotFound => return Ok(SaveHistory::default()),
+            Err(e) => return Err(e.into()),
+        };
+        if crypto::is_encrypted(&data) {
+            data = crypto::decrypt_bytes(&download_passphrase()?, &data)?;
+        }
+        Ok(SaveHistory::from_toml(&data)?)
+    }
+
+    async fn write_save_history(&self, game_id: u32, history: &SaveHistory) -> Result<()> {
+        self.create_dir(&format!("{}/", game_id)).await?;
+        let content = history.to_toml().into_bytes();
+        let content = match upload_passphrase(self).await? {
+            Some(passphrase) => crypto::encrypt_bytes(&passphrase, &content)?,
+            None => content,
+        };
+        self.write(&format!("{game_id}/{HISTORY_FILENAME}"), content)
             .await?;
-        let config_str = toml::to_string(&*CONFIG.lock()).unwrap();
-        uploader.write(config_str).await?;
-        uploader.close().await?;
         Ok(())
     }
 
-    async fn get_remote_config(&self) -> Result<Option<Config>> {
-        let reader = self
-            .reader_with(CONFIG_FILENAME)
-            .chunk(4 * 1024 * 1024)
-            .concurrent(8)
-            .await?;
+    async fn upload_config_inner(
+        &self,
+        config: Config,
+        expected: Option<&ConfigVersion>,
+    ) -> Result<()> {
+        match config.settings.storage.layout {
+            RemoteLayout::Single => {
+                let content = toml::to_string(&config)?.into_bytes();
+                let content = encrypt_with(upload_passphrase(self).await?.as_deref(), content)?;
+                write_single(self, content, expected).await
+            }
+            RemoteLayout::Split => {
+                let split = SplitConfig::new(&config)?;
+                write_split(self, split, expected).await
+            }
+        }
+    }
 
-        let buf = match reader.read(..).await {
-            Ok(b) => b,
-            Err(e) if e.kind() == opendal::ErrorKind::NotFound => return Ok(None),
-            Err(e) => return Err(e.into()),
+    async fn get_remote_config_versioned(&self) -> Result<(Option<Config>, ConfigVersion)> {
+        let (config, version) = match read_index(self).await? {
+            Some((index, version)) => (read_split(self, index).await?, version),
+            None => {
+                let Some((bytes, version)) =
+                    read_versioned(self, CONFIG_FILENAME, RemoteLayout::Single).await?
+                else {
+                    return Ok((None, ConfigVersion::default()));
+                };
+                (toml::from_slice(&decrypt_download(bytes)?)?, version)
+            }
         };
-        let mut new_config: Config = toml::from_slice(&buf.to_bytes())?;
+        let mut new_config = migrate(config);
         new_config.last_sync = Some(Utc::now());
-        Ok(Some(new_config))
+        Ok((Some(new_config), version))
     }
 
     #[cfg(feature = "config-daily-backup")]
     async fn replicate_config(&self) -> Result<()> {
-        let to = &format!("config_{}.toml", chrono::Local::now().format("%Y%m%d"));
-        info!("replicate config from {} to {}", CONFIG_FILENAME, to);
-        self.copy(CONFIG_FILENAME, to).await?;
+        let to = &snapshot_name(chrono::Local::now().date_naive());
+        info!("replicate config to {to}");
+        // a single file in any layout, to be restored as is
+        let Some(config) = self.get_remote_config().await? else {
+            return Ok(());
+        };
+        let content = toml::to_string(&config)?.into_bytes();
+        self.write(
+            to,
+            encrypt_with(upload_passphrase(self).await?.as_deref(), content)?,
+        )
+        .await?;
         Ok(())
     }
+
+    async fn list_config_snapshots(&self) -> Result<Vec<ConfigSnapshot>> {
+        let mut snapshots = vec![];
+        for entry in self.list("/").await? {
+            let Some(date) = parse_snapshot_name(entry.name()) else {
+                continue;
+            };
+            let size = self.stat(entry.path()).await?.content_length();
+            snapshots.push(ConfigSnapshot {
+                name: entry.name().to_string(),
+                date,
+                size,
+            });
+        }
+        snapshots.sort_by_key(|s| std::cmp::Reverse(s.date));
+        Ok(snapshots)
+    }
+
+    async fn get_config_snapshot(&self, name: &str) -> Result<Config> {
+        if parse_snapshot_name(name).is_none() {
+            return Err(Error::InvalidPath);
+        }
+        let bytes = decrypt_download(self.read(name).await?.to_vec())?;
+        Ok(migrate(toml::from_slice(&bytes)?))
+    }
+
+    async fn prune_config_snapshots(&self, keep: u32) -> Result<Vec<String>> {
+        let snapshots = self.list_config_snapshots().await?;
+        let mut pruned = vec![];
+        for snapshot in snapshot::to_prune(&snapshots, keep) {
+            info!("pruning config snapshot {}", snapshot.name);
+            self.delete(&snapshot.name).await?;
+            pruned.push(snapshot.name.clone());
+        }
+        Ok(pruned)
+    }
 }
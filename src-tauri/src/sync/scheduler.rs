@@ -0,0 +1,245 @@
+//! Background config sync. It runs in the backend so that it keeps going while
+//! the webview is suspended, and reports every sync with the same
+//! [`CONFIG_SYNC_EVENT`].
+
+use std::{
+    sync::atomic::{AtomicU32, Ordering},
+    time::{Duration, Instant},
+};
+
+use log::{error, info, warn};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter as _, Manager as _};
+use tauri_plugin_notification::NotificationExt as _;
+use ts_rs::TS;
+
+use super::UploadConfigStatus;
+use crate::{
+    bindings,
+    db::{CONFIG, Config},
+};
+
+/// Emitted with a [`ConfigSyncEvent`] after every background sync.
+pub const CONFIG_SYNC_EVENT: &str = "sync://config";
+
+/// How often the interval sync checks whether it is due, so that changes of
+/// [`Settings::auto_sync_interval`](crate::db::settings::Settings) are picked
+/// up without a restart.
+const INTERVAL_POLL: Duration = Duration::from_secs(10);
+
+/// What started a background sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub enum SyncTrigger {
+    Startup,
+    Interval,
+    Minimize,
+    GameExit,
+    Quit,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ConfigSyncOutcome {
+    Uploaded,
+    /// Remote changes were merged and uploaded.
+    Merged,
+    /// Nothing to upload.
+    LocalClean,
+    /// Local and remote changes conflict, nothing was synced.
+    Conflict,
+    /// The remote config was applied over `previous`.
+    Applied {
+        previous: Box<Config>,
+    },
+    /// The remote config was not applied since it is not newer, missing, or
+    /// conflicts with local changes.
+    UpToDate,
+    Failed {
+        error: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigSyncEvent {
+    /// Increases with every sync, to tell events apart.
+    pub id: u32,
+    pub trigger: SyncTrigger,
+    pub outcome: ConfigSyncOutcome,
+}
+
+static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+static LAST_EVENT: Mutex<Option<ConfigSyncEvent>> = Mutex::new(None);
+/// Keeps syncs from running concurrently.
+static RUNNING: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// The last background sync, for a webview that started listening late.
+pub fn last_event() -> Option<ConfigSyncEvent> {
+    LAST_EVENT.lock().clone()
+}
+
+fn enabled(trigger: SyncTrigger) -> bool {
+    let lock = CONFIG.lock();
+    let settings = &lock.settings;
+    !settings.storage.is_not_set()
+        && match trigger {
+            SyncTrigger::Startup => settings.auto_sync.on_startup,
+            SyncTrigger::Interval => settings.auto_sync_interval > 0,
+            SyncTrigger::Minimize => settings.auto_sync.on_minimize,
+            SyncTrigger::GameExit => settings.auto_sync.on_game_exit,
+            SyncTrigger::Quit => settings.auto_sync.on_quit,
+        }
+}
+
+/// Sync the config if `trigger` is enabled: apply the remote config on
+/// startup, upload the local one otherwise, both the safe way. Returns `None`
+/// if disabled.
+pub async fn sync(app: &AppHandle, trigger: SyncTrigger) -> Option<ConfigSyncOutcome> {
+    if !enabled(trigger) {
+        return None;
+    }
+    let _running = RUNNING.lock().await;
+    info!("[{trigger:?}] syncing config...");
+    let outcome = match trigger {
+        SyncTrigger::Startup => match bindings::apply_remote_config(app.clone(), true).await {
+            Ok((Some(previous), _)) => ConfigSyncOutcome::Applied {
+                previous: Box::new(previous),
+            },
+            Ok((None, _)) => ConfigSyncOutcome::UpToDate,
+            Err(e) => ConfigSyncOutcome::Failed {
+                error: e.to_string(),
+            },
+        },
+        _ => match bindings::upload_config(app.clone(), true).await {
+            Ok(UploadConfigStatus::Uploaded) => ConfigSyncOutcome::Uploaded,
+            Ok(UploadConfigStatus::Merged) => ConfigSyncOutcome::Merged,
+            Ok(UploadConfigStatus::LocalClean) => ConfigSyncOutcome::LocalClean,
+            Ok(UploadConfigStatus::Conflict) => ConfigSyncOutcome::Conflict,
+            Err(e) => ConfigSyncOutcome::Failed {
+                error: e.to_string(),
+            },
+        },
+    };
+    match &outcome {
+        ConfigSyncOutcome::Failed { error } => error!("[{trigger:?}] config sync failed: {error}"),
+        ConfigSyncOutcome::Conflict => warn!("[{trigger:?}] config conflict detected"),
+        outcome => info!("[{trigger:?}] config sync done: {outcome:?}"),
+    }
+
+    if matches!(trigger, SyncTrigger::Minimize | SyncTrigger::Quit) {
+        notify(app, &outcome);
+    }
+    let event = ConfigSyncEvent {
+        id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        trigger,
+        outcome: outcome.clone(),
+    };
+    if let Err(e) = app.emit(CONFIG_SYNC_EVENT, &event) {
+        warn!("failed to emit config sync event: {e}");
+    }
+    *LAST_EVENT.lock() = Some(event);
+    Some(outcome)
+}
+
+/// Like [`sync`], without waiting for it.
+pub fn spawn_sync(app: &AppHandle, trigger: SyncTrigger) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move { sync(&app, trigger).await });
+}
+
+/// Start the background sync: once on startup, then every
+/// [`Settings::auto_sync_interval`](crate::db::settings::Settings).
+pub fn start(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        sync(&app, SyncTrigger::Startup).await;
+        let mut last = Instant::now();
+        loop {
+            tokio::time::sleep(INTERVAL_POLL).await;
+            let interval = CONFIG.lock().settings.auto_sync_interval;
+            if interval == 0 || last.elapsed() < Duration::from_secs(interval.into()) {
+                continue;
+            }
+            sync(&app, SyncTrigger::Interval).await;
+            last = Instant::now();
+        }
+    });
+}
+
+/// Quit the app, uploading the config first if `sync` and enabled.
+pub fn quit(app: &AppHandle, sync: bool) {
+    if !sync || !enabled(SyncTrigger::Quit) {
+        app.exit(0);
+        return;
+    }
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.minimize();
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let outcome = self::sync(&app, SyncTrigger::Quit).await;
+        // let the notification show up
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let failed = matches!(outcome, Some(ConfigSyncOutcome::Failed { .. }));
+        app.exit(if failed { 1 } else { 0 });
+    });
+}
+
+/// Notify about a sync while the window is hidden.
+fn notify(app: &AppHandle, outcome: &ConfigSyncOutcome) {
+    let (title, body) = match outcome {
+        ConfigSyncOutcome::Uploaded => (
+            "\u{2705} Synced",
+            "Configuration uploaded successfully".into(),
+        ),
+        ConfigSyncOutcome::Merged => (
+            "\u{2705} Synced",
+            "Remote changes merged and uploaded".into(),
+        ),
+        ConfigSyncOutcome::LocalClean | ConfigSyncOutcome::UpToDate => (
+            "\u{23ed} Sync Skipped",
+            "Local configuration is up to date".into(),
+        ),
+        ConfigSyncOutcome::Conflict => (
+            "\u{26a0}\u{fe0f} Sync Conflict",
+            "Local and remote configuration conflict \u{2014} please resolve them first".into(),
+        ),
+        ConfigSyncOutcome::Applied { .. } => {
+            ("\u{2705} Synced", "Remote configuration applied".into())
+        }
+        ConfigSyncOutcome::Failed { error } => (
+            "\u{274c} Sync Failed",
+            format!("Failed to upload configuration: {error}"),
+        ),
+    };
+    _ = app.notification().builder().title(title).body(body).show();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outcome_is_tagged_by_kind() {
+        let event = ConfigSyncEvent {
+            id: 1,
+            trigger: SyncTrigger::GameExit,
+            outcome: ConfigSyncOutcome::Failed {
+                error: "offline".to_string(),
+            },
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({
+                "id": 1,
+                "trigger": "gameExit",
+                "outcome": { "kind": "failed", "error": "offline" },
+            })
+        );
+    }
+}
@@ -0,0 +1,86 @@
+//! Daily snapshots of the remote config, taken by
+//! [`replicate_config`](super::MyOperation::replicate_config) as
+//! `config_YYYYMMDD.toml` in the remote root.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+const PREFIX: &str = "config_";
+const SUFFIX: &str = ".toml";
+const DATE_FORMAT: &str = "%Y%m%d";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigSnapshot {
+    pub name: String,
+    pub date: NaiveDate,
+    pub size: u64,
+}
+
+/// Name of the snapshot taken on `date`.
+#[inline]
+pub fn snapshot_name(date: NaiveDate) -> String {
+    format!("{PREFIX}{}{SUFFIX}", date.format(DATE_FORMAT))
+}
+
+/// The date of the snapshot called `name`, `None` if it is not a snapshot.
+pub fn parse_snapshot_name(name: &str) -> Option<NaiveDate> {
+    let date = name.strip_prefix(PREFIX)?.strip_suffix(SUFFIX)?;
+    NaiveDate::parse_from_str(date, DATE_FORMAT)
+        .ok()
+        .filter(|d| snapshot_name(*d) == name)
+}
+
+/// Snapshots beyond the `keep` newest ones. `0` keeps all.
+pub fn to_prune(snapshots: &[ConfigSnapshot], keep: u32) -> Vec<&ConfigSnapshot> {
+    if keep == 0 {
+        return vec![];
+    }
+    let mut snapshots: Vec<_> = snapshots.iter().collect();
+    snapshots.sort_by_key(|s| std::cmp::Reverse(s.date));
+    snapshots.split_off((keep as usize).min(snapshots.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(day: u32) -> ConfigSnapshot {
+        let date = NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        ConfigSnapshot {
+            name: snapshot_name(date),
+            date,
+            size: 0,
+        }
+    }
+
+    #[test]
+    fn parses_snapshot_names() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 9).unwrap();
+        assert_eq!(snapshot_name(date), "config_20240309.toml");
+        assert_eq!(parse_snapshot_name("config_20240309.toml"), Some(date));
+        for name in [
+            "config.toml",
+            "config_2024039.toml",
+            "config_20241309.toml",
+            "config_20240309.toml.bak",
+            "../config_20240309.toml",
+        ] {
+            assert_eq!(parse_snapshot_name(name), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn prunes_the_oldest_snapshots() {
+        let snapshots = [snapshot(2), snapshot(4), snapshot(1), snapshot(3)];
+        let pruned: Vec<_> = to_prune(&snapshots, 2)
+            .into_iter()
+            .map(|s| s.date.format("%d").to_string())
+            .collect();
+        assert_eq!(pruned, ["02", "01"]);
+        assert!(to_prune(&snapshots, 0).is_empty());
+        assert!(to_prune(&snapshots, 10).is_empty());
+    }
+}
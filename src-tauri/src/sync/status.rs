@@ -0,0 +1,299 @@
+//! Comparison of the local and remote archives of a game, and what it takes
+//! to bring both sides in sync.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::archive::{ArchiveInfo, ArchiveTrigger};
+
+/// Where an archive is, and whether both copies agree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub enum ArchiveSyncState {
+    LocalOnly,
+    RemoteOnly,
+    /// On both sides with the same content.
+    Identical,
+    /// On both sides under the same name, but with different content.
+    Different,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveSyncStatus {
+    pub name: String,
+    pub state: ArchiveSyncState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub local_size: Option<u64>,
+    /// Size of the remote copy, which may be compressed or encrypted
+    /// differently from the local one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub remote_size: Option<u64>,
+    /// Whether the archive is a snapshot taken while the game was running.
+    /// These are never uploaded by [`reconcile`](crate::bindings::reconcile).
+    pub in_session: bool,
+}
+
+/// Number and total size of archives in a state. Archives on both sides count
+/// with their local size.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncTotal {
+    pub count: u32,
+    pub size: u64,
+}
+
+impl SyncTotal {
+    #[inline]
+    fn add(&mut self, size: u64) {
+        self.count += 1;
+        self.size += size;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub struct GameSyncStatus {
+    pub game_id: u32,
+    /// Sorted by name.
+    pub archives: Vec<ArchiveSyncStatus>,
+    pub local_only: SyncTotal,
+    pub remote_only: SyncTotal,
+    pub identical: SyncTotal,
+    pub different: SyncTotal,
+}
+
+impl GameSyncStatus {
+    /// Classify the archives of a game. `same` tells whether the local and
+    /// remote copies of an archive on both sides have the same content.
+    pub fn new(
+        game_id: u32,
+        local: Vec<ArchiveInfo>,
+        remote: Vec<ArchiveInfo>,
+        same: impl Fn(&ArchiveInfo, &ArchiveInfo) -> bool,
+    ) -> Self {
+        let mut remote: HashMap<_, _> = remote.into_iter().map(|a| (a.name.clone(), a)).collect();
+        let mut archives = BTreeMap::new();
+        for local in local {
+            let remote = remote.remove(&local.name);
+            let state = match &remote {
+                None => ArchiveSyncState::LocalOnly,
+                Some(remote) if same(&local, remote) => ArchiveSyncState::Identical,
+                Some(_) => ArchiveSyncState::Different,
+            };
+            archives.insert(
+                local.name.clone(),
+                ArchiveSyncStatus {
+                    state,
+                    local_size: Some(local.size),
+                    remote_size: remote.map(|r| r.size),
+                    in_session: is_in_session(&local),
+                    name: local.name,
+                },
+            );
+        }
+        for (name, remote) in remote {
+            archives.insert(
+                name.clone(),
+                ArchiveSyncStatus {
+                    name,
+                    state: ArchiveSyncState::RemoteOnly,
+                    local_size: None,
+                    remote_size: Some(remote.size),
+                    in_session: is_in_session(&remote),
+                },
+            );
+        }
+
+        let mut status = Self {
+            game_id,
+            archives: archives.into_values().collect(),
+            local_only: SyncTotal::default(),
+            remote_only: SyncTotal::default(),
+            identical: SyncTotal::default(),
+            different: SyncTotal::default(),
+        };
+        for archive in &status.archives {
+            let size = archive
+                .local_size
+                .or(archive.remote_size)
+                .unwrap_or_default();
+            match archive.state {
+                ArchiveSyncState::LocalOnly => status.local_only.add(size),
+                ArchiveSyncState::RemoteOnly => status.remote_only.add(size),
+                ArchiveSyncState::Identical => status.identical.add(size),
+                ArchiveSyncState::Different => status.different.add(size),
+            }
+        }
+        status
+    }
+
+    /// Names of the archives in `state`.
+    pub fn names(&self, state: ArchiveSyncState) -> impl Iterator<Item = &str> {
+        self.archives
+            .iter()
+            .filter(move |a| a.state == state)
+            .map(|a| a.name.as_str())
+    }
+}
+
+#[inline]
+fn is_in_session(archive: &ArchiveInfo) -> bool {
+    archive
+        .meta
+        .as_ref()
+        .is_some_and(|m| m.trigger == ArchiveTrigger::InSession)
+}
+
+/// Whether two copies of an archive have the same content, judging by their
+/// checksums, or by their metadata if either has no checksum. Copies with
+/// neither are assumed to be the same.
+pub fn same_archive(
+    local: &ArchiveInfo,
+    local_checksum: Option<&str>,
+    remote: &ArchiveInfo,
+    remote_checksum: Option<&str>,
+) -> bool {
+    if let (Some(local), Some(remote)) = (local_checksum, remote_checksum) {
+        return local == remote;
+    }
+    match (&local.meta, &remote.meta) {
+        (Some(local), Some(remote)) => {
+            local.created_at == remote.created_at
+                && local.device_uid == remote.device_uid
+                && local.content_hash == remote.content_hash
+        }
+        _ => true,
+    }
+}
+
+/// Which archives [`reconcile`](crate::bindings::reconcile) copies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub enum ReconcileDirection {
+    /// Upload local-only archives.
+    Upload,
+    /// Pull remote-only archives.
+    Pull,
+    Both,
+}
+
+impl ReconcileDirection {
+    #[inline]
+    pub fn uploads(self) -> bool {
+        matches!(self, Self::Upload | Self::Both)
+    }
+
+    #[inline]
+    pub fn pulls(self) -> bool {
+        matches!(self, Self::Pull | Self::Both)
+    }
+}
+
+/// Outcome of copying an archive while reconciling.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconcileResult {
+    pub game_id: u32,
+    pub name: String,
+    /// `true` if uploaded, `false` if pulled.
+    pub uploaded: bool,
+    /// The error if copying failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone as _, Utc};
+
+    use super::*;
+    use crate::archive::{ArchiveAlgo, ArchiveMeta};
+
+    fn archive(name: &str, size: u64, trigger: ArchiveTrigger) -> ArchiveInfo {
+        ArchiveInfo {
+            name: name.to_string(),
+            size,
+            format: None,
+            meta: Some(ArchiveMeta {
+                created_at: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+                device_uid: "a".to_string(),
+                device_name: String::new(),
+                algorithm: ArchiveAlgo::Tar,
+                use_time: Duration::zero(),
+                trigger,
+                note: None,
+                pinned: false,
+                fingerprint: None,
+                content_hash: Some(name.to_string()),
+            }),
+        }
+    }
+
+    #[test]
+    fn classifies_archives() {
+        let local = vec![
+            archive("both", 10, ArchiveTrigger::Manual),
+            archive("changed", 20, ArchiveTrigger::Manual),
+            archive("local", 30, ArchiveTrigger::InSession),
+        ];
+        let remote = vec![
+            archive("both", 12, ArchiveTrigger::Manual),
+            archive("changed", 22, ArchiveTrigger::Manual),
+            archive("remote", 40, ArchiveTrigger::AutoUpload),
+        ];
+        let status = GameSyncStatus::new(1, local, remote, |l, _| l.name == "both");
+
+        let states: Vec<_> = status
+            .archives
+            .iter()
+            .map(|a| (a.name.as_str(), a.state))
+            .collect();
+        assert_eq!(
+            states,
+            [
+                ("both", ArchiveSyncState::Identical),
+                ("changed", ArchiveSyncState::Different),
+                ("local", ArchiveSyncState::LocalOnly),
+                ("remote", ArchiveSyncState::RemoteOnly),
+            ]
+        );
+        assert!(status.archives[2].in_session);
+        assert_eq!(status.archives[1].remote_size, Some(22));
+        assert_eq!(status.identical, SyncTotal { count: 1, size: 10 });
+        assert_eq!(status.different, SyncTotal { count: 1, size: 20 });
+        assert_eq!(status.local_only, SyncTotal { count: 1, size: 30 });
+        assert_eq!(status.remote_only, SyncTotal { count: 1, size: 40 });
+        assert_eq!(
+            status
+                .names(ArchiveSyncState::RemoteOnly)
+                .collect::<Vec<_>>(),
+            ["remote"]
+        );
+    }
+
+    #[test]
+    fn compares_checksums_before_metadata() {
+        let a = archive("a", 1, ArchiveTrigger::Manual);
+        let mut b = a.clone();
+        assert!(same_archive(&a, Some("x"), &b, Some("x")));
+        assert!(!same_archive(&a, Some("x"), &b, Some("y")));
+        assert!(same_archive(&a, None, &b, Some("y")));
+
+        b.meta.as_mut().unwrap().content_hash = Some("other".to_string());
+        assert!(!same_archive(&a, Some("x"), &b, None));
+        b.meta = None;
+        assert!(same_archive(&a, None, &b, None));
+    }
+}
@@ -5,13 +5,29 @@ use std::{io, path::Path};
 
 use similar::TextDiff;
 
-use crate::{archive::ArchiveInfo, db::Config};
+use crate::{
+    archive::{ArchiveInfo, is_sidecar, read_meta_or_legacy},
+    db::Config,
+};
 
 pub fn list_dir_all(path: impl AsRef<Path>) -> io::Result<Vec<ArchiveInfo>> {
     let entries = std::fs::read_dir(path)?;
     let mut ret = vec![];
     for entry in entries {
-        ret.push(ArchiveInfo::from(entry?));
+        let entry = entry?;
+        // e.g. the chunk dir of snapshots
+        if entry.file_type()?.is_dir() {
+            continue;
+        }
+        // sidecars of the archives
+        if is_sidecar(&entry.file_name().to_string_lossy()) {
+            continue;
+        }
+        let meta = read_meta_or_legacy(&entry.path())?;
+        ret.push(ArchiveInfo {
+            meta,
+            ..ArchiveInfo::from(entry)
+        });
     }
     Ok(ret)
 }
@@ -19,9 +35,12 @@ pub fn list_dir_all(path: impl AsRef<Path>) -> io::Result<Vec<ArchiveInfo>> {
 pub fn diff(old_conf: &Config, new_conf: &Config) -> String {
     let old_str = toml::to_string(old_conf).unwrap();
     let new_str = toml::to_string(new_conf).unwrap();
+    diff_text(&old_str, &new_str)
+}
 
+pub fn diff_text(old_str: &str, new_str: &str) -> String {
     // 生成 diff
-    TextDiff::from_lines(&old_str, &new_str)
+    TextDiff::from_lines(old_str, new_str)
         .unified_diff()
         .context_radius(2)
         .header("Old", "New")
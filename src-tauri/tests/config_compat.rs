@@ -6,7 +6,7 @@
 //! through TOML and asserting that historical config fragments still
 //! deserialize without data loss.
 
-use app_lib::db::{Config, Game, TimeCmp};
+use app_lib::db::{Config, Game, SavePathConfig, TimeCmp, settings::RemoteLayout};
 use chrono::{DateTime, Utc};
 
 /// A realistically-populated config that touches every field which has
@@ -28,7 +28,11 @@ fn sample_config() -> Config {
         id: 42,
         name: "Sample".into(),
         excutable_path: Some("/games/sample.exe".into()),
-        save_paths: vec!["{home}/save".into()],
+        save_paths: vec![SavePathConfig {
+            path: "{home}/save".into(),
+            include: vec![],
+            exclude: vec!["*.log".into()],
+        }],
         image_url: Some("http://example/x.png".into()),
         image_sha256: Some("deadbeef".into()),
         added_time: DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")
@@ -60,7 +64,7 @@ fn config_toml_roundtrip_preserves_all_fields() {
     let g = &deserialized.games[0];
     assert_eq!(g.id, 42);
     assert_eq!(g.name, "Sample");
-    assert_eq!(g.save_paths, vec!["{home}/save"]);
+    assert_eq!(g.save_paths, original.games[0].save_paths);
     assert_eq!(g.use_time, chrono::Duration::seconds(3661));
     assert!(g.last_played_time.is_some());
 }
@@ -125,3 +129,51 @@ path = "/new/path"
     let parsed: Config = toml::from_str(toml_str).expect("new local struct should parse");
     assert_eq!(parsed.settings.storage.local.path, "/new/path");
 }
+
+#[test]
+fn settings_without_auto_sync_keep_previous_behavior() {
+    // `autoSync` was added after `autoSyncInterval`; older configs synced on
+    // startup, minimize and quit, but not after a game exits.
+    let toml_str = r#"
+[settings]
+autoSyncInterval = 600
+"#;
+    let parsed: Config = toml::from_str(toml_str).expect("config without autoSync should parse");
+    assert_eq!(parsed.settings.auto_sync_interval, 600);
+    let auto_sync = parsed.settings.auto_sync;
+    assert!(auto_sync.on_startup && auto_sync.on_minimize && auto_sync.on_quit);
+    assert!(!auto_sync.on_game_exit);
+}
+
+#[test]
+fn revisions_roundtrip_and_default_to_empty() {
+    // Configs written before revisions have none; they are derived from the
+    // timestamps when the config is migrated.
+    let toml_str = r#"
+dbVersion = 1
+lastUpdated = "2024-01-01T00:00:00Z"
+"#;
+    let parsed: Config = toml::from_str(toml_str).expect("config without revisions should parse");
+    assert_eq!(parsed.db_version, 1);
+    assert_eq!(parsed.revision, Default::default());
+
+    let mut original = sample_config();
+    original.revision.bump_device("device-a");
+    original.synced_revision = original.revision.clone();
+    original.revision.bump_device("device-b");
+    let serialized = toml::to_string(&original).unwrap();
+    let deserialized: Config = toml::from_str(&serialized).unwrap();
+    assert_eq!(deserialized.revision, original.revision);
+    assert_eq!(deserialized.synced_revision, original.synced_revision);
+    assert!(deserialized.is_dirty());
+}
+
+#[test]
+fn storage_without_layout_uses_single_layout() {
+    let toml_str = r#"
+[settings.storage]
+provider = "local"
+"#;
+    let parsed: Config = toml::from_str(toml_str).expect("storage without layout should parse");
+    assert_eq!(parsed.settings.storage.layout, RemoteLayout::Single);
+}
@@ -8,34 +8,22 @@ import { Route, Router, useLocation } from '@solidjs/router'
 import { BiRegularExtension } from 'solid-icons/bi'
 import { CgGames } from 'solid-icons/cg'
 import { IoSettingsOutline } from 'solid-icons/io'
-import { createEffect, createMemo, createSignal, onMount, type Component } from 'solid-js'
+import { createEffect, createMemo, onMount, type Component } from 'solid-js'
 import { Toaster } from 'solid-toast'
 import { I18nProvider, useI18n, type Locale } from './i18n'
 import Game from './pages/Game'
 import Plugin from './pages/Plugin'
 import Settings from './pages/Settings'
 import { Sidebar, SidebarItem } from './Sidebar'
-import { checkAndPullRemote, performAutoUpload, useConfig, useConfigInit } from './store'
-import { useAutoUploadService } from './store/AutoUploadService'
+import { useConfig, useConfigInit } from './store'
 
 const MainLayout: Component = () => {
   const { config } = useConfig()
   const { t, setLocale } = useI18n()
   const { colorMode } = useColorMode()
-  const [isServiceReady, setServiceReady] = createSignal(false)
 
   useConfigInit(t)
 
-  checkAndPullRemote(t).finally(() => {
-    setServiceReady(true)
-  })
-  useAutoUploadService({
-    enabled: isServiceReady,
-    execUploadFunc: async () => {
-      await performAutoUpload(t)
-    }
-  })
-
   // 同步 Kobalte 状态到 HTML class
   createEffect(() => {
     const root = document.documentElement
@@ -78,6 +78,16 @@ export const dict = {
       download: 'Download to local',
       deleteLocalArchive: 'Delete Local Archive',
       deleteRemoteArchive: 'Delete Remote Archive',
+      pin: 'Pin (never cleaned up automatically)',
+      unpin: 'Unpin',
+      conflict: 'Save Conflict',
+      conflictDesc: 'Saves diverged between devices since the last sync',
+      restoreConflictDesc: 'Restoring will overwrite saves never uploaded',
+      keepLocal: 'Keep Local',
+      keepRemote: 'Keep Remote',
+      keepBoth: 'Keep Both',
+      restoreAnyway: 'Restore Anyway',
+      reconcile: 'Sync missing archives both ways',
       local: 'Lo',
       remote: 'Re',
       status: {
@@ -152,9 +162,35 @@ export const dict = {
     autoUpload: {
       name: 'Auto Upload Saves',
       description: 'Automatically archive and upload game saves when the game exits',
-      maxKept: 'Max Kept',
+      maxKept: 'Keep Latest',
       maxKeptDesc:
-        'Max number of saves to keep per game; oldest are evicted when exceeded (0 = unlimited)',
+        'Number of newest saves to keep per game; older ones are evicted unless kept by the rules below or pinned (all 0 = unlimited)',
+      keepDaily: 'Daily',
+      keepWeekly: 'Weekly',
+      keepMonthly: 'Monthly',
+      keepPeriodDesc: 'Also keep the newest save of each of the last N days / weeks / months',
+      previewRetention: 'Preview Cleanup',
+      previewNothing: 'Nothing would be deleted',
+      previewLocal: 'Local: ',
+      previewRemote: 'Remote: ',
+      hashContents: 'Compare Contents',
+      hashContentsDesc:
+        'Tell whether saves changed by their content instead of modification time; slower, but skips saves that were rewritten unchanged',
+      pullBeforeLaunch: 'Pull Before Launch',
+      pullBeforeLaunchDesc:
+        'Restore the newest remote save before the game starts if it is newer than the local saves; stops if both changed',
+      sessionSnapshots: 'Snapshots While Playing',
+      sessionSnapshotsDesc:
+        'Keep local snapshots of the saves while the game is running, once it stops writing to them',
+      sessionQuietSecs: 'Quiet Time (s)',
+      sessionQuietSecsDesc: 'Seconds without writes to the saves before a snapshot',
+      sessionIntervalMins: 'Interval (min)',
+      sessionIntervalMinsDesc: 'Also snapshot changed saves every N minutes (0 = off)',
+      sessionMaxSnapshots: 'Per Session',
+      sessionMaxSnapshotsDesc: 'Most snapshots per play session (0 = unlimited)',
+      sessionMaxKept: 'Snapshots Kept',
+      sessionMaxKeptDesc:
+        'Number of newest snapshots to keep, apart from the saves on exit (0 = unlimited)',
       retentionScope: 'Retention Scope',
       scopeLocal: 'Local Only',
       scopeRemote: 'Remote Only',
@@ -253,6 +289,13 @@ export const dict = {
         'Timeout for data transfer operations (upload / download), in seconds',
       nonIoTimeout: 'Operation Timeout',
       nonIoTimeoutDesc: 'Timeout for remote operations (list / delete), in seconds',
+      encryption: 'Encryption',
+      encryptionDesc:
+        'Encrypt archives and config before uploading. Every device syncing with this remote needs the same passphrase',
+      passphrase: 'Passphrase',
+      encryptSecrets: 'Encrypt Local Credentials',
+      encryptSecretsDesc:
+        'Encrypt the storage credentials saved on this device with a key kept in the system keyring',
       s3EndpointDesc: 'Leave empty for AWS',
       s3Region: 'Region',
       s3Bucket: 'Bucket Name',
@@ -269,6 +312,40 @@ export const dict = {
       autoSyncInterval: 'Config Auto Sync Interval',
       autoSyncIntervalDesc: 'Upload config only, not saves',
       autoSyncIntervalPlaceholder: 'seconds, 0 to disable',
+      syncOnStartup: 'Pull on Startup',
+      syncOnStartupDesc: 'Apply the remote config on startup if it is newer',
+      syncOnMinimize: 'Upload on Minimize',
+      syncOnMinimizeDesc: 'Upload config when the window is closed to the tray',
+      syncOnGameExit: 'Upload on Game Exit',
+      syncOnGameExitDesc: 'Upload config after a game exits',
+      syncOnQuit: 'Upload on Quit',
+      syncOnQuitDesc: 'Upload config when quitting from the tray',
+      splitLayout: 'Store Games Separately',
+      splitLayoutDesc:
+        'Store each game as its own remote file, so that only changed games are synced. Older versions of the app syncing to the same storage do not see the config in this layout',
+      snapshotRetention: 'Config Snapshots',
+      snapshotRetentionDesc:
+        'Number of daily remote config snapshots to keep, 0 keeps all',
+      snapshots: 'Remote Config Snapshots',
+      noSnapshot: 'No snapshots yet',
+      snapshotSame: 'Same as the local config',
+      diffSnapshot: 'Compare with local config',
+      restoreSnapshot: 'Restore Snapshot',
+      restoreSnapshotDesc:
+        'The local config is backed up, then replaced with the snapshot: ',
+      pruneSnapshots: 'Delete snapshots beyond the retention',
+      backups: 'Local Config Backups',
+      backupsDesc:
+        'Taken daily and before the local config is replaced, the newest 20 are kept',
+      noBackup: 'No backups yet',
+      restoreBackup: 'Restore Backup',
+      restoreBackupDesc: 'The local config is backed up, then replaced with the backup: ',
+      backupReason: {
+        daily: 'Daily',
+        broken: 'Failed to load',
+        restore: 'Before a restore',
+        remote: 'Before applying the remote config'
+      },
       inSecs: 'In seconds',
       manualSync: 'Manual Syncing',
       forceOp: 'Forced Operation'
@@ -368,12 +445,31 @@ export const dict = {
     uploadFailed: 'Upload Failed: ',
     uploading: 'Uploading: ',
     uploadSuccess: 'Upload Success: ',
+    uploadSkipped: 'Saves unchanged, upload skipped: ',
     duplicateGameId: 'CRITICAL: Duplicate game id detected!',
     retryError: 'An error occurred, retrying',
     openDirFailed: 'Failed to open game directory',
     exePathNotAbsolute:
       'Resolved executable path is not absolute, which may cause launch failure',
     pathNotExist: 'Path does not exist on this device',
+    pullFailed: 'Pull saves failed: ',
+    pulling: 'Pulling newest saves: ',
+    pullSuccess: 'Pulled newest saves: ',
+    reconciling: 'Syncing missing archives...',
+    reconcileDone: 'Uploaded {{uploaded}}, pulled {{pulled}}, failed {{failed}}',
+    restoringSnapshot: 'Restoring config snapshot: ',
+    snapshotRestored: 'Config snapshot restored: ',
+    restoreSnapshotFailed: 'Failed to restore config snapshot: ',
+    snapshotsPruned: 'Deleted {{n}} config snapshot(s)',
+    restoringBackup: 'Restoring config backup: ',
+    backupRestored: 'Config backup restored: ',
+    restoreBackupFailed: 'Failed to restore config backup: ',
+    setSecretsEncryptedFailed: 'Failed to change the encryption of local credentials: ',
+    resolvingConflict: 'Resolving save conflict: ',
+    conflictResolved: 'Save conflict resolved: ',
+    resolveConflictFailed: 'Failed to resolve save conflict: ',
+    saveConflict:
+      'Saves changed both on this device and on another one since the last sync, pull or upload them manually: ',
     partialPathNotExist: 'Partial path does not exist on this device',
     resolveExeFailed: 'Failed to resolve game path (possibly an undefined variable)',
     selectImageFailed: 'Failed to select image',
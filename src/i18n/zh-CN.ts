@@ -78,6 +78,16 @@ export const dict: DeepPartial<RawDictionary> = {
       download: '下载到本地',
       deleteLocalArchive: '删除本地存档',
       deleteRemoteArchive: '删除云端存档',
+      pin: '固定（不会被自动清理）',
+      unpin: '取消固定',
+      conflict: '存档冲突',
+      conflictDesc: '自上次同步后各设备的存档出现了分歧',
+      restoreConflictDesc: '恢复将覆盖尚未上传的存档',
+      keepLocal: '保留本地',
+      keepRemote: '保留远程',
+      keepBoth: '全部保留',
+      restoreAnyway: '仍然恢复',
+      reconcile: '双向同步缺失的存档',
       local: '本',
       remote: '云',
       status: {
@@ -155,8 +165,32 @@ export const dict: DeepPartial<RawDictionary> = {
     autoUpload: {
       name: '自动上传存档',
       description: '游戏退出时自动归档并上传存档',
-      maxKept: '最多保留',
-      maxKeptDesc: '每个游戏最多保留的存档份数，超出时删除最旧的（0 表示不限制）',
+      maxKept: '保留最新',
+      maxKeptDesc:
+        '每个游戏保留的最新存档份数，更旧的存档会被删除，除非被下方规则保留或已固定（全为 0 表示不限制）',
+      keepDaily: '每天',
+      keepWeekly: '每周',
+      keepMonthly: '每月',
+      keepPeriodDesc: '另外保留最近 N 天 / 周 / 月中每一天 / 周 / 月最新的存档',
+      previewRetention: '预览清理',
+      previewNothing: '不会删除任何存档',
+      previewLocal: '本地：',
+      previewRemote: '云端：',
+      hashContents: '比较内容',
+      hashContentsDesc: '按文件内容而不是修改时间判断存档是否变化，较慢，但能识别被重写而未改变的存档',
+      pullBeforeLaunch: '启动前拉取',
+      pullBeforeLaunchDesc:
+        '启动游戏前，若远程最新存档比本地存档新，则拉取并恢复；若两边都有修改则停止启动',
+      sessionSnapshots: '游戏中快照',
+      sessionSnapshotsDesc: '游戏运行时，在存档停止写入后保存本地快照',
+      sessionQuietSecs: '静默时间（秒）',
+      sessionQuietSecsDesc: '存档多少秒未写入后保存快照',
+      sessionIntervalMins: '间隔（分钟）',
+      sessionIntervalMinsDesc: '另外每 N 分钟为变化的存档保存快照（0 = 关闭）',
+      sessionMaxSnapshots: '每次游戏上限',
+      sessionMaxSnapshotsDesc: '每次游戏最多保存的快照数（0 = 不限）',
+      sessionMaxKept: '快照保留数',
+      sessionMaxKeptDesc: '保留最新的快照数量，与退出时的存档分开清理（0 = 不限）',
       retentionScope: '清理范围',
       scopeLocal: '仅本地',
       scopeRemote: '仅远端',
@@ -248,6 +282,11 @@ export const dict: DeepPartial<RawDictionary> = {
       ioTimeoutDesc: '数据传输操作（上传/下载）的超时时间，单位：秒',
       nonIoTimeout: '操作超时',
       nonIoTimeoutDesc: '远端操作（列表/删除）的超时时间，单位：秒',
+      encryption: '加密',
+      encryptionDesc: '上传前加密存档与配置，所有同步此远端的设备需使用相同的密码',
+      passphrase: '加密密码',
+      encryptSecrets: '加密本地凭据',
+      encryptSecretsDesc: '使用系统钥匙串中的密钥加密保存在本设备上的存储凭据',
       s3EndpointDesc: '留空则使用 AWS',
       s3Region: 'Region',
       s3Bucket: 'Bucket Name',
@@ -264,6 +303,36 @@ export const dict: DeepPartial<RawDictionary> = {
       autoSyncInterval: '自动上传间隔',
       autoSyncIntervalDesc: '仅上传配置，不上传存档',
       autoSyncIntervalPlaceholder: '单位：秒，设为 0 禁用自动上传',
+      syncOnStartup: '启动时拉取',
+      syncOnStartupDesc: '启动时若远程配置更新则应用',
+      syncOnMinimize: '最小化时上传',
+      syncOnMinimizeDesc: '关闭窗口到托盘时上传配置',
+      syncOnGameExit: '游戏退出时上传',
+      syncOnGameExitDesc: '游戏退出后上传配置',
+      syncOnQuit: '退出时上传',
+      syncOnQuitDesc: '从托盘退出时上传配置',
+      splitLayout: '分别存储游戏',
+      splitLayoutDesc: '每个游戏存为单独的远程文件，只同步改动的游戏。同步到同一存储的旧版本应用无法读取此格式的配置',
+      snapshotRetention: '配置快照',
+      snapshotRetentionDesc: '保留的远程配置每日快照数量，0 为全部保留',
+      snapshots: '远程配置快照',
+      noSnapshot: '暂无快照',
+      snapshotSame: '与本地配置相同',
+      diffSnapshot: '与本地配置比较',
+      restoreSnapshot: '恢复快照',
+      restoreSnapshotDesc: '将先备份本地配置，再替换为快照: ',
+      pruneSnapshots: '删除超出保留数量的快照',
+      backups: '本地配置备份',
+      backupsDesc: '每天及本地配置被替换前自动备份，保留最新的 20 个',
+      noBackup: '暂无备份',
+      restoreBackup: '恢复备份',
+      restoreBackupDesc: '将先备份本地配置，再替换为备份: ',
+      backupReason: {
+        daily: '每日',
+        broken: '加载失败',
+        restore: '恢复前',
+        remote: '应用远程配置前'
+      },
       inSecs: '（秒）',
       manualSync: '管理配置',
       forceOp: '强制上传/下载'
@@ -362,11 +431,29 @@ export const dict: DeepPartial<RawDictionary> = {
     uploadFailed: '上传失败: ',
     uploading: '正在上传: ',
     uploadSuccess: '上传成功: ',
+    uploadSkipped: '存档未变化，跳过上传: ',
     duplicateGameId: '疑似配置损坏: 检测到重复的游戏 ID! 请手动修复配置。',
     retryError: '发生错误，正在重试',
     openDirFailed: '打开游戏目录失败',
     exePathNotAbsolute: '游戏启动路径解析后不是绝对路径，可能导致启动失败',
     pathNotExist: '路径在当前设备上不存在',
+    pullFailed: '拉取存档失败: ',
+    pulling: '正在拉取最新存档: ',
+    pullSuccess: '已拉取最新存档: ',
+    reconciling: '正在同步缺失的存档...',
+    reconcileDone: '已上传 {{uploaded}} 个，已拉取 {{pulled}} 个，失败 {{failed}} 个',
+    restoringSnapshot: '正在恢复配置快照: ',
+    snapshotRestored: '已恢复配置快照: ',
+    restoreSnapshotFailed: '恢复配置快照失败: ',
+    snapshotsPruned: '已删除 {{n}} 个配置快照',
+    restoringBackup: '正在恢复配置备份: ',
+    backupRestored: '已恢复配置备份: ',
+    restoreBackupFailed: '恢复配置备份失败: ',
+    setSecretsEncryptedFailed: '更改本地凭据加密失败: ',
+    resolvingConflict: '正在解决存档冲突: ',
+    conflictResolved: '已解决存档冲突: ',
+    resolveConflictFailed: '解决存档冲突失败: ',
+    saveConflict: '自上次同步后本设备与其他设备的存档都有修改，请手动拉取或上传: ',
     partialPathNotExist: '部分路径在当前设备上不存在',
     resolveExeFailed: '无法解析游戏路径（可能存在未定义的变量）',
     selectImageFailed: '选择图片失败',
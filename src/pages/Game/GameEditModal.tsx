@@ -14,8 +14,6 @@ import { Button } from '~/components/ui/Button'
 import { Input } from '~/components/ui/Input'
 import { InputWithSuffix } from '~/components/ui/InputWithSuffix'
 import { useI18n } from '~/i18n'
-import { PLUGIN_REGISTRY } from '~/pages/Plugin/plugins'
-import { buildNewInstance } from '~/pages/Plugin/plugins/types'
 import { useConfig } from '~/store'
 import { FiRefreshCw, FiSearch } from 'solid-icons/fi'
 import {
@@ -75,15 +73,9 @@ export default function GameEditModal(props: GameEditModalProps) {
     return vars ? replaceWithVarNames(v, vars) : v
   }
 
-  // Auto-populate plugins for new games based on autoAdd meta config
+  // Plugins marked autoAdd are attached by the backend when a new game is added
   const baseGame = structuredClone(unwrap(props.gameInfo ?? DEFAULT_GAME))
-  if (!isEditMode()) {
-    const autoPlugins = PLUGIN_REGISTRY.filter(def => {
-      const meta = config.pluginMetadatas[def.metaKey] as Record<string, unknown>
-      return meta?.['autoAdd'] === true
-    }).map(def => buildNewInstance(def, config.pluginMetadatas))
-    baseGame.plugins = autoPlugins.length > 0 ? autoPlugins : []
-  }
+  baseGame.plugins ??= []
 
   const [localGame, setLocalGame] = createStore<Game>(baseGame)
 
@@ -326,8 +318,18 @@ export default function GameEditModal(props: GameEditModalProps) {
 
             <PathListEditor
               label={t('game.edit.savePath')}
-              paths={localGame.savePaths}
-              onChange={newPaths => setLocalGame('savePaths', newPaths)}
+              paths={localGame.savePaths.map(p => p.path)}
+              onChange={newPaths =>
+                // keep the include/exclude rules of edited and moved paths
+                setLocalGame(
+                  'savePaths',
+                  newPaths.map((path, i) => ({
+                    ...(localGame.savePaths.find(p => p.path === path) ??
+                      localGame.savePaths[i]),
+                    path
+                  }))
+                )
+              }
               onBulkInput={bulkPathTransform}
               checkVars
               checkPathExist
@@ -1,5 +1,8 @@
 import { type ArchiveInfo } from '@bindings/ArchiveInfo'
 import type { Game } from '@bindings/Game'
+import type { ReconcileResult } from '@bindings/ReconcileResult'
+import type { RetentionPreview } from '@bindings/RetentionPreview'
+import type { SaveConflict } from '@bindings/SaveConflict'
 import { invoke } from '@tauri-apps/api/core'
 import { listen, type UnlistenFn } from '@tauri-apps/api/event'
 import { formatBytes } from '@utils/file'
@@ -10,12 +13,17 @@ import {
   TbOutlineCloudDownload,
   TbOutlineCloudUpload,
   TbOutlineEdit,
+  TbFilledPin,
   TbOutlineFileZip,
+  TbOutlinePin,
+  TbOutlineRefresh,
   TbOutlineTrash,
+  TbOutlineTrashX,
   TbOutlineX
 } from 'solid-icons/tb'
 import { createSignal, For, Match, onMount, Show, Switch } from 'solid-js'
 import toast from 'solid-toast'
+import { confirmRestoreConflict, promptSaveConflict } from './saveConflict'
 
 // --- 类型定义 ---
 
@@ -31,6 +39,11 @@ interface ArchiveSyncModalProps {
   onClose: () => void
 }
 
+/** 按创建时间倒序排序，没有元数据的存档排在最后 */
+const byNewest = (a: ArchiveInfo, b: ArchiveInfo) =>
+  (b.meta?.createdAt ?? '').localeCompare(a.meta?.createdAt ?? '') ||
+  b.name.localeCompare(a.name)
+
 // --- 主组件 ---
 
 export function ArchiveSyncModal(props: ArchiveSyncModalProps) {
@@ -98,8 +111,8 @@ export function ArchiveSyncModal(props: ArchiveSyncModalProps) {
         return { ...baseInfo, status }
       })
 
-      // 按名称倒序排序
-      merged.sort((a, b) => b.name.localeCompare(a.name))
+      // 按创建时间倒序排序
+      merged.sort(byNewest)
       setArchives(merged)
     } catch (e) {
       console.error('Archive fetch failed:', e)
@@ -130,7 +143,15 @@ export function ArchiveSyncModal(props: ArchiveSyncModalProps) {
         )
       })
 
-      await invoke('upload_archive', { gameId: props.gameId, archiveFilename: filename })
+      const conflict = await invoke<SaveConflict | null>('upload_archive', {
+        gameId: props.gameId,
+        archiveFilename: filename
+      })
+      if (conflict) {
+        toast.dismiss(toastId)
+        promptSaveConflict(t, conflict, fetchData)
+        return
+      }
       toast.success(t('hint.uploadSuccess') + filename, { id: toastId })
 
       // 上传成功：LocalOnly -> Synced
@@ -162,6 +183,22 @@ export function ArchiveSyncModal(props: ArchiveSyncModalProps) {
   }
 
   const handleExtract = async (filename: string) => {
+    try {
+      const conflict = await invoke<SaveConflict | null>('check_restore_conflict', {
+        gameId: props.gameId,
+        archiveFilename: filename
+      })
+      if (conflict) {
+        confirmRestoreConflict(t, conflict, () => extract(filename))
+        return
+      }
+    } catch (e) {
+      log.warn(`Failed to check restore conflict: ${e}`)
+    }
+    await extract(filename)
+  }
+
+  const extract = async (filename: string) => {
     const toastId = toast.loading(t('hint.reverting') + filename + '...')
     try {
       await invoke('extract', { gameId: props.gameId, archiveFilename: filename })
@@ -219,6 +256,70 @@ export function ArchiveSyncModal(props: ArchiveSyncModalProps) {
       toast.error(filename + ' ' + t('hint.deleteFailed') + e, { id: toastId })
     }
   }
+  const handleTogglePin = async (item: ArchiveItem) => {
+    const pinned = !item.meta?.pinned
+    const args = { gameId: props.gameId, archiveFilename: item.name, pinned }
+    try {
+      if (item.status !== 'RemoteOnly') await invoke('set_local_archive_pinned', args)
+      if (item.status !== 'LocalOnly') await invoke('set_remote_archive_pinned', args)
+      if (!item.meta) {
+        // 旧版本的存档没有元数据，由后端生成后重新获取
+        await fetchData()
+        return
+      }
+      setArchives(prev =>
+        prev.map(a =>
+          a.name === item.name && a.meta ? { ...a, meta: { ...a.meta, pinned } } : a
+        )
+      )
+    } catch (e) {
+      toast.error(`${item.name}: ${e}`)
+    }
+  }
+
+  const handlePreviewRetention = async () => {
+    try {
+      const preview = await invoke<RetentionPreview>('preview_retention', {
+        gameId: props.gameId
+      })
+      const lines = [
+        ...preview.local.map(n => t('plugin.autoUpload.previewLocal') + n),
+        ...preview.remote.map(n => t('plugin.autoUpload.previewRemote') + n)
+      ]
+      toast(lines.length ? lines.join('\n') : t('plugin.autoUpload.previewNothing'), {
+        duration: 8000
+      })
+    } catch (e) {
+      toast.error(`${e}`)
+    }
+  }
+
+  const handleReconcile = async () => {
+    const toastId = toast.loading(t('hint.reconciling'))
+    try {
+      const results = await invoke<ReconcileResult[]>('reconcile', {
+        gameId: props.gameId,
+        direction: 'both'
+      })
+      const failed = results.filter(r => r.error)
+      const done = results.filter(r => !r.error)
+      const summary = t('hint.reconcileDone', {
+        uploaded: String(done.filter(r => r.uploaded).length),
+        pulled: String(done.filter(r => !r.uploaded).length),
+        failed: String(failed.length)
+      })
+      const lines = [summary, ...failed.map(r => `${r.name}: ${r.error}`)]
+      if (failed.length) {
+        toast.error(lines.join('\n'), { id: toastId, duration: 8000 })
+      } else {
+        toast.success(summary, { id: toastId })
+      }
+    } catch (e) {
+      toast.error(t('hint.syncFailed') + e, { id: toastId })
+    }
+    fetchData()
+  }
+
   // --- 重命名逻辑 ---
   const startRename = (name: string) => {
     setEditingName(name)
@@ -308,7 +409,7 @@ export function ArchiveSyncModal(props: ArchiveSyncModalProps) {
         const updatedList = prev.map(item =>
           item.name === oldName ? { ...item, name: newName } : item
         )
-        return updatedList.sort((a, b) => b.name.localeCompare(a.name))
+        return updatedList.sort(byNewest)
       })
 
       // 只有成功时才关闭编辑框
@@ -335,12 +436,30 @@ export function ArchiveSyncModal(props: ArchiveSyncModalProps) {
             {archives().length} {t('game.sync.archiveNum')}
           </span>
         </div>
-        <button
-          onClick={props.onClose}
-          class="p-1.5 rounded-md hover:bg-gray-200 dark:hover:bg-gray-700 text-gray-500 dark:text-gray-400 transition-colors cursor-pointer"
-        >
-          <TbOutlineX class="w-5 h-5" />
-        </button>
+        <div class="flex items-center gap-1">
+          <Show when={props.gameInfo.savePaths.length !== 0}>
+            <button
+              onClick={handleReconcile}
+              class="p-1.5 rounded-md hover:bg-gray-200 dark:hover:bg-gray-700 text-gray-500 dark:text-gray-400 transition-colors cursor-pointer"
+              title={t('game.sync.reconcile')}
+            >
+              <TbOutlineRefresh class="w-5 h-5" />
+            </button>
+          </Show>
+          <button
+            onClick={handlePreviewRetention}
+            class="p-1.5 rounded-md hover:bg-gray-200 dark:hover:bg-gray-700 text-gray-500 dark:text-gray-400 transition-colors cursor-pointer"
+            title={t('plugin.autoUpload.previewRetention')}
+          >
+            <TbOutlineTrashX class="w-5 h-5" />
+          </button>
+          <button
+            onClick={props.onClose}
+            class="p-1.5 rounded-md hover:bg-gray-200 dark:hover:bg-gray-700 text-gray-500 dark:text-gray-400 transition-colors cursor-pointer"
+          >
+            <TbOutlineX class="w-5 h-5" />
+          </button>
+        </div>
       </div>
 
       {/* Body List */}
@@ -401,6 +520,23 @@ export function ArchiveSyncModal(props: ArchiveSyncModalProps) {
                               >
                                 <TbOutlineEdit class="w-3.5 h-3.5" />
                               </button>
+                              <button
+                                onClick={() => handleTogglePin(item)}
+                                class="p-1 text-gray-400 hover:text-blue-500 transition-opacity cursor-pointer flex-shrink-0"
+                                classList={{
+                                  'opacity-0 group-hover:opacity-100': !item.meta?.pinned
+                                }}
+                                title={
+                                  item.meta?.pinned ? t('game.sync.unpin') : t('game.sync.pin')
+                                }
+                              >
+                                <Show
+                                  when={item.meta?.pinned}
+                                  fallback={<TbOutlinePin class="w-3.5 h-3.5" />}
+                                >
+                                  <TbFilledPin class="w-3.5 h-3.5 text-blue-500" />
+                                </Show>
+                              </button>
                             </div>
                           }
                         >
@@ -434,6 +570,16 @@ export function ArchiveSyncModal(props: ArchiveSyncModalProps) {
                           <span class="text-[11px] text-gray-400 dark:text-gray-500 leading-none font-mono whitespace-nowrap flex-shrink-0">
                             {formatBytes(item.size)}
                           </span>
+
+                          {/* 备注 */}
+                          <Show when={item.meta?.note}>
+                            <span
+                              class="text-[11px] text-gray-500 dark:text-gray-400 leading-none truncate"
+                              title={item.meta?.note}
+                            >
+                              {item.meta?.note}
+                            </span>
+                          </Show>
                         </div>
                       </div>
                     </div>
@@ -1,5 +1,7 @@
 import { type Game } from '@bindings/Game'
+import type { SaveConflict } from '@bindings/SaveConflict'
 import { type SortType } from '@bindings/SortType'
+import type { VersionVector } from '@bindings/VersionVector'
 import { DropArea } from '@components/DropArea'
 import FullScreenMask from '@components/ui/FullScreenMask'
 import { myToast } from '@components/ui/myToast'
@@ -39,6 +41,7 @@ import toast from 'solid-toast'
 import { Virtualizer } from 'virtua/solid'
 import GameEditModal from './GameEditModal'
 import { GameItem, GameItemWrapper } from './GameItem'
+import { promptSaveConflict } from './saveConflict'
 import { ArchiveSyncModal } from './SyncModal'
 
 const GamePage = (): JSX.Element => {
@@ -49,6 +52,8 @@ const GamePage = (): JSX.Element => {
   const [isSyncModalOpen, setSyncModalOpen] = createSignal(false)
   const [isEditMode, setEditMode] = createSignal(false)
   const [editingGameInfo, setEditingGameInfo] = createSignal<Game | null>(null)
+  // revision of the config when the edit modal was opened
+  let editingRevision: VersionVector | undefined
   const [editingIndex, setEditingIndex] = createSignal<number | null>(null)
 
   // 使用数组存储多个正在操作的游戏 ID
@@ -190,6 +195,7 @@ const GamePage = (): JSX.Element => {
   const openEditModal = (index: number) => {
     setEditingIndex(index)
     setEditingGameInfo(config.games[index])
+    editingRevision = structuredClone(unwrap(config.revision))
     setEditMode(true)
     setEditModalOpen(true)
   }
@@ -260,7 +266,7 @@ const GamePage = (): JSX.Element => {
     if (index === null) {
       actions.addGame(game)
     } else {
-      actions.replaceGame(index, game)
+      actions.replaceGame(index, game, editingRevision)
     }
     closeEditModal()
 
@@ -384,10 +390,15 @@ const GamePage = (): JSX.Element => {
         )
       })
 
-      await invoke<void>('upload_archive', {
+      const conflict = await invoke<SaveConflict | null>('upload_archive', {
         gameId: game.id,
         archiveFilename: archived_filename
       })
+      if (conflict) {
+        toast.dismiss(toastId)
+        promptSaveConflict(t, conflict)
+        return
+      }
 
       toast.success(t('hint.syncSuccess') + game.name, {
         id: toastId,
@@ -0,0 +1,74 @@
+import type { ConflictResolution } from '@bindings/ConflictResolution'
+import type { SaveConflict } from '@bindings/SaveConflict'
+import type { SyncRecord } from '@bindings/SyncRecord'
+import { myToast } from '@components/ui/myToast'
+import { invoke } from '@tauri-apps/api/core'
+import { type useI18n } from '~/i18n'
+import toast from 'solid-toast'
+
+type Translator = ReturnType<typeof useI18n>['t']
+
+const describe = (record: SyncRecord) =>
+  `${record.deviceName}: ${record.archive || '-'}`
+
+function ConflictMessage(props: { desc: string; conflict: SaveConflict }) {
+  return (
+    <>
+      <div>{props.desc}</div>
+      <div class="mt-1 font-mono text-xs">{describe(props.conflict.local)}</div>
+      <div class="font-mono text-xs">{describe(props.conflict.remote)}</div>
+    </>
+  )
+}
+
+/** 询问用户如何解决上传时的存档冲突，解决后调用 `onResolved` */
+export function promptSaveConflict(
+  t: Translator,
+  conflict: SaveConflict,
+  onResolved?: () => void
+) {
+  const resolve = async (resolution: ConflictResolution) => {
+    const toastId = toast.loading(t('hint.resolvingConflict') + conflict.local.archive)
+    try {
+      await invoke('resolve_save_conflict', { conflict, resolution })
+      toast.success(t('hint.conflictResolved') + conflict.local.archive, { id: toastId })
+      onResolved?.()
+    } catch (e) {
+      toast.error(t('hint.resolveConflictFailed') + e, { id: toastId })
+    }
+  }
+
+  myToast({
+    variant: 'warning',
+    title: t('game.sync.conflict'),
+    message: <ConflictMessage desc={t('game.sync.conflictDesc')} conflict={conflict} />,
+    actions: [
+      { label: t('game.sync.keepLocal'), onClick: () => resolve('keepLocal') },
+      { label: t('game.sync.keepRemote'), onClick: () => resolve('keepRemote') },
+      {
+        label: t('game.sync.keepBoth'),
+        variant: 'secondary',
+        onClick: () => resolve('keepBoth')
+      }
+    ]
+  })
+}
+
+/** 恢复存档会覆盖未上传的存档时，询问用户是否继续 */
+export function confirmRestoreConflict(
+  t: Translator,
+  conflict: SaveConflict,
+  onConfirm: () => void
+) {
+  myToast({
+    variant: 'warning',
+    title: t('game.sync.conflict'),
+    message: (
+      <ConflictMessage desc={t('game.sync.restoreConflictDesc')} conflict={conflict} />
+    ),
+    actions: [
+      { label: t('ui.cancel'), variant: 'secondary', onClick: () => {} },
+      { label: t('game.sync.restoreAnyway'), variant: 'danger', onClick: onConfirm }
+    ]
+  })
+}
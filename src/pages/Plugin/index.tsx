@@ -34,11 +34,11 @@ export default function PluginPage() {
   }
 
   const setEnabled = (def: AnyPluginDef, enabled: boolean) => {
-    actions.mutate(state => {
-      state.pluginMetadatas = patchPluginMeta(def.metaKey, state.pluginMetadatas, {
+    actions.updatePluginMetadatas(metas =>
+      patchPluginMeta(def.metaKey, metas, {
         enabled
       })
-    })
+    )
   }
 
   return (
@@ -153,14 +153,13 @@ export default function PluginPage() {
                                 }
                                 config={meta()}
                                 onCommit={(m: Record<string, unknown>) => {
-                                  // Persists to disk: actions.mutate → save()
-                                  actions.mutate(state => {
-                                    state.pluginMetadatas = patchPluginMeta(
+                                  actions.updatePluginMetadatas(metas =>
+                                    patchPluginMeta(
                                       def.metaKey as PluginId,
-                                      state.pluginMetadatas,
+                                      metas,
                                       m as Parameters<typeof patchPluginMeta>[2]
                                     )
-                                  })
+                                  )
                                 }}
                               />
                             </div>
@@ -190,16 +189,15 @@ export default function PluginPage() {
                                   ] as AnyGameConfig
                                 }
                                 onCommit={(newDefaults: Record<string, unknown>) => {
-                                  // Persists to disk: actions.mutate → save()
-                                  actions.mutate(state => {
-                                    state.pluginMetadatas = patchPluginMeta(
+                                  actions.updatePluginMetadatas(metas =>
+                                    patchPluginMeta(
                                       def.metaKey as PluginId,
-                                      state.pluginMetadatas,
+                                      metas,
                                       { configDefaults: newDefaults } as Parameters<
                                         typeof patchPluginMeta
                                       >[2]
                                     )
-                                  })
+                                  )
                                 }}
                               />
                             </div>
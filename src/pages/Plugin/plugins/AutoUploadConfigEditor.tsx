@@ -3,24 +3,51 @@
  */
 import type { AutoUploadGameConfig } from '@bindings/AutoUploadGameConfig'
 import type { RetentionScope } from '@bindings/RetentionScope'
-import { FormField, FormInput, FormSelect } from '@components/ui/form'
+import { FormField, FormInput, FormSelect, FormSwitch } from '@components/ui/form'
 import { useI18n } from '~/i18n'
-import type { JSX } from 'solid-js'
+import { Show, type JSX } from 'solid-js'
 import { AutoAddMetaEditor } from './AutoAddMetaEditor'
 import type { ConfigEditorProps, PluginDefinition } from './types'
 
+type CountKey =
+  | 'maxKept'
+  | 'keepDaily'
+  | 'keepWeekly'
+  | 'keepMonthly'
+  | 'sessionQuietSecs'
+  | 'sessionIntervalMins'
+  | 'sessionMaxSnapshots'
+  | 'sessionMaxKept'
+
 function AutoUploadGameConfigEditor(
   props: ConfigEditorProps<AutoUploadGameConfig>
 ): JSX.Element {
   const { t } = useI18n()
 
   /** Parse a non-negative integer; falls back to the current value on invalid
-   *  input. 0 means unlimited retention. */
-  const parseMaxKept = (raw: string): number => {
+   *  input. All counts being 0 means unlimited retention. */
+  const parseCount = (raw: string, key: CountKey): number => {
     const val = Math.floor(Number(raw))
-    return Number.isFinite(val) && val >= 0 ? val : props.config.maxKept
+    return Number.isFinite(val) && val >= 0 ? val : props.config[key]
   }
 
+  const CountInput = (p: { key: CountKey }) => (
+    <FormInput
+      class="w-full"
+      type="text"
+      inputmode="numeric"
+      value={String(props.config[p.key])}
+      onBlur={(e: FocusEvent) => {
+        const el = e.target as HTMLInputElement
+        const parsed = parseCount(el.value, p.key)
+        el.value = String(parsed)
+        if (parsed !== props.config[p.key]) {
+          props.onCommit({ ...props.config, [p.key]: parsed })
+        }
+      }}
+    />
+  )
+
   return (
     <div class="flex flex-wrap gap-5 items-start">
       <FormField
@@ -28,20 +55,20 @@ function AutoUploadGameConfigEditor(
         description={t('plugin.autoUpload.maxKeptDesc')}
         class="w-28"
       >
-        <FormInput
-          class="w-full"
-          type="text"
-          inputmode="numeric"
-          value={String(props.config.maxKept)}
-          onBlur={(e: FocusEvent) => {
-            const el = e.target as HTMLInputElement
-            const parsed = parseMaxKept(el.value)
-            el.value = String(parsed)
-            if (parsed !== props.config.maxKept) {
-              props.onCommit({ ...props.config, maxKept: parsed })
-            }
-          }}
-        />
+        <CountInput key="maxKept" />
+      </FormField>
+      <FormField
+        label={t('plugin.autoUpload.keepDaily')}
+        description={t('plugin.autoUpload.keepPeriodDesc')}
+        class="w-20"
+      >
+        <CountInput key="keepDaily" />
+      </FormField>
+      <FormField label={t('plugin.autoUpload.keepWeekly')} class="w-20">
+        <CountInput key="keepWeekly" />
+      </FormField>
+      <FormField label={t('plugin.autoUpload.keepMonthly')} class="w-20">
+        <CountInput key="keepMonthly" />
       </FormField>
       <FormField label={t('plugin.autoUpload.retentionScope')} class="w-40">
         <FormSelect
@@ -60,6 +87,72 @@ function AutoUploadGameConfigEditor(
           }
         />
       </FormField>
+      <FormField
+        label={t('plugin.autoUpload.hashContents')}
+        description={t('plugin.autoUpload.hashContentsDesc')}
+        class="w-auto"
+      >
+        <FormSwitch
+          checked={props.config.hashContents}
+          onChange={(checked: boolean) =>
+            props.onCommit({ ...props.config, hashContents: checked })
+          }
+        />
+      </FormField>
+      <FormField
+        label={t('plugin.autoUpload.pullBeforeLaunch')}
+        description={t('plugin.autoUpload.pullBeforeLaunchDesc')}
+        class="w-auto"
+      >
+        <FormSwitch
+          checked={props.config.pullBeforeLaunch}
+          onChange={(checked: boolean) =>
+            props.onCommit({ ...props.config, pullBeforeLaunch: checked })
+          }
+        />
+      </FormField>
+      <FormField
+        label={t('plugin.autoUpload.sessionSnapshots')}
+        description={t('plugin.autoUpload.sessionSnapshotsDesc')}
+        class="w-auto"
+      >
+        <FormSwitch
+          checked={props.config.sessionSnapshots}
+          onChange={(checked: boolean) =>
+            props.onCommit({ ...props.config, sessionSnapshots: checked })
+          }
+        />
+      </FormField>
+      <Show when={props.config.sessionSnapshots}>
+        <FormField
+          label={t('plugin.autoUpload.sessionQuietSecs')}
+          description={t('plugin.autoUpload.sessionQuietSecsDesc')}
+          class="w-28"
+        >
+          <CountInput key="sessionQuietSecs" />
+        </FormField>
+        <FormField
+          label={t('plugin.autoUpload.sessionIntervalMins')}
+          description={t('plugin.autoUpload.sessionIntervalMinsDesc')}
+          class="w-28"
+        >
+          <CountInput key="sessionIntervalMins" />
+        </FormField>
+        <FormField
+          label={t('plugin.autoUpload.sessionMaxSnapshots')}
+          description={t('plugin.autoUpload.sessionMaxSnapshotsDesc')}
+          class="w-28"
+        >
+          <CountInput key="sessionMaxSnapshots" />
+        </FormField>
+        <FormField
+          label={t('plugin.autoUpload.sessionMaxKept')}
+          description={t('plugin.autoUpload.sessionMaxKeptDesc')}
+          class="w-28"
+        >
+          <CountInput key="sessionMaxKept" />
+        </FormField>
+      </Show>
     </div>
   )
 }
@@ -69,12 +162,25 @@ export const AUTO_UPLOAD_PLUGIN: PluginDefinition<'autoUpload'> = {
     id: 'autoUpload',
     nameKey: 'plugin.autoUpload.name',
     descriptionKey: 'plugin.autoUpload.description',
-    version: '1.2.0',
+    version: '1.3.0',
     author: 'BUILTIN',
     links: []
   },
   metaKey: 'autoUpload',
-  configDefaults: { maxKept: 20, retentionScope: 'both' },
+  configDefaults: {
+    maxKept: 20,
+    keepDaily: 0,
+    keepWeekly: 0,
+    keepMonthly: 0,
+    retentionScope: 'both',
+    hashContents: false,
+    pullBeforeLaunch: false,
+    sessionSnapshots: false,
+    sessionQuietSecs: 30,
+    sessionIntervalMins: 0,
+    sessionMaxSnapshots: 10,
+    sessionMaxKept: 10
+  },
   MetaEditor: AutoAddMetaEditor,
   GameEditor: AutoUploadGameConfigEditor
 }
@@ -0,0 +1,144 @@
+import type { ConfigBackup } from '@bindings/ConfigBackup'
+import { myToast } from '@components/ui/myToast'
+import { invoke } from '@tauri-apps/api/core'
+import { formatBytes } from '@utils/file'
+import { useI18n } from '~/i18n'
+import {
+  TbOutlineArrowBackUp,
+  TbOutlineFileDiff,
+  TbOutlineHistory,
+  TbOutlineX
+} from 'solid-icons/tb'
+import { createSignal, For, onMount, Show } from 'solid-js'
+import toast from 'solid-toast'
+
+const iconButton =
+  'p-1.5 rounded-md hover:bg-gray-200 dark:hover:bg-gray-700 text-gray-500 dark:text-gray-400 transition-colors cursor-pointer'
+
+/** 本地配置的备份：查看与当前配置的差异、恢复 */
+export function ConfigBackupsModal(props: { onClose: () => void }) {
+  const { t } = useI18n()
+  const [backups, setBackups] = createSignal<ConfigBackup[]>([])
+  const [loading, setLoading] = createSignal(false)
+  const [diff, setDiff] = createSignal<{ name: string; text: string } | null>(null)
+
+  const loadFailed = (e: unknown) =>
+    toast.error(t('hint.failToLoadLocalConfig') + ': ' + e)
+
+  onMount(async () => {
+    setLoading(true)
+    try {
+      setBackups(await invoke<ConfigBackup[]>('list_config_backups'))
+    } catch (e) {
+      loadFailed(e)
+    }
+    setLoading(false)
+  })
+
+  const handleDiff = async (name: string) => {
+    if (diff()?.name === name) return setDiff(null)
+    try {
+      const text = await invoke<string>('diff_config_backup', { name })
+      setDiff({ name, text: text || t('settings.config.snapshotSame') })
+    } catch (e) {
+      loadFailed(e)
+    }
+  }
+
+  const restore = async (name: string) => {
+    const toastId = toast.loading(t('hint.restoringBackup') + name)
+    try {
+      await invoke('restore_config_backup', { name })
+      toast.success(t('hint.backupRestored') + name, { id: toastId })
+      props.onClose()
+    } catch (e) {
+      toast.error(t('hint.restoreBackupFailed') + e, { id: toastId })
+    }
+  }
+
+  const handleRestore = (name: string) =>
+    myToast({
+      variant: 'warning',
+      title: t('settings.config.restoreBackup'),
+      message: t('settings.config.restoreBackupDesc') + name,
+      actions: [
+        { label: t('ui.cancel'), variant: 'secondary', onClick: () => {} },
+        { label: t('ui.confirm'), variant: 'danger', onClick: () => restore(name) }
+      ]
+    })
+
+  return (
+    <div class="flex flex-col w-[90vw] max-w-2xl h-[80vh] bg-white dark:bg-gray-800 rounded-xl shadow-2xl overflow-hidden border border-gray-200 dark:border-gray-700">
+      <div class="flex justify-between items-center px-5 py-4 border-b border-gray-200 dark:border-gray-700 bg-gray-50 dark:bg-gray-800/50 flex-shrink-0">
+        <h2 class="text-lg font-bold text-gray-900 dark:text-white">
+          {t('settings.config.backups')}
+        </h2>
+        <button onClick={props.onClose} class={iconButton}>
+          <TbOutlineX class="w-5 h-5" />
+        </button>
+      </div>
+
+      <div class="flex-1 overflow-y-auto custom-scrollbar p-2 min-h-0">
+        <Show
+          when={!loading()}
+          fallback={
+            <div class="flex items-center justify-center h-full text-gray-500 dark:text-gray-400 text-sm">
+              {t('ui.loading')}
+            </div>
+          }
+        >
+          <Show
+            when={backups().length > 0}
+            fallback={
+              <div class="flex flex-col items-center justify-center h-full text-gray-400 dark:text-gray-500 gap-2">
+                <TbOutlineHistory class="w-8 h-8 opacity-50" />
+                <span class="text-sm">{t('settings.config.noBackup')}</span>
+              </div>
+            }
+          >
+            <For each={backups()}>
+              {item => (
+                <div class="p-3 rounded-lg hover:bg-gray-100 dark:hover:bg-gray-700/50">
+                  <div class="flex items-center justify-between gap-4">
+                    <div class="min-w-0">
+                      <div class="text-sm font-medium text-gray-700 dark:text-gray-200">
+                        {item.createdAt.replace('T', ' ').slice(0, 19)}
+                        <span class="ml-2 text-xs font-normal text-gray-500 dark:text-gray-400">
+                          {t(`settings.config.backupReason.${item.reason}`)}
+                        </span>
+                      </div>
+                      <div class="text-[10px] text-gray-400 dark:text-gray-500 truncate">
+                        {item.name} · {formatBytes(item.size)}
+                      </div>
+                    </div>
+                    <div class="flex items-center gap-1 flex-shrink-0">
+                      <button
+                        onClick={() => handleDiff(item.name)}
+                        class={iconButton}
+                        title={t('settings.config.diffSnapshot')}
+                      >
+                        <TbOutlineFileDiff class="w-4 h-4" />
+                      </button>
+                      <button
+                        onClick={() => handleRestore(item.name)}
+                        class={iconButton}
+                        title={t('settings.config.restoreBackup')}
+                      >
+                        <TbOutlineArrowBackUp class="w-4 h-4" />
+                      </button>
+                    </div>
+                  </div>
+                  <Show when={diff()?.name === item.name}>
+                    <pre class="mt-2 max-h-64 overflow-auto rounded bg-gray-100 dark:bg-gray-900 p-2 text-xs font-mono text-gray-700 dark:text-gray-300">
+                      {diff()?.text}
+                    </pre>
+                  </Show>
+                </div>
+              )}
+            </For>
+          </Show>
+        </Show>
+      </div>
+    </div>
+  )
+}
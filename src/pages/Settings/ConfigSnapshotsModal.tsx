@@ -0,0 +1,159 @@
+import type { ConfigSnapshot } from '@bindings/ConfigSnapshot'
+import { myToast } from '@components/ui/myToast'
+import { invoke } from '@tauri-apps/api/core'
+import { formatBytes } from '@utils/file'
+import { useI18n } from '~/i18n'
+import {
+  TbOutlineArrowBackUp,
+  TbOutlineFileDiff,
+  TbOutlineHistory,
+  TbOutlineTrashX,
+  TbOutlineX
+} from 'solid-icons/tb'
+import { createSignal, For, onMount, Show } from 'solid-js'
+import toast from 'solid-toast'
+
+const iconButton =
+  'p-1.5 rounded-md hover:bg-gray-200 dark:hover:bg-gray-700 text-gray-500 dark:text-gray-400 transition-colors cursor-pointer'
+
+/** 远程配置的每日快照：查看与本地配置的差异、恢复、清理 */
+export function ConfigSnapshotsModal(props: { onClose: () => void }) {
+  const { t } = useI18n()
+  const [snapshots, setSnapshots] = createSignal<ConfigSnapshot[]>([])
+  const [loading, setLoading] = createSignal(false)
+  const [diff, setDiff] = createSignal<{ name: string; text: string } | null>(null)
+
+  const fetchData = async () => {
+    setLoading(true)
+    try {
+      setSnapshots(await invoke<ConfigSnapshot[]>('list_config_snapshots'))
+    } catch (e) {
+      toast.error(t('hint.syncFailed') + e)
+    }
+    setLoading(false)
+  }
+  onMount(fetchData)
+
+  const handleDiff = async (name: string) => {
+    if (diff()?.name === name) return setDiff(null)
+    try {
+      const text = await invoke<string>('diff_config_snapshot', { name })
+      setDiff({ name, text: text || t('settings.config.snapshotSame') })
+    } catch (e) {
+      toast.error(t('hint.syncFailed') + e)
+    }
+  }
+
+  const restore = async (name: string) => {
+    const toastId = toast.loading(t('hint.restoringSnapshot') + name)
+    try {
+      await invoke('restore_config_snapshot', { name })
+      toast.success(t('hint.snapshotRestored') + name, { id: toastId })
+      props.onClose()
+    } catch (e) {
+      toast.error(t('hint.restoreSnapshotFailed') + e, { id: toastId })
+    }
+  }
+
+  const handleRestore = (name: string) =>
+    myToast({
+      variant: 'warning',
+      title: t('settings.config.restoreSnapshot'),
+      message: t('settings.config.restoreSnapshotDesc') + name,
+      actions: [
+        { label: t('ui.cancel'), variant: 'secondary', onClick: () => {} },
+        { label: t('ui.confirm'), variant: 'danger', onClick: () => restore(name) }
+      ]
+    })
+
+  const handlePrune = async () => {
+    try {
+      const pruned = await invoke<string[]>('prune_config_snapshots')
+      toast.success(t('hint.snapshotsPruned', { n: String(pruned.length) }))
+    } catch (e) {
+      toast.error(t('hint.syncFailed') + e)
+    }
+    fetchData()
+  }
+
+  return (
+    <div class="flex flex-col w-[90vw] max-w-2xl h-[80vh] bg-white dark:bg-gray-800 rounded-xl shadow-2xl overflow-hidden border border-gray-200 dark:border-gray-700">
+      <div class="flex justify-between items-center px-5 py-4 border-b border-gray-200 dark:border-gray-700 bg-gray-50 dark:bg-gray-800/50 flex-shrink-0">
+        <h2 class="text-lg font-bold text-gray-900 dark:text-white">
+          {t('settings.config.snapshots')}
+        </h2>
+        <div class="flex items-center gap-1">
+          <button
+            onClick={handlePrune}
+            class={iconButton}
+            title={t('settings.config.pruneSnapshots')}
+          >
+            <TbOutlineTrashX class="w-5 h-5" />
+          </button>
+          <button onClick={props.onClose} class={iconButton}>
+            <TbOutlineX class="w-5 h-5" />
+          </button>
+        </div>
+      </div>
+
+      <div class="flex-1 overflow-y-auto custom-scrollbar p-2 min-h-0">
+        <Show
+          when={!loading()}
+          fallback={
+            <div class="flex items-center justify-center h-full text-gray-500 dark:text-gray-400 text-sm">
+              {t('ui.loading')}
+            </div>
+          }
+        >
+          <Show
+            when={snapshots().length > 0}
+            fallback={
+              <div class="flex flex-col items-center justify-center h-full text-gray-400 dark:text-gray-500 gap-2">
+                <TbOutlineHistory class="w-8 h-8 opacity-50" />
+                <span class="text-sm">{t('settings.config.noSnapshot')}</span>
+              </div>
+            }
+          >
+            <For each={snapshots()}>
+              {item => (
+                <div class="p-3 rounded-lg hover:bg-gray-100 dark:hover:bg-gray-700/50">
+                  <div class="flex items-center justify-between gap-4">
+                    <div class="min-w-0">
+                      <div class="text-sm font-medium text-gray-700 dark:text-gray-200">
+                        {item.date}
+                      </div>
+                      <div class="text-[10px] text-gray-400 dark:text-gray-500 truncate">
+                        {item.name} · {formatBytes(item.size)}
+                      </div>
+                    </div>
+                    <div class="flex items-center gap-1 flex-shrink-0">
+                      <button
+                        onClick={() => handleDiff(item.name)}
+                        class={iconButton}
+                        title={t('settings.config.diffSnapshot')}
+                      >
+                        <TbOutlineFileDiff class="w-4 h-4" />
+                      </button>
+                      <button
+                        onClick={() => handleRestore(item.name)}
+                        class={iconButton}
+                        title={t('settings.config.restoreSnapshot')}
+                      >
+                        <TbOutlineArrowBackUp class="w-4 h-4" />
+                      </button>
+                    </div>
+                  </div>
+                  <Show when={diff()?.name === item.name}>
+                    <pre class="mt-2 max-h-64 overflow-auto rounded bg-gray-100 dark:bg-gray-900 p-2 text-xs font-mono text-gray-700 dark:text-gray-300">
+                      {diff()?.text}
+                    </pre>
+                  </Show>
+                </div>
+              )}
+            </For>
+          </Show>
+        </Show>
+      </div>
+    </div>
+  )
+}
@@ -4,13 +4,15 @@ import type { S3Config } from '@bindings/S3Config'
 import type { StorageProvider } from '@bindings/StorageProvider'
 import type { WebDavConfig } from '@bindings/WebDavConfig'
 import { FieldHint } from '@components/ui/FieldHint'
+import FullScreenMask from '@components/ui/FullScreenMask'
 import {
   Button,
   Input,
   Select,
   SettingRow,
   SettingSection,
-  SettingSubGroup
+  SettingSubGroup,
+  SwitchToggle
 } from '@components/ui/settings'
 import { invoke } from '@tauri-apps/api/core'
 import { debounce } from '@utils/debounce'
@@ -18,12 +20,24 @@ import { useVarWarning } from '@utils/useVarWarning'
 import { useI18n } from '~/i18n'
 import { checkAndPullRemote, performManualUpload, useConfig } from '~/store'
 import { FiDownload, FiLoader, FiUpload } from 'solid-icons/fi'
-import { createMemo, createSignal, Match, Show, Switch, type Component } from 'solid-js'
+import {
+  createMemo,
+  createSignal,
+  Match,
+  onMount,
+  Show,
+  Switch,
+  type Component
+} from 'solid-js'
+import toast from 'solid-toast'
+import { ConfigBackupsModal } from './ConfigBackupsModal'
+import { ConfigSnapshotsModal } from './ConfigSnapshotsModal'
 
 const COMPRESSION_RULES: Record<string, { min: number; max: number; disabled: boolean }> =
   {
     squashfsZstd: { min: 1, max: 22, disabled: false }, // Zstd 通常 1-22
-    tar: { min: 0, max: 0, disabled: true } // Tar 通常仅归档不压缩，禁用等级
+    tar: { min: 0, max: 0, disabled: true }, // Tar 通常仅归档不压缩，禁用等级
+    snapshot: { min: 0, max: 0, disabled: true } // 分块去重，不压缩
   }
 
 // --- 子组件：WebDAV 表单 ---
@@ -206,7 +220,8 @@ const CompressionForm: Component<{
           }
           options={[
             { label: 'Squashfs + Zstd', value: 'squashfsZstd' },
-            { label: 'tar', value: 'tar' }
+            { label: 'tar', value: 'tar' },
+            { label: 'Snapshot (dedup)', value: 'snapshot' }
           ]}
         />
       </SettingRow>
@@ -283,6 +298,19 @@ export const StorageTab: Component = () => {
     await performManualUpload(t)
     setUploading(false)
   }
+  // 本地凭据是否用系统钥匙串中的密钥加密
+  const [secretsEncrypted, setSecretsEncrypted] = createSignal(false)
+  onMount(async () => setSecretsEncrypted(await invoke<boolean>('secrets_encrypted')))
+  const handleSecretsEncrypted = async (encrypted: boolean) => {
+    try {
+      await invoke('set_secrets_encrypted', { encrypted })
+      setSecretsEncrypted(encrypted)
+    } catch (e) {
+      toast.error(t('hint.setSecretsEncryptedFailed') + e)
+    }
+  }
+  const [showSnapshots, setShowSnapshots] = createSignal(false)
+  const [showBackups, setShowBackups] = createSignal(false)
   const [downloading, setDownloading] = createSignal(false)
   const handleDownloadConfig = async () => {
     setDownloading(true)
@@ -353,6 +381,39 @@ export const StorageTab: Component = () => {
             placeholder="15"
           />
         </SettingRow>
+        <SettingRow
+          label={t('settings.storage.encryption')}
+          description={t('settings.storage.encryptionDesc')}
+        >
+          <SwitchToggle
+            checked={config.settings.storage.encryption.enabled}
+            onChange={e => {
+              actions.updateSettings(s => (s.storage.encryption.enabled = e))
+              invoke('clean_current_operator')
+            }}
+          />
+        </SettingRow>
+        <Show when={config.settings.storage.encryption.enabled}>
+          <SettingRow label={t('settings.storage.passphrase')} indent>
+            <Input
+              type="password"
+              value={config.settings.storage.encryption.passphrase || ''}
+              onChange={e => {
+                const value = e.currentTarget.value
+                actions.updateSettingsDebounced(
+                  s => (s.storage.encryption.passphrase = value || null)
+                )
+                cleanOperatorDebounced()
+              }}
+            />
+          </SettingRow>
+        </Show>
+        <SettingRow
+          label={t('settings.storage.encryptSecrets')}
+          description={t('settings.storage.encryptSecretsDesc')}
+        >
+          <SwitchToggle checked={secretsEncrypted()} onChange={handleSecretsEncrypted} />
+        </SettingRow>
       </SettingSection>
 
       <CompressionForm config={config.settings.archive} actions={actions} />
@@ -372,6 +433,77 @@ export const StorageTab: Component = () => {
             placeholder={t('settings.config.autoSyncIntervalPlaceholder')}
           />
         </SettingRow>
+        <SettingRow
+          label={t('settings.config.syncOnStartup')}
+          description={t('settings.config.syncOnStartupDesc')}
+        >
+          <SwitchToggle
+            checked={config.settings.autoSync.onStartup}
+            onChange={e => actions.updateSettings(s => (s.autoSync.onStartup = e))}
+          />
+        </SettingRow>
+        <SettingRow
+          label={t('settings.config.syncOnMinimize')}
+          description={t('settings.config.syncOnMinimizeDesc')}
+        >
+          <SwitchToggle
+            checked={config.settings.autoSync.onMinimize}
+            onChange={e => actions.updateSettings(s => (s.autoSync.onMinimize = e))}
+          />
+        </SettingRow>
+        <SettingRow
+          label={t('settings.config.syncOnGameExit')}
+          description={t('settings.config.syncOnGameExitDesc')}
+        >
+          <SwitchToggle
+            checked={config.settings.autoSync.onGameExit}
+            onChange={e => actions.updateSettings(s => (s.autoSync.onGameExit = e))}
+          />
+        </SettingRow>
+        <SettingRow
+          label={t('settings.config.syncOnQuit')}
+          description={t('settings.config.syncOnQuitDesc')}
+        >
+          <SwitchToggle
+            checked={config.settings.autoSync.onQuit}
+            onChange={e => actions.updateSettings(s => (s.autoSync.onQuit = e))}
+          />
+        </SettingRow>
+        <SettingRow
+          label={t('settings.config.splitLayout')}
+          description={t('settings.config.splitLayoutDesc')}
+        >
+          <SwitchToggle
+            checked={config.settings.storage.layout === 'split'}
+            onChange={e =>
+              actions.updateSettings(s => (s.storage.layout = e ? 'split' : 'single'))
+            }
+          />
+        </SettingRow>
+        <SettingRow
+          label={t('settings.config.snapshotRetention')}
+          description={t('settings.config.snapshotRetentionDesc')}
+        >
+          <Input
+            value={config.settings.configSnapshotRetention}
+            onChange={e =>
+              actions.updateSettingsDebounced(
+                s => (s.configSnapshotRetention = parseInt(e.currentTarget.value) || 0)
+              )
+            }
+          />
+          <Button onClick={() => setShowSnapshots(true)} class="mx-1">
+            {t('ui.browse')}
+          </Button>
+        </SettingRow>
+        <SettingRow
+          label={t('settings.config.backups')}
+          description={t('settings.config.backupsDesc')}
+        >
+          <Button onClick={() => setShowBackups(true)} class="mx-1">
+            {t('ui.browse')}
+          </Button>
+        </SettingRow>
         <SettingRow
           label={t('settings.config.manualSync')}
           description={t('settings.config.forceOp')}
@@ -405,6 +537,17 @@ export const StorageTab: Component = () => {
           </Button>
         </SettingRow>
       </SettingSection>
+
+      <Show when={showSnapshots()}>
+        <FullScreenMask onClose={() => setShowSnapshots(false)}>
+          <ConfigSnapshotsModal onClose={() => setShowSnapshots(false)} />
+        </FullScreenMask>
+      </Show>
+      <Show when={showBackups()}>
+        <FullScreenMask onClose={() => setShowBackups(false)}>
+          <ConfigBackupsModal onClose={() => setShowBackups(false)} />
+        </FullScreenMask>
+      </Show>
     </div>
   )
 }
@@ -1,9 +1,13 @@
 // src/stores/configStore.ts
 import { type Config } from '@bindings/Config'
+import type { ConfigSyncEvent } from '@bindings/ConfigSyncEvent'
 import type { Device } from '@bindings/Device'
 import type { Game } from '@bindings/Game'
+import type { PluginInstance } from '@bindings/PluginInstance'
+import type { PluginMetadatas } from '@bindings/PluginMetadatas'
 import type { Settings } from '@bindings/Settings'
 import type { UploadConfigStatus } from '@bindings/UploadConfigStatus'
+import type { VersionVector } from '@bindings/VersionVector'
 import { myToast, type ToastVariant } from '@components/ui/myToast'
 import * as i18n from '@solid-primitives/i18n'
 import { invoke } from '@tauri-apps/api/core'
@@ -73,6 +77,8 @@ const DEFAULT_CONFIG: Config = {
   dbVersion: 0,
   lastUpdated: new Date().toISOString(),
   lastSync: null,
+  revision: {},
+  syncedRevision: {},
   lastUploaded: null,
   games: [],
   devices: [],
@@ -92,12 +98,18 @@ const DEFAULT_CONFIG: Config = {
         endpoint: '',
         accessKey: '',
         secretKey: ''
-      }
+      },
+      encryption: {
+        enabled: false,
+        passphrase: null
+      },
+      layout: 'single'
     },
     archive: {
       algorithm: 'squashfsZstd',
       level: 3,
-      backupBeforeRestore: true
+      backupBeforeRestore: true,
+      mirrorRestore: false
     },
     appearance: {
       theme: 'system',
@@ -112,8 +124,15 @@ const DEFAULT_CONFIG: Config = {
       precisionMode: true
     },
     autoSyncInterval: 1200,
+    autoSync: {
+      onStartup: true,
+      onMinimize: true,
+      onGameExit: false,
+      onQuit: true
+    },
     syncIoTimeoutSecs: 60,
-    syncNonIoTimeoutSecs: 15
+    syncNonIoTimeoutSecs: 15,
+    configSnapshotRetention: 30
   },
   pluginMetadatas: {
     execute: {
@@ -133,7 +152,17 @@ const DEFAULT_CONFIG: Config = {
       autoAdd: false,
       configDefaults: {
         maxKept: 20,
-        retentionScope: 'both'
+        keepDaily: 0,
+        keepWeekly: 0,
+        keepMonthly: 0,
+        retentionScope: 'both',
+        hashContents: false,
+        pullBeforeLaunch: false,
+        sessionSnapshots: false,
+        sessionQuietSecs: 30,
+        sessionIntervalMins: 0,
+        sessionMaxSnapshots: 10,
+        sessionMaxKept: 10
       }
     },
     gameWrapper: {
@@ -200,6 +229,7 @@ export const useConfigInit = (t?: i18n.Translator<Dictionary>) => {
   onMount(() => {
     let unlisten: (() => void) | undefined
     let unlistenToast: (() => void) | undefined
+    let unlistenSync: (() => void) | undefined
     let mounted = true
 
     const init = async () => {
@@ -211,6 +241,13 @@ export const useConfigInit = (t?: i18n.Translator<Dictionary>) => {
           return
         }
         unlistenToast = toastFn
+
+        const syncFn = await startConfigSyncListener(t)
+        if (!mounted) {
+          syncFn()
+          return
+        }
+        unlistenSync = syncFn
       }
 
       // 1. 监听 Rust 端的主动推送
@@ -237,6 +274,7 @@ export const useConfigInit = (t?: i18n.Translator<Dictionary>) => {
       mounted = false
       unlisten?.()
       unlistenToast?.()
+      unlistenSync?.()
     })
   })
 }
@@ -251,6 +289,86 @@ const refreshConfig = async () => {
   }
 }
 
+// 弹出带撤回按钮的 Toast
+const showAppliedConfig = (
+  t: i18n.Translator<Dictionary>,
+  oldConfig: Config,
+  message: string
+) => {
+  myToast({
+    variant: 'success',
+    title: t('hint.syncSuccess'),
+    message,
+    actions: [
+      {
+        label: t('ui.withdraw'),
+        variant: 'secondary',
+        onClick: async () => {
+          // 恢复旧配置到磁盘，保留之后写入的游戏时长
+          const baseRevision = structuredClone(unwrap(config.revision))
+          setConfig(reconcile(oldConfig))
+          if (await edit('restore_previous_config', { previous: oldConfig }, baseRevision)) {
+            toast.success(t('hint.restorePreviousConfigSuccess'))
+          }
+        }
+      }
+    ]
+  })
+}
+
+// ── Background config sync ──────────────────────────────────────────────────
+
+let lastConfigSyncId = 0
+
+/** Show the outcome of a config sync run in the background by the backend. */
+const showConfigSync = (t: i18n.Translator<Dictionary>, event: ConfigSyncEvent) => {
+  // the last sync may be fetched and emitted at the same time
+  if (event.id <= lastConfigSyncId) return
+  lastConfigSyncId = event.id
+
+  const { outcome } = event
+  switch (outcome.kind) {
+    case 'applied':
+      showAppliedConfig(t, outcome.previous, t('hint.appliedNewConfig'))
+      break
+    case 'upToDate':
+      toast.success(t('hint.localIsTheNewest'))
+      break
+    case 'uploaded':
+    case 'merged':
+      toast.success(t('hint.configAutoUploadSuccess'))
+      break
+    case 'conflict':
+      toast.error(t('hint.configUploadConflict'))
+      break
+    case 'failed': {
+      const hint =
+        event.trigger === 'startup'
+          ? t('hint.checkRemoteConfigFailed')
+          : t('hint.configAutoUploadFailed')
+      toast.error(hint + ': ' + outcome.error)
+      break
+    }
+  }
+}
+
+/**
+ * Start listening for `sync://config` events. The startup sync may finish
+ * before the webview listens, so the last sync is fetched as well.
+ */
+const startConfigSyncListener = async (t: i18n.Translator<Dictionary>) => {
+  const unlisten = await listen<ConfigSyncEvent>('sync://config', event => {
+    showConfigSync(t, event.payload)
+  })
+  try {
+    const last = await invoke<ConfigSyncEvent | null>('last_config_sync')
+    if (last) showConfigSync(t, last)
+  } catch (e) {
+    log.error(`Failed to get last config sync: ${e}`)
+  }
+  return unlisten
+}
+
 // 核心逻辑：拉取远端并提供撤回
 export const checkAndPullRemote = async (
   t: i18n.Translator<Dictionary>,
@@ -272,26 +390,11 @@ export const checkAndPullRemote = async (
       return
     }
     if (oldConfig) {
-      // 弹出带撤回按钮的 Toast
-      myToast({
-        variant: 'success',
-        title: t('hint.syncSuccess'),
-        message: skipCheck ? t('hint.forceUpdatedConfig') : t('hint.appliedNewConfig'),
-        actions: [
-          {
-            label: t('ui.withdraw'),
-            variant: 'secondary',
-            onClick: () => {
-              setConfig(reconcile(oldConfig))
-              // 恢复旧配置到磁盘
-              ;(async () => {
-                invoke('save_config', { newConfig: oldConfig })
-                toast.success(t('hint.restorePreviousConfigSuccess'))
-              })()
-            }
-          }
-        ]
-      })
+      showAppliedConfig(
+        t,
+        oldConfig,
+        skipCheck ? t('hint.forceUpdatedConfig') : t('hint.appliedNewConfig')
+      )
     } else {
       toast.success(t('hint.localIsTheNewest'))
     }
@@ -303,25 +406,11 @@ export const checkAndPullRemote = async (
   }
 }
 
-export const performAutoUpload = async (t: i18n.Translator<Dictionary>) => {
-  log.info('[ConfigAutoUpload] Triggered')
-  try {
-    const res = await invoke<UploadConfigStatus>('upload_config', { safe: true })
-    if (res === 'uploaded') {
-      toast.success(t('hint.configAutoUploadSuccess'))
-    } else if (res === 'conflict') {
-      toast.error(t('hint.configUploadConflict'))
-    }
-  } catch (e) {
-    toast.error(t('hint.configAutoUploadFailed') + ': ' + e)
-  }
-}
-
 export const performManualUpload = async (t: i18n.Translator<Dictionary>) => {
   log.info('[ConfigManualUpload] Triggered')
   try {
     const res = await invoke<UploadConfigStatus>('upload_config', { safe: false })
-    if (res === 'uploaded') {
+    if (res === 'uploaded' || res === 'merged') {
       toast.success(t('hint.configUploadSuccess'))
     } else if (res === 'conflict') {
       toast.error(t('hint.configUploadConflict'))
@@ -331,30 +420,86 @@ export const performManualUpload = async (t: i18n.Translator<Dictionary>) => {
   }
 }
 
-// 用户触发的保存操作
-const save = async () => {
-  try {
-    console.log('save invoked')
-    await invoke('save_config', { newConfig: unwrap(config) })
-  } catch (e) {
-    toast.error(`Failed to save config: ${e}`)
-  }
+// ── Config edits ────────────────────────────────────────────────────────────
+
+// Edits are sent to the backend one at a time, each with the revision of the
+// config it was made on. The backend refuses an edit if the config changed in
+// the meantime (e.g. a synced remote config was applied), instead of
+// overwriting it; the local copy is then reloaded. Backend-only writes such as
+// the play time of a running game do not count as changes.
+const sameRevision = (a: VersionVector, b: VersionVector) => {
+  const keys = Object.keys(a)
+  return keys.length === Object.keys(b).length && keys.every(k => a[k] === b[k])
+}
+let pendingEdit: Promise<void> = Promise.resolve()
+// Edits queued behind our own last edit were made on the revision it replaced
+let lastEdit: { from: VersionVector; to: VersionVector } | undefined
+const edit = (
+  cmd: string,
+  args: Record<string, unknown>,
+  baseRevision: VersionVector = structuredClone(unwrap(config.revision))
+) => {
+  const saved = pendingEdit.then(async () => {
+    const expectedRevision =
+      lastEdit && sameRevision(baseRevision, lastEdit.from) ? lastEdit.to : baseRevision
+    try {
+      const revision = await invoke<VersionVector>(cmd, { ...args, expectedRevision })
+      lastEdit = { from: expectedRevision, to: revision }
+      setConfig('revision', reconcile(revision))
+      return true
+    } catch (e) {
+      toast.error(`Failed to save config: ${e}`)
+      await refreshConfig()
+      return false
+    }
+  })
+  pendingEdit = saved.then(() => {})
+  return saved
 }
 
+// 用户触发的保存操作，整体替换配置；尽量使用上面的细粒度修改
+const save = () => edit('save_config', { newConfig: unwrap(config) })
+
 // Debounced config persistence: coalesces rapid mutations (e.g. typing in a
 // settings text field) into a single disk write instead of one per keystroke.
 // Per the project rule, config writes must not happen in frequent callbacks
-// such as an input's onChange.
-let saveDebounceTimer: ReturnType<typeof setTimeout> | undefined
+// such as an input's onChange. Each kind of edit has its own timer, and reads
+// the store when it fires.
+const debounceTimers = new Map<string, ReturnType<typeof setTimeout>>()
 const SAVE_DEBOUNCE_MS = 500
-const scheduleSave = () => {
-  if (saveDebounceTimer) clearTimeout(saveDebounceTimer)
-  saveDebounceTimer = setTimeout(() => {
-    saveDebounceTimer = undefined
-    void save()
-  }, SAVE_DEBOUNCE_MS)
+const debounced = (key: string, fn: () => void) => {
+  clearTimeout(debounceTimers.get(key))
+  debounceTimers.set(
+    key,
+    setTimeout(() => {
+      debounceTimers.delete(key)
+      fn()
+    }, SAVE_DEBOUNCE_MS)
+  )
+}
+const scheduleSave = () => debounced('save_config', save)
+
+const saveSettings = () => edit('update_settings', { settings: unwrap(config.settings) })
+
+const saveDevice = (uid: string) => {
+  const device = config.devices.find(d => d.uid === uid)
+  if (device) edit('update_device', { device: unwrap(device) })
 }
 
+const upsertDevice = (uid: string, device: Device) =>
+  setConfig(
+    produce(state => {
+      const index = state.devices.findIndex(d => d.uid === uid)
+      if (index !== -1) {
+        state.devices[index] = device
+      }
+      // 如果没有找到，则添加
+      else {
+        state.devices.push(device)
+      }
+    })
+  )
+
 export const useConfig = () => {
   return {
     config,
@@ -362,32 +507,51 @@ export const useConfig = () => {
     save,
     saveDebounced: scheduleSave,
     actions: {
-      addGame: (game: Game) => {
-        game.addedTime = new Date().toISOString()
+      /** The backend assigns the id and attaches the autoAdd plugins. */
+      addGame: (game: Game) => edit('add_game', { game: unwrap(game) }),
+      removeGame: (index: number) => {
+        const id = config.games[index]?.id
+        if (id === undefined) return
         setConfig(
           produce(state => {
-            state.games.push(unwrap(game))
+            state.games.splice(index, 1)
           })
         )
-        save()
+        edit('delete_game', { gameId: id })
       },
-      removeGame: (index: number) => {
+      /** `baseRevision` is the revision `game` was read at, if not the current
+       *  one (e.g. when an edit modal was opened). */
+      replaceGame: (index: number, game: Game, baseRevision?: VersionVector) => {
+        if (!config.games[index]) return
+        const gameUnwrap = unwrap(game)
         setConfig(
           produce(state => {
-            state.games.splice(index, 1)
+            state.games[index] = gameUnwrap
           })
         )
-        save()
+        edit('update_game', { game: gameUnwrap }, baseRevision)
       },
-      replaceGame: (index: number, game: Game) => {
+      /** Games not in `ids` keep their order after the listed ones. */
+      reorderGames: (ids: number[]) => {
         setConfig(
           produce(state => {
-            if (state.games[index]) {
-              state.games[index] = unwrap(game)
+            const rank = (id: number) => {
+              const i = ids.indexOf(id)
+              return i === -1 ? ids.length : i
             }
+            state.games.sort((a, b) => rank(a.id) - rank(b.id))
           })
         )
-        save()
+        edit('reorder_games', { gameIds: ids })
+      },
+      setGamePlugins: (gameId: number, plugins: PluginInstance[]) => {
+        setConfig(
+          produce(state => {
+            const game = state.games.find(g => g.id === gameId)
+            if (game) game.plugins = plugins
+          })
+        )
+        edit('set_game_plugins', { gameId, plugins })
       },
       updateDeviceVar: (deviceUid: string, key: string, value: string) => {
         setConfig(
@@ -398,17 +562,17 @@ export const useConfig = () => {
             }
           })
         )
-        save()
+        saveDevice(deviceUid)
       },
       updateSettings: (fn: (settings: Settings) => void) => {
         setConfig(produce(state => fn(state.settings)))
-        save()
+        saveSettings()
       },
       /** Like {@link updateSettings} but debounces the disk write — use this in
        *  frequent callbacks such as a text input's onChange. */
       updateSettingsDebounced: (fn: (settings: Settings) => void) => {
         setConfig(produce(state => fn(state.settings)))
-        scheduleSave()
+        debounced('update_settings', saveSettings)
       },
       getCurrentDevice: async (): Promise<Device | undefined> => {
         const uid = await currentDeviceId()
@@ -425,40 +589,21 @@ export const useConfig = () => {
       },
       updateCurrentDevice: async (device: Device) => {
         const uid = await currentDeviceId()
-        const deviceUnwrap = unwrap(device)
-        setConfig(
-          produce(state => {
-            const index = state.devices.findIndex(d => d.uid === uid)
-            if (index !== -1) {
-              state.devices[index] = deviceUnwrap
-            }
-            // 如果没有找到，则添加
-            else {
-              state.devices.push(deviceUnwrap)
-            }
-          })
-        )
-        save()
+        upsertDevice(uid, unwrap(device))
+        saveDevice(uid)
       },
       /** Like {@link updateCurrentDevice} but debounces the disk write. */
       updateCurrentDeviceDebounced: async (device: Device) => {
         const uid = await currentDeviceId()
-        const deviceUnwrap = unwrap(device)
-        setConfig(
-          produce(state => {
-            const index = state.devices.findIndex(d => d.uid === uid)
-            if (index !== -1) {
-              state.devices[index] = deviceUnwrap
-            } else {
-              state.devices.push(deviceUnwrap)
-            }
-          })
-        )
-        scheduleSave()
+        upsertDevice(uid, unwrap(device))
+        debounced('update_device', () => saveDevice(uid))
       },
-      mutate: (fn: (state: Config) => void) => {
-        setConfig(produce(fn))
-        save()
+      /** `fn` returns the new plugin metadata, e.g. from `patchPluginMeta`. */
+      updatePluginMetadatas: (fn: (metas: PluginMetadatas) => PluginMetadatas) => {
+        setConfig('pluginMetadatas', metas => fn(metas))
+        edit('update_plugin_metadatas', {
+          pluginMetadatas: unwrap(config.pluginMetadatas)
+        })
       }
     }
   }